@@ -0,0 +1,34 @@
+//! CPU cost of submitting and drawing 100k quads with each `QuadPath`.
+//!
+//! `cargo run --release -p font --example quad_path`
+use font::*;
+use glow::HasContext;
+
+fn main() {
+    let (width, height, _window, _events, _glfw, gl) = create_window();
+    let mut rd = Renderer::new(gl, width, height);
+
+    for path in [QuadPath::Vertices, QuadPath::Instanced] {
+        rd.reset();
+        rd.path = path;
+
+        //Roughly 100k glyphs worth of quads.
+        let now = std::time::Instant::now();
+        for i in 0..100_000 {
+            let (x, y) = ((i % 400) as f32 * 2.0, (i / 400) as f32 * 2.0);
+            rd.rect(
+                Rect::new(x, y, 8.0, 12.0),
+                Vec4::new(0.0, 0.0, 1.0, 1.0),
+                hex(0xdcdcaa),
+            );
+        }
+        let submit = now.elapsed();
+
+        let now = std::time::Instant::now();
+        rd.draw();
+        unsafe { gl.finish() };
+        let draw = now.elapsed();
+
+        println!("{path:?}: submit {submit:?}, draw {draw:?}");
+    }
+}
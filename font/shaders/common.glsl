@@ -0,0 +1,6 @@
+//Set by `Renderer::set_projection`.
+uniform mat4 projection;
+
+vec4 project(vec2 position) {
+    return projection * vec4(position, 0.0, 1.0);
+}
\ No newline at end of file
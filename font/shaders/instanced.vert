@@ -0,0 +1,28 @@
+#version 330 core
+
+//Corner of the unit quad, (0, 0) is the bottom left.
+layout(location = 0) in vec2 corner;
+layout(location = 1) in vec4 rect;
+//Texture coordinates of the bottom left and top right corners.
+layout(location = 2) in vec4 uv_rect;
+layout(location = 3) in vec4 color;
+layout(location = 4) in uint transform;
+
+#include "common.glsl"
+
+//Two texels per affine transform, (a, c, x) and (b, d, y).
+uniform sampler2D transforms;
+
+out vec4 out_color;
+out vec2 out_uv;
+
+void main() {
+    vec4 row0 = texelFetch(transforms, ivec2(0, int(transform)), 0);
+    vec4 row1 = texelFetch(transforms, ivec2(1, int(transform)), 0);
+    vec3 local = vec3(rect.xy + corner * rect.zw, 1.0);
+    vec2 position = vec2(dot(row0.xyz, local), dot(row1.xyz, local));
+
+    gl_Position = project(position);
+    out_color = color;
+    out_uv = mix(uv_rect.xy, uv_rect.zw, corner);
+}
\ No newline at end of file
@@ -8,5 +8,5 @@ out vec4 color;
 uniform sampler2D image;
 
 void main() {
-    color = texture(image, out_uv).x * out_color;
+    color = texture(image, out_uv) * out_color;
 }
\ No newline at end of file
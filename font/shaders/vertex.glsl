@@ -0,0 +1,4 @@
+//Matches `Vertex` and `Vertex::bind_layout`.
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
\ No newline at end of file
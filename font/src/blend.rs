@@ -0,0 +1,53 @@
+use crate::*;
+
+/// How new pixels are combined with the framebuffer.
+/// Every mode writes `src.a + dst.a * (1 - src.a)` to alpha, so translucent render targets
+/// end up premultiplied and should be drawn with `Premultiplied`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Straight alpha, `src * src.a + dst * (1 - src.a)`.
+    #[default]
+    Alpha,
+    /// For textures uploaded with `TextureOptions::premultiply`, `src + dst * (1 - src.a)`.
+    /// Vertex colors are premultiplied for you while this mode is recorded.
+    Premultiplied,
+    /// `src * src.a + dst`, for glows and particles.
+    Additive,
+    /// `src * dst`, darkens. Expects opaque or premultiplied sources.
+    Multiply,
+    /// `src + dst * (1 - src)`, lightens.
+    Screen,
+    /// Blending disabled, `src` overwrites `dst`.
+    Replace,
+}
+
+impl BlendMode {
+    pub fn apply(self, gl: &glow::Context) {
+        let (src, dst) = match self {
+            BlendMode::Alpha => (glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Premultiplied => (glow::ONE, glow::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (glow::SRC_ALPHA, glow::ONE),
+            BlendMode::Multiply => (glow::DST_COLOR, glow::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Screen => (glow::ONE, glow::ONE_MINUS_SRC_COLOR),
+            BlendMode::Replace => {
+                unsafe { gl.disable(glow::BLEND) };
+                return;
+            }
+        };
+
+        unsafe {
+            gl.enable(glow::BLEND);
+            gl.blend_equation(glow::FUNC_ADD);
+            gl.blend_func_separate(src, dst, glow::ONE, glow::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+}
+
+pub fn premultiply(color: Vec4) -> Vec4 {
+    Vec4::new(
+        color.x * color.w,
+        color.y * color.w,
+        color.z * color.w,
+        color.w,
+    )
+}
@@ -0,0 +1,165 @@
+use crate::*;
+use std::ops::Range;
+
+/// Rounded corners are split into this many triangles.
+const CORNER_SEGMENTS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clip {
+    /// Axis-aligned clips use `glScissor`.
+    Rect(Rect),
+    /// Any other shape is drawn into the stencil buffer.
+    /// The range points to the mask triangles inside `Renderer::vertices`.
+    Stencil(Range<usize>),
+}
+
+/// Clip state while replaying commands inside of `Renderer::draw`.
+#[derive(Debug, Default)]
+pub struct ClipStack {
+    pub stack: Vec<Clip>,
+    /// Number of stencil clips on the stack.
+    /// Pixels inside every stencil clip will have this value in the stencil buffer.
+    pub depth: i32,
+}
+
+impl ClipStack {
+    /// `base` is the index of the first vertex inside of the vertex buffer.
+    pub fn push(&mut self, gl: &glow::Context, clip: Clip, base: usize) {
+        unsafe {
+            match &clip {
+                Clip::Rect(_) => {}
+                Clip::Stencil(range) => {
+                    gl.enable(glow::STENCIL_TEST);
+                    gl.color_mask(false, false, false, false);
+                    //Only increment pixels that are inside of the parent clip.
+                    gl.stencil_func(glow::EQUAL, self.depth, 0xFF);
+                    gl.stencil_op(glow::KEEP, glow::KEEP, glow::INCR);
+                    gl.draw_arrays(
+                        glow::TRIANGLES,
+                        (base + range.start) as i32,
+                        range.len() as i32,
+                    );
+                    gl.color_mask(true, true, true, true);
+                    self.depth += 1;
+                }
+            }
+            self.stack.push(clip);
+            self.apply(gl);
+        }
+    }
+
+    pub fn pop(&mut self, gl: &glow::Context, base: usize) {
+        unsafe {
+            if let Clip::Stencil(range) = self.take() {
+                //Undo the increment so the parent clip is restored.
+                gl.color_mask(false, false, false, false);
+                gl.stencil_func(glow::EQUAL, self.depth + 1, 0xFF);
+                gl.stencil_op(glow::KEEP, glow::KEEP, glow::DECR);
+                gl.draw_arrays(
+                    glow::TRIANGLES,
+                    (base + range.start) as i32,
+                    range.len() as i32,
+                );
+                gl.color_mask(true, true, true, true);
+            }
+            self.apply(gl);
+        }
+    }
+
+    /// Removes the top clip and updates `depth` without touching any GL state.
+    pub fn take(&mut self) -> Clip {
+        let clip = self
+            .stack
+            .pop()
+            .expect("pop_clip was called without a matching push_clip");
+        if let Clip::Stencil(_) = clip {
+            self.depth -= 1;
+        }
+        clip
+    }
+
+    /// Intersection of every rectangle on the stack.
+    pub fn scissor(&self) -> Option<Rect> {
+        self.stack
+            .iter()
+            .filter_map(|clip| match clip {
+                Clip::Rect(rect) => Some(*rect),
+                Clip::Stencil(_) => None,
+            })
+            .reduce(|a, b| a.intersect(&b))
+    }
+
+    pub fn apply(&self, gl: &glow::Context) {
+        unsafe {
+            match self.scissor() {
+                Some(rect) => {
+                    gl.enable(glow::SCISSOR_TEST);
+                    gl.scissor(
+                        rect.x.floor() as i32,
+                        rect.y.floor() as i32,
+                        rect.width.ceil() as i32,
+                        rect.height.ceil() as i32,
+                    );
+                }
+                None => gl.disable(glow::SCISSOR_TEST),
+            }
+
+            if self.depth > 0 {
+                gl.enable(glow::STENCIL_TEST);
+                gl.stencil_func(glow::EQUAL, self.depth, 0xFF);
+                gl.stencil_op(glow::KEEP, glow::KEEP, glow::KEEP);
+            } else {
+                gl.disable(glow::STENCIL_TEST);
+            }
+        }
+    }
+
+    pub fn reset(&mut self, gl: &glow::Context) {
+        self.stack.clear();
+        self.depth = 0;
+        self.apply(gl);
+    }
+}
+
+/// Outline of a rounded rectangle in counter clockwise order.
+pub fn rounded_rect(x: f32, y: f32, w: f32, h: f32, radius: f32) -> Vec<Vec2> {
+    let r = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+    if r == 0.0 {
+        return vec![
+            Vec2::new(x, y),
+            Vec2::new(x + w, y),
+            Vec2::new(x + w, y + h),
+            Vec2::new(x, y + h),
+        ];
+    }
+
+    //Bottom right, top right, top left, bottom left.
+    let corners = [
+        (x + w - r, y + r, -0.5),
+        (x + w - r, y + h - r, 0.0),
+        (x + r, y + h - r, 0.5),
+        (x + r, y + r, 1.0),
+    ];
+
+    let mut points = Vec::with_capacity(corners.len() * (CORNER_SEGMENTS + 1));
+    for (cx, cy, start) in corners {
+        for i in 0..=CORNER_SEGMENTS {
+            let angle = (start + 0.5 * i as f32 / CORNER_SEGMENTS as f32) * std::f32::consts::PI;
+            points.push(Vec2::new(cx + r * angle.cos(), cy + r * angle.sin()));
+        }
+    }
+    points
+}
+
+/// Triangulates a convex polygon as a triangle fan.
+pub fn fan(points: &[Vec2]) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for i in 1..points.len().saturating_sub(1) {
+        vertices.extend([
+            vertex!(points[0]),
+            vertex!(points[i]),
+            vertex!(points[i + 1]),
+        ]);
+    }
+    vertices
+}
@@ -0,0 +1,311 @@
+use crate::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// RGBA color with components in 0..1.
+/// Colors are sRGB unless they came from `to_linear`, which is what the GPU should blend with.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::new(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+
+    pub fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    /// `0xRRGGBB`
+    pub fn hex(hex: u32) -> Self {
+        let [_, r, g, b] = hex.to_be_bytes();
+        Self::rgba8(r, g, b, 255)
+    }
+
+    /// `0xRRGGBBAA`
+    pub fn hex_rgba(hex: u32) -> Self {
+        let [r, g, b, a] = hex.to_be_bytes();
+        Self::rgba8(r, g, b, a)
+    }
+
+    pub fn to_rgba8(self) -> [u8; 4] {
+        let c = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [c(self.r), c(self.g), c(self.b), c(self.a)]
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// Alpha is already linear and isn't changed.
+    pub fn to_linear(self) -> Self {
+        Self::new(
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        )
+    }
+
+    pub fn to_srgb(self) -> Self {
+        Self::new(
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        )
+    }
+
+    /// Hue in degrees, saturation and lightness in 0..1.
+    pub fn hsl(h: f32, s: f32, l: f32) -> Self {
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Self::from_chroma(h, c, l - c / 2.0)
+    }
+
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (max, min) = (self.max(), self.min());
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        (self.hue(), s, l)
+    }
+
+    /// Hue in degrees, saturation and value in 0..1.
+    pub fn hsv(h: f32, s: f32, v: f32) -> Self {
+        let c = v * s;
+        Self::from_chroma(h, c, v - c)
+    }
+
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let max = self.max();
+        let s = if max == 0.0 {
+            0.0
+        } else {
+            (max - self.min()) / max
+        };
+        (self.hue(), s, max)
+    }
+
+    fn from_chroma(h: f32, c: f32, m: f32) -> Self {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        Self::rgb(r + m, g + m, b + m)
+    }
+
+    fn max(self) -> f32 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    fn min(self) -> f32 {
+        self.r.min(self.g).min(self.b)
+    }
+
+    fn hue(self) -> f32 {
+        let (max, delta) = (self.max(), self.max() - self.min());
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == self.r {
+            ((self.g - self.b) / delta).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / delta + 2.0
+        } else {
+            (self.r - self.g) / delta + 4.0
+        };
+        h * 60.0
+    }
+
+    /// Perceptual lightness, a and b. https://bottosson.github.io/posts/oklab/
+    #[allow(clippy::excessive_precision)]
+    pub fn to_oklab(self) -> [f32; 3] {
+        let Color { r, g, b, .. } = self.to_linear();
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+        [
+            0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        ]
+    }
+
+    #[allow(clippy::excessive_precision)]
+    pub fn oklab(lightness: f32, a: f32, b: f32) -> Self {
+        let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+        let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+        let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+        Self::rgb(
+            4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+            -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+            -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+        )
+        .to_srgb()
+    }
+
+    /// Interpolates in OKLab, which avoids the muddy middle of sRGB gradients.
+    pub fn mix(self, other: Color, t: f32) -> Self {
+        let (a, b) = (self.to_oklab(), other.to_oklab());
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Self::oklab(lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2]))
+            .with_alpha(lerp(self.a, other.a))
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl From<Color> for Vec4 {
+    fn from(c: Color) -> Self {
+        Vec4::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+impl From<Vec4> for Color {
+    fn from(v: Vec4) -> Self {
+        Color::new(v.x, v.y, v.z, v.w)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid color '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`, `hsla()`
+/// and a few named colors.
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseColorError(s.to_string());
+        let text = s.trim().to_ascii_lowercase();
+
+        if let Some(hex) = text.strip_prefix('#') {
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(error());
+            }
+            let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap() * 17;
+            let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+            return match hex.len() {
+                3 => Ok(Color::rgba8(digit(0), digit(1), digit(2), 255)),
+                4 => Ok(Color::rgba8(digit(0), digit(1), digit(2), digit(3))),
+                6 => Ok(Color::rgba8(pair(0), pair(2), pair(4), 255)),
+                8 => Ok(Color::rgba8(pair(0), pair(2), pair(4), pair(6))),
+                _ => Err(error()),
+            };
+        }
+
+        if let Some((name, args)) = text.split_once('(') {
+            let args: Vec<&str> = args
+                .strip_suffix(')')
+                .ok_or_else(error)?
+                .split([',', ' ', '/'])
+                .filter(|a| !a.is_empty())
+                .collect();
+
+            let parse = |n: &str| {
+                n.parse::<f32>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(error)
+            };
+            //Numbers can be 0..255 or percentages for rgb, alpha is 0..1 or a percentage.
+            //Out of range values are clamped like CSS does.
+            let number = |arg: &str, scale: f32| -> Result<f32, ParseColorError> {
+                let n = match arg.strip_suffix('%') {
+                    Some(percent) => parse(percent)? / 100.0,
+                    None => parse(arg)? / scale,
+                };
+                Ok(n.clamp(0.0, 1.0))
+            };
+            //Saturation and lightness have to be percentages.
+            let percent = |arg: &str| number(arg.strip_suffix('%').ok_or_else(error)?, 100.0);
+            let hue = |arg: &str| parse(arg.strip_suffix("deg").unwrap_or(arg));
+            let alpha = match args.get(3) {
+                Some(a) => number(a, 1.0)?,
+                None => 1.0,
+            };
+            if args.len() != 3 && args.len() != 4 {
+                return Err(error());
+            }
+
+            return match name.trim() {
+                "rgb" | "rgba" => Ok(Color::new(
+                    number(args[0], 255.0)?,
+                    number(args[1], 255.0)?,
+                    number(args[2], 255.0)?,
+                    alpha,
+                )),
+                "hsl" | "hsla" => {
+                    let color = Color::hsl(hue(args[0])?, percent(args[1])?, percent(args[2])?);
+                    Ok(color.with_alpha(alpha))
+                }
+                _ => Err(error()),
+            };
+        }
+
+        match text.as_str() {
+            "transparent" => Ok(Color::TRANSPARENT),
+            "black" => Ok(Color::BLACK),
+            "white" => Ok(Color::WHITE),
+            "red" => Ok(Color::hex(0xff0000)),
+            "green" => Ok(Color::hex(0x008000)),
+            "blue" => Ok(Color::hex(0x0000ff)),
+            "yellow" => Ok(Color::hex(0xffff00)),
+            "cyan" => Ok(Color::hex(0x00ffff)),
+            "magenta" => Ok(Color::hex(0xff00ff)),
+            "orange" => Ok(Color::hex(0xffa500)),
+            "purple" => Ok(Color::hex(0x800080)),
+            "gray" | "grey" => Ok(Color::hex(0x808080)),
+            _ => Err(error()),
+        }
+    }
+}
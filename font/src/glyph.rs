@@ -8,6 +8,7 @@ use freetype_sys::{
 };
 pub use glow::HasContext;
 
+/// In logical pixels, the atlas is rasterized at `FONT_SIZE * scale`.
 const FONT_SIZE: u32 = 48;
 
 ///https://learnopengl.com/img/in-practice/glyph.png
@@ -25,20 +26,51 @@ pub struct Glyph {
     pub buffer: Vec<u8>,
 }
 
+/// Glyph metrics and the texture size are in physical pixels.
 #[derive(Debug)]
 pub struct Atlas {
     pub width: i32,
     pub height: i32,
     pub texture: glow::NativeTexture,
     pub glyphs: [Glyph; 128],
+    /// Coverage is sampled as `(c, c, c, c)` instead of `(1, 1, 1, c)`.
+    pub premultiplied: bool,
+    /// Content scale the glyphs were rasterized at.
+    pub scale: f32,
+    /// Kept around to rasterize again when the scale changes.
+    pub font: Vec<u8>,
 }
 
 impl Atlas {
+    /// Switches between straight and premultiplied sampling, pair it with the matching `BlendMode`.
+    pub fn set_premultiplied(&mut self, gl: &glow::Context, premultiplied: bool) {
+        unsafe { gl.bind_texture(glow::TEXTURE_2D, Some(self.texture)) };
+        swizzle(gl, premultiplied);
+        self.premultiplied = premultiplied;
+    }
+
     //TODO: Figure out how to scale a texture.
     //It does seem like the projection is squishing the font.
     //The big letters like j seem fine but letters like e are squished.
     //I should probably align everything in the texture and save myself the trouble.
-    pub fn draw_text(&self, rd: &mut Renderer, text: &str, mut x: f32, mut y: f32, color: Vec4) {
+    /// Rasterizes the font again if the renderer's scale changed since the last call.
+    pub fn draw_text(
+        &mut self,
+        rd: &mut Renderer,
+        text: &str,
+        mut x: f32,
+        mut y: f32,
+        color: Vec4,
+    ) {
+        if self.scale != rd.scale {
+            self.rasterize(rd.gl, rd.scale);
+        }
+        rd.set_texture(self.texture);
+
+        //Glyphs are placed on physical pixels so they aren't resampled.
+        let scale = self.scale;
+        let snap = |v: f32| (v * scale).round() / scale;
+
         let start_x = x;
         for c in text.chars() {
             let ch = match self.glyphs.get(c as usize) {
@@ -47,42 +79,34 @@ impl Atlas {
             };
 
             if c == '\n' {
-                y -= self.height as f32;
+                y -= self.height as f32 / scale;
                 x = start_x;
             }
 
-            let xpos = x + ch.bearing.x;
-            let ypos = y - (ch.height - ch.bearing.y);
+            let xpos = snap(x + ch.bearing.x / scale);
+            let ypos = snap(y - (ch.height - ch.bearing.y) / scale);
 
-            let w = ch.width;
-            let h = ch.height;
+            let w = ch.width / scale;
+            let h = ch.height / scale;
 
             //The projection matrix is top left which flips the y.
             //So we no longer need to flip UV's.
             //~~The y UV is flipped here. !uv.y~~
             let uv_left = ch.uv;
             let uv_right = ch.uv + (ch.width / self.width as f32);
-            let uv_top = h / self.height as f32;
+            let uv_top = ch.height / self.height as f32;
             let uv_bottom = 0.0;
 
-            //Top left, Bottom left, Bottom right
-            //Bottom right, Top right, Top left
-            #[rustfmt::skip]
-            let vert = [
-                vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
-                vertex!((xpos, ypos),         color, (uv_left, uv_top)),
-                vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
-                vertex!((xpos + w, ypos),     color, (uv_right, uv_top)),
-                vertex!((xpos + w, ypos + h), color, (uv_right, uv_bottom)),
-                vertex!((xpos, ypos + h),     color, (uv_left, uv_bottom)),
-            ];
-
-            rd.vertices.extend(vert);
+            rd.rect(
+                Rect::new(xpos, ypos, w, h),
+                Vec4::new(uv_left, uv_top, uv_right, uv_bottom),
+                color,
+            );
 
             // Advance cursors for the next glyph
-            x += (ch.advance.x) as f32;
+            x += ch.advance.x / scale;
             //There are no characters with vertical advance without using the `VerticalLayout` flag.
-            y += (ch.advance.y) as f32;
+            y += ch.advance.y / scale;
         }
     }
 }
@@ -90,57 +114,6 @@ impl Atlas {
 pub unsafe fn load_font(rd: &Renderer, font: &[u8]) -> Atlas {
     let gl = &rd.gl;
 
-    let lib = Library::init().unwrap();
-    // FT_Library_SetLcdFilter(lib.raw(), FT_LCD_FILTER_DEFAULT);
-
-    let mut face = lib.new_memory_face2(font, 0).unwrap();
-    face.set_pixel_sizes(0, FONT_SIZE).unwrap();
-
-    let mut width = 0;
-    let mut height = 0;
-    #[allow(invalid_value)]
-    let mut glyphs: [Glyph; 128] = std::mem::zeroed();
-
-    //Load symbols, numbers and letters.
-    for i in 32..127 {
-        // face.load_char(i, LoadFlag::RENDER).unwrap();
-        let err = FT_Load_Char(
-            face.raw_mut(),
-            i as u32,
-            // FT_LOAD_RENDER | FT_RENDER_MODE_SDF as i32,
-            FT_LOAD_RENDER,
-        );
-
-        if err != FT_Err_Ok {
-            panic!("{}", Error::from(err));
-        }
-
-        let glyph = face.glyph();
-        let bitmap = glyph.bitmap();
-
-        width += bitmap.width();
-
-        if height < bitmap.rows() {
-            height = bitmap.rows();
-        }
-
-        glyph.render_glyph(RenderMode::Normal).unwrap();
-
-        //Bitshift by 6 to get value in pixels. (2^6 = 64, advance is 1/64 pixels)
-        glyphs[i].advance = Vec2::new(
-            (glyph.advance().x >> 6) as f32,
-            (glyph.advance().y >> 6) as f32,
-        );
-        glyphs[i].width = bitmap.width() as f32;
-        glyphs[i].height = bitmap.rows() as f32;
-        glyphs[i].bearing = Vec2::new(glyph.bitmap_left() as f32, glyph.bitmap_top() as f32);
-        glyphs[i].buffer = bitmap.buffer().to_vec();
-        assert_eq!(
-            glyphs[i].buffer.len() as f32,
-            glyphs[i].width * glyphs[i].height
-        );
-    }
-
     let texture = unsafe { gl.create_texture().unwrap() };
     gl.bind_texture(glow::TEXTURE_2D, Some(texture));
 
@@ -164,63 +137,147 @@ pub unsafe fn load_font(rd: &Renderer, font: &[u8]) -> Atlas {
         glow::TEXTURE_WRAP_T,
         glow::CLAMP_TO_EDGE as i32,
     );
+    swizzle(gl, false);
     gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
 
-    debug_assert!(width >= 0);
-    debug_assert!(width < glow::MAX_TEXTURE_SIZE as i32);
-    debug_assert!(height < glow::MAX_TEXTURE_SIZE as i32);
-
-    //If we don't zero this texture, bad things will happen.
-    gl.tex_image_2d(
-        glow::TEXTURE_2D,
-        0,
-        glow::RED as i32,
-        width as i32,
-        height as i32,
-        0,
-        glow::RED,
-        glow::UNSIGNED_BYTE,
-        // Some(&vec![0; (width * height) as usize]),
-        None,
-    );
-
-    let mut x = 0;
+    let mut atlas = Atlas {
+        width: 0,
+        height: 0,
+        texture,
+        glyphs: std::array::from_fn(|_| Glyph::default()),
+        premultiplied: false,
+        scale: 0.0,
+        font: font.to_vec(),
+    };
+    atlas.rasterize(gl, rd.scale);
+    atlas
+}
 
-    for i in 32..127 {
-        glyphs[i].uv = x as f32 / width as f32;
+impl Atlas {
+    /// Loads every glyph at `FONT_SIZE * scale` pixels into the existing texture.
+    pub fn rasterize(&mut self, gl: &glow::Context, scale: f32) {
+        unsafe {
+            let lib = Library::init().unwrap();
+            // FT_Library_SetLcdFilter(lib.raw(), FT_LCD_FILTER_DEFAULT);
+
+            let mut face = lib.new_memory_face2(&self.font, 0).unwrap();
+            face.set_pixel_sizes(0, (FONT_SIZE as f32 * scale).round() as u32)
+                .unwrap();
+
+            let mut width = 0;
+            let mut height = 0;
+            let mut glyphs: [Glyph; 128] = std::array::from_fn(|_| Glyph::default());
+
+            //Load symbols, numbers and letters.
+            for i in 32..127 {
+                // face.load_char(i, LoadFlag::RENDER).unwrap();
+                let err = FT_Load_Char(
+                    face.raw_mut(),
+                    i as u32,
+                    // FT_LOAD_RENDER | FT_RENDER_MODE_SDF as i32,
+                    FT_LOAD_RENDER,
+                );
+
+                if err != FT_Err_Ok {
+                    panic!("{}", Error::from(err));
+                }
+
+                let glyph = face.glyph();
+                let bitmap = glyph.bitmap();
+
+                width += bitmap.width();
+
+                if height < bitmap.rows() {
+                    height = bitmap.rows();
+                }
+
+                glyph.render_glyph(RenderMode::Normal).unwrap();
+
+                //Bitshift by 6 to get value in pixels. (2^6 = 64, advance is 1/64 pixels)
+                glyphs[i].advance = Vec2::new(
+                    (glyph.advance().x >> 6) as f32,
+                    (glyph.advance().y >> 6) as f32,
+                );
+                glyphs[i].width = bitmap.width() as f32;
+                glyphs[i].height = bitmap.rows() as f32;
+                glyphs[i].bearing =
+                    Vec2::new(glyph.bitmap_left() as f32, glyph.bitmap_top() as f32);
+                glyphs[i].buffer = bitmap.buffer().to_vec();
+                assert_eq!(
+                    glyphs[i].buffer.len() as f32,
+                    glyphs[i].width * glyphs[i].height
+                );
+            }
 
-        if x + glyphs[i].width as i32 > glow::TEXTURE_WIDTH as i32
-            || 0 + glyphs[i].height as i32 > glow::TEXTURE_HEIGHT as i32
-        {
-            panic!("texture is too big!");
-        }
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+
+            debug_assert!(width >= 0);
+            debug_assert!(width < glow::MAX_TEXTURE_SIZE as i32);
+            debug_assert!(height < glow::MAX_TEXTURE_SIZE as i32);
+
+            //If we don't zero this texture, bad things will happen.
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RED as i32,
+                width as i32,
+                height as i32,
+                0,
+                glow::RED,
+                glow::UNSIGNED_BYTE,
+                // Some(&vec![0; (width * height) as usize]),
+                None,
+            );
+
+            let mut x = 0;
+
+            for i in 32..127 {
+                glyphs[i].uv = x as f32 / width as f32;
+
+                if x + glyphs[i].width as i32 > glow::TEXTURE_WIDTH as i32
+                    || 0 + glyphs[i].height as i32 > glow::TEXTURE_HEIGHT as i32
+                {
+                    panic!("texture is too big!");
+                }
+
+                if (glyphs[i].width as i32) < 0 || (glyphs[i].height as i32) < 0 {
+                    panic!("too small!");
+                }
+
+                gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+                gl.tex_sub_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    x,
+                    0,
+                    glyphs[i].width as i32,
+                    glyphs[i].height as i32,
+                    glow::RED,
+                    glow::UNSIGNED_BYTE,
+                    glow::PixelUnpackData::Slice(&glyphs[i].buffer),
+                );
+
+                check_error(gl);
+
+                x += glyphs[i].width as i32;
+            }
 
-        if (glyphs[i].width as i32) < 0 || (glyphs[i].height as i32) < 0 {
-            panic!("too small!");
+            self.width = width;
+            self.height = height;
+            self.glyphs = glyphs;
+            self.scale = scale;
         }
-
-        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
-        gl.tex_sub_image_2d(
-            glow::TEXTURE_2D,
-            0,
-            x,
-            0,
-            glyphs[i].width as i32,
-            glyphs[i].height as i32,
-            glow::RED,
-            glow::UNSIGNED_BYTE,
-            glow::PixelUnpackData::Slice(&glyphs[i].buffer),
-        );
-
-        check_error(gl);
-
-        x += glyphs[i].width as i32;
     }
+}
 
-    Atlas {
-        width,
-        height,
-        texture,
-        glyphs,
+/// Sample as white with the coverage in alpha, so text can share a shader with images.
+/// Premultiplied white is the coverage in every channel.
+fn swizzle(gl: &glow::Context, premultiplied: bool) {
+    let color = if premultiplied { glow::RED } else { glow::ONE } as i32;
+    unsafe {
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_SWIZZLE_R, color);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_SWIZZLE_G, color);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_SWIZZLE_B, color);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_SWIZZLE_A, glow::RED as i32);
     }
 }
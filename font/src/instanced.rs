@@ -0,0 +1,222 @@
+use crate::*;
+use std::ops::Range;
+
+/// Selects how `Renderer::rect` submits quads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuadPath {
+    /// Six vertices per quad, built on the CPU.
+    Vertices,
+    /// One `Instance` per quad, expanded in the vertex shader.
+    Instanced,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, VertexLayout)]
+pub struct Instance {
+    /// x, y, width, height.
+    #[layout(location = 1)]
+    pub rect: Vec4,
+    /// Texture coordinates of the bottom left and top right corners.
+    pub uv: Vec4,
+    pub color: Vec4,
+    /// Index into `InstancedQuads::transforms`, counted from the start of its batch.
+    pub transform: u32,
+}
+
+/// Unit quad as two counter clockwise triangles.
+#[rustfmt::skip]
+const CORNERS: [f32; 12] = [
+    0.0, 0.0,
+    1.0, 0.0,
+    1.0, 1.0,
+    1.0, 1.0,
+    0.0, 1.0,
+    0.0, 0.0,
+];
+
+pub struct InstancedQuads {
+    pub shader: Shader,
+    pub vao: NativeVertexArray,
+    pub quad: NativeBuffer,
+    pub buffer: NativeBuffer,
+    /// RGBA32F texture holding two texels per transform.
+    pub transform_texture: NativeTexture,
+    pub instances: Vec<Instance>,
+    pub transforms: Vec<Transform>,
+    /// Instances before this index have already been added to `Renderer::commands`.
+    pub flushed: usize,
+    /// First transform of the current batch.
+    pub batch: usize,
+    /// Transforms per batch, one row of the texture each so at most `GL_MAX_TEXTURE_SIZE`.
+    pub batch_size: usize,
+    /// Batch currently in `transform_texture`.
+    pub uploaded: Option<usize>,
+}
+
+impl InstancedQuads {
+    pub fn new(gl: &'static glow::Context) -> Self {
+        let (vao, quad, buffer) = unsafe {
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+
+            let quad = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(quad));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, buffer(&CORNERS), glow::STATIC_DRAW);
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 8, 0);
+
+            let buffer = gl.create_buffer().unwrap();
+            layout::gl::divisor::<Instance>(gl, 1);
+            (vao, quad, buffer)
+        };
+
+        let mut shader = Shader::new(
+            gl,
+            source!("../shaders/instanced.vert"),
+            source!("../shaders/text.frag"),
+        )
+        .unwrap();
+        let directory = std::path::Path::new(SHADER_DIRECTORY);
+        shader.files = vec![
+            directory.join("instanced.vert"),
+            directory.join("text.frag"),
+        ];
+        shader.set_i32("transforms", 1);
+
+        unsafe {
+            let batch_size = gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) as usize;
+            let transform_texture = gl.create_texture().unwrap();
+            gl.bind_texture(glow::TEXTURE_2D, Some(transform_texture));
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+
+            Self {
+                shader,
+                vao,
+                quad,
+                buffer,
+                transform_texture,
+                instances: Vec::new(),
+                transforms: Vec::new(),
+                flushed: 0,
+                batch: 0,
+                batch_size,
+                uploaded: None,
+            }
+        }
+    }
+
+    /// Call `next_batch` before pushing a quad with this transform, after flushing the pending instances.
+    pub fn full(&self, transform: &Transform) -> bool {
+        self.transforms.last() != Some(transform)
+            && self.transforms.len() - self.batch >= self.batch_size
+    }
+
+    /// Quads pushed after this index transforms from a new batch.
+    pub fn next_batch(&mut self) {
+        self.batch = self.transforms.len();
+    }
+
+    pub fn push(&mut self, rect: Rect, uv: Vec4, color: Vec4, transform: Transform) {
+        debug_assert!(!self.full(&transform));
+        //Consecutive quads almost always share a transform.
+        if self.transforms.last() != Some(&transform) {
+            self.transforms.push(transform);
+        }
+
+        self.instances.push(Instance {
+            rect: Vec4::new(rect.x, rect.y, rect.width, rect.height),
+            uv,
+            color,
+            transform: (self.transforms.len() - 1 - self.batch) as u32,
+        });
+    }
+
+    /// Instances that haven't been added to a command yet.
+    pub fn pending(&self) -> bool {
+        self.flushed < self.instances.len()
+    }
+
+    /// Sends the instances to the GPU, call once before drawing.
+    /// Transforms are uploaded by `draw` one batch at a time.
+    pub fn upload(&mut self, gl: &glow::Context) {
+        self.uploaded = None;
+        if self.instances.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                self.instances.align_to::<u8>().1,
+                glow::STREAM_DRAW,
+            );
+        }
+    }
+
+    /// Leaves texture unit 1 active.
+    fn upload_batch(&mut self, gl: &glow::Context, batch: usize) {
+        let end = (batch + self.batch_size).min(self.transforms.len());
+        let texels: Vec<f32> = self.transforms[batch..end]
+            .iter()
+            .flat_map(|t| [t.a, t.c, t.x, 0.0, t.b, t.d, t.y, 0.0])
+            .collect();
+
+        unsafe {
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.transform_texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA32F as i32,
+                2,
+                (end - batch) as i32,
+                0,
+                glow::RGBA,
+                glow::FLOAT,
+                Some(buffer(&texels)),
+            );
+        }
+        self.uploaded = Some(batch);
+    }
+
+    /// Draws `range` of `instances`, which were pushed during `batch`.
+    /// Leaves the instanced program and vertex array bound.
+    pub fn draw(&mut self, gl: &glow::Context, range: Range<usize>, batch: usize) {
+        let offset = (range.start * std::mem::size_of::<Instance>()) as u32;
+        if self.uploaded != Some(batch) {
+            self.upload_batch(gl, batch);
+        }
+
+        unsafe {
+            self.shader.bind();
+            gl.bind_vertex_array(Some(self.vao));
+
+            //Glsl 330 has no base instance, so the pointers are moved to the first instance instead.
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            layout::gl::bind_at::<Instance>(gl, offset);
+
+            gl.active_texture(glow::TEXTURE1);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.transform_texture));
+            gl.active_texture(glow::TEXTURE0);
+
+            gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, range.len() as i32);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.instances.clear();
+        self.transforms.clear();
+        self.flushed = 0;
+        self.batch = 0;
+    }
+}
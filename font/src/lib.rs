@@ -1,20 +1,52 @@
 #![feature(const_maybe_uninit_zeroed)]
 use glow::*;
+use layout::VertexLayout;
 use std::mem::MaybeUninit;
+use std::path::{Path, PathBuf};
 
 extern crate nalgebra_glm as glm;
 
+pub mod blend;
+pub mod clip;
+pub mod color;
 pub mod glyph;
+pub mod instanced;
 pub mod math;
-
+pub mod pan_zoom;
+pub mod preprocess;
+pub mod shader;
+pub mod sprite;
+pub mod surface;
+pub mod target;
+pub mod texture;
+pub mod upload;
+
+pub use blend::*;
+pub use clip::*;
+pub use color::*;
 pub use glyph::*;
+pub use instanced::*;
 pub use math::*;
+pub use pan_zoom::*;
+pub use preprocess::*;
+pub use reload::ShaderWatcher;
+pub use shader::*;
+//`glow` also exports a `Shader`.
+pub use shader::Shader;
+pub use sprite::*;
+pub use surface::*;
+pub use target::*;
+pub use texture::*;
+pub use upload::*;
 
 #[cfg(test)]
 mod tests;
 
 pub static mut GL: MaybeUninit<Context> = MaybeUninit::uninit();
 
+/// Shaders that ship with the crate, used instead of the embedded copies while hot reloading.
+pub const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+
 pub const TOP_LEFT: Vec2 = Vec2::new(-0.5, 0.5);
 pub const BOTTOM_LEFT: Vec2 = Vec2::new(-0.5, -0.5);
 pub const TOP_RIGHT: Vec2 = Vec2::new(0.5, 0.5);
@@ -35,6 +67,9 @@ pub fn create_window() -> (
     use glfw::Context;
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
     glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
+    glfw.window_hint(glfw::WindowHint::SRgbCapable(true));
+    //Size the window in logical pixels on platforms that don't do it already.
+    glfw.window_hint(glfw::WindowHint::ScaleToMonitor(true));
     let monitor = glfw::Monitor::from_primary();
     let video_mode = monitor.get_video_mode().unwrap();
     let (width, height) = (
@@ -51,6 +86,7 @@ pub fn create_window() -> (
         .expect("Failed to create GLFW window.");
     window.set_resizable(true);
     window.set_key_polling(true);
+    window.set_content_scale_polling(true);
     window.make_current();
 
     assert!(window.is_opengl_debug_context());
@@ -65,44 +101,11 @@ pub fn create_window() -> (
 
     println!("Loaded OpenGL {:?}", gl.version());
 
+    //The framebuffer can be larger than the window on scaled displays.
+    let (width, height) = window.get_framebuffer_size();
     (width, height, window, events, glfw, gl)
 }
 
-//TODO: Remove
-pub unsafe fn container() -> glow::NativeTexture {
-    let gl = GL.assume_init_ref();
-    let bytes = include_bytes!("../container.jpg");
-    let im = image::load_from_memory(bytes).unwrap();
-    let texture = gl.create_texture().unwrap();
-
-    gl.active_texture(glow::TEXTURE0);
-    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
-    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
-    gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::REPEAT as i32);
-    gl.tex_parameter_i32(
-        glow::TEXTURE_2D,
-        glow::TEXTURE_MIN_FILTER,
-        glow::LINEAR as i32,
-    );
-    gl.tex_parameter_i32(
-        glow::TEXTURE_2D,
-        glow::TEXTURE_MAG_FILTER,
-        glow::LINEAR as i32,
-    );
-    gl.tex_image_2d(
-        glow::TEXTURE_2D,
-        0,
-        glow::RGB as i32,
-        im.width() as i32,
-        im.height() as i32,
-        0,
-        glow::RGB,
-        glow::UNSIGNED_BYTE,
-        Some(im.as_bytes()),
-    );
-    texture
-}
-
 #[track_caller]
 pub fn check_error(gl: &Context) {
     let error = unsafe { gl.get_error() };
@@ -119,71 +122,6 @@ pub fn check_error(gl: &Context) {
     }
 }
 
-//TODO: Uniforms?
-/// Macro for creating shaders.
-/// ```rs
-/// let program = shader! {
-///     include_str!("../shaders/simple.vert"),
-///     include_str!("../shaders/text.frag"),
-///     Vec2 => 0,
-///     Vec4 => 1,
-///     Vec2 => 2
-/// };
-/// ```
-#[macro_export]
-macro_rules! shader {
-    ($vert:expr, $frag:expr, $($type:ident => $position:expr),*$(,)?) => {
-        unsafe {
-            use glow::HasContext;
-
-            let gl = $crate::GL.assume_init_ref();
-            let mut stride = 0;
-            let mut _offset = 0;
-
-            $(
-                stride += std::mem::size_of::<$type>();
-            )*
-
-            $(
-                let n = std::mem::size_of::<$type>() / std::mem::size_of::<f32>();
-                gl.enable_vertex_attrib_array($position);
-                gl.vertex_attrib_pointer_f32($position, n as i32, glow::FLOAT, false, stride as i32, _offset as i32);
-                _offset += std::mem::size_of::<$type>();
-            )*
-
-            let program = gl.create_program().unwrap();
-
-            let v = gl.create_shader(glow::VERTEX_SHADER).unwrap();
-            let error = gl.get_shader_info_log(v);
-            if !error.is_empty() {
-                panic!("{error}");
-            }
-            gl.shader_source(v, $vert);
-            gl.compile_shader(v);
-            gl.attach_shader(program, v);
-
-            let f = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
-            let error = gl.get_shader_info_log(f);
-            if !error.is_empty() {
-                panic!("{error}");
-            }
-            gl.shader_source(f, $frag);
-            gl.compile_shader(f);
-            gl.attach_shader(program, f);
-
-            gl.link_program(program);
-            if !gl.get_program_link_status(program) {
-                panic!("{}", gl.get_program_info_log(program));
-            }
-
-            gl.delete_shader(v);
-            gl.delete_shader(f);
-
-            program
-        }
-   };
-}
-
 #[macro_export]
 macro_rules! vertex {
     () => {
@@ -215,7 +153,7 @@ macro_rules! vertex {
 //I think rust packed my struct in a weird way.
 //So align won't work unless you use `repr(C)`.
 #[repr(C)]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, VertexLayout)]
 pub struct Vertex {
     pub position: Vec2,
     pub uv: Vec2,
@@ -235,6 +173,12 @@ impl Vertex {
         self.color = color;
         self
     }
+
+    /// Points attributes 0, 1 and 2 at the buffer bound to `ARRAY_BUFFER`.
+    /// Matches the inputs in `vertex.glsl`.
+    pub fn bind_layout(gl: &glow::Context) {
+        layout::gl::bind::<Vertex>(gl);
+    }
 }
 
 #[inline]
@@ -247,29 +191,87 @@ pub fn buffer(vertices: &[f32]) -> &[u8] {
     }
 }
 
+/// sRGB `0xRRGGBB`, see `Color` for other formats.
 #[inline]
 pub fn hex(hex: u32) -> Vec4 {
-    let bytes: [u8; 4] = hex.to_be_bytes();
-
-    Vec4::new(
-        bytes[1] as f32 / 255.,
-        bytes[2] as f32 / 255.,
-        bytes[3] as f32 / 255.,
-        1.0,
-    )
+    Color::hex(hex).into()
+}
+
+fn tint(color: Vec4, srgb: bool, premultiplied: bool) -> Vec4 {
+    let color = if srgb {
+        Color::from(color).to_linear().into()
+    } else {
+        color
+    };
+    if premultiplied {
+        premultiply(color)
+    } else {
+        color
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Draw a range of `Renderer::vertices`.
+    Draw(std::ops::Range<usize>),
+    /// Draw a range of `InstancedQuads::instances`.
+    /// `batch` is the first transform of the batch they index into.
+    DrawInstanced {
+        instances: std::ops::Range<usize>,
+        batch: usize,
+    },
+    /// Binds a texture to `TEXTURE0`.
+    Texture(NativeTexture),
+    Blend(BlendMode),
+    PushClip(Clip),
+    PopClip,
+    /// Redirects drawing into a `RenderTarget` until the matching `EndTarget`.
+    BeginTarget {
+        framebuffer: NativeFramebuffer,
+        width: i32,
+        height: i32,
+    },
+    EndTarget,
 }
 
 pub struct Renderer {
     pub gl: &'static glow::Context,
     pub vertices: Vec<Vertex>,
+    /// Commands are replayed every time `draw` is called.
+    pub commands: Vec<Command>,
+    /// Vertices before this index have already been added to `commands`.
+    pub flushed: usize,
+    /// Number of clips pushed but not popped.
+    pub clip_depth: usize,
+    pub clips: ClipStack,
+    /// Number of render targets begun but not ended.
+    pub target_depth: usize,
+    /// Applied to every position when it's submitted.
+    pub transform: TransformStack,
+    pub textures: TextureRegistry,
+    /// 1x1 white texture used for solid colors.
+    pub white: NativeTexture,
+    /// Texture that was last recorded with `set_texture`.
+    pub texture: Option<NativeTexture>,
+    /// Applied at the start of every `draw`, see `enable_blend`.
+    pub default_blend: BlendMode,
+    /// Blend mode that was last recorded with `set_blend_mode`.
+    pub blend: Option<BlendMode>,
+    /// Colors are converted from sRGB to linear and blended in linear space, see `set_srgb`.
+    pub srgb: bool,
     pub vao: NativeVertexArray,
-    pub vbo: NativeBuffer,
-    pub buffer_size: usize,
+    pub stream: VertexStream,
+    pub path: QuadPath,
+    pub quads: InstancedQuads,
+    /// Framebuffer size in physical pixels.
     pub width: i32,
     pub height: i32,
+    /// Physical pixels per logical pixel, drawing happens in logical pixels. See `set_scale`.
+    pub scale: f32,
     pub projection: glm::Mat4x4,
-    pub projection_location: NativeUniformLocation,
-    pub shader: NativeProgram,
+    pub shader: Shader,
+    /// Only set after `watch_shaders`.
+    pub watcher: Option<ShaderWatcher>,
 }
 
 impl Renderer {
@@ -325,48 +327,115 @@ impl Renderer {
             let vao = gl.create_vertex_array().unwrap();
             gl.bind_vertex_array(Some(vao));
 
-            let vbo = gl.create_buffer().unwrap();
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            let stream = VertexStream::new(gl, UploadStrategy::SubData);
+            Vertex::bind_layout(gl);
 
-            #[allow(unused)]
-            let basic = shader! {
-                include_str!("../shaders/simple.vert"),
-                include_str!("../shaders/text.frag"),
-                Vec2 => 0,
-                Vec2 => 1,
-                Vec4 => 2
-            };
+            let white = upload(
+                gl,
+                &image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])),
+                TextureOptions::default().filter(Filter::Nearest),
+            );
 
-            gl.use_program(Some(basic));
+            let quads = InstancedQuads::new(gl);
+            gl.bind_vertex_array(Some(vao));
+
+            let mut shader = Shader::new(
+                gl,
+                source!("../shaders/simple.vert"),
+                source!("../shaders/text.frag"),
+            )
+            .unwrap();
+            let directory = std::path::Path::new(SHADER_DIRECTORY);
+            shader.files = vec![directory.join("simple.vert"), directory.join("text.frag")];
 
             //1:1 pixel mapping projection matrix. Bottom left origin.
             let projection = glm::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
-            let location = gl.get_uniform_location(basic, "projection").unwrap();
-            gl.uniform_matrix_4_f32_slice(Some(&location), false, projection.as_slice());
+            quads.shader.set_mat4("projection", &projection);
+            shader.set_mat4("projection", &projection);
 
             Self {
                 gl,
                 vao,
-                vbo,
+                stream,
+                path: QuadPath::Vertices,
+                quads,
                 vertices: Vec::new(),
-                buffer_size: 0,
+                commands: Vec::new(),
+                flushed: 0,
+                clip_depth: 0,
+                clips: ClipStack::default(),
+                target_depth: 0,
+                transform: TransformStack::default(),
+                textures: TextureRegistry::new(),
+                white,
+                texture: None,
+                default_blend: BlendMode::Replace,
+                blend: None,
+                srgb: false,
                 width,
                 height,
+                scale: 1.0,
                 projection,
-                projection_location: location,
-                shader: basic,
+                shader,
+                watcher: None,
             }
         }
     }
 
     pub fn vertex(&mut self, position: Vec2, color: Vec4, uv: Vec2) {
         self.vertices.push(Vertex {
-            position,
-            color,
+            position: self.transform.current.apply(position),
+            color: self.tint(color),
             uv,
         });
     }
 
+    /// Converts the color to linear when `srgb` is set and
+    /// premultiplies it when `BlendMode::Premultiplied` is being recorded.
+    pub fn tint(&self, color: Vec4) -> Vec4 {
+        tint(
+            color,
+            self.srgb,
+            self.blend == Some(BlendMode::Premultiplied),
+        )
+    }
+
+    /// Adds vertices after applying the current transform.
+    pub fn submit(&mut self, vertices: impl IntoIterator<Item = Vertex>) {
+        if self.quads.pending() {
+            self.flush();
+        }
+        let transform = self.transform.current;
+        let (srgb, premultiplied) = (self.srgb, self.blend == Some(BlendMode::Premultiplied));
+        self.vertices.extend(vertices.into_iter().map(|mut v| {
+            v.position = transform.apply(v.position);
+            v.color = tint(v.color, srgb, premultiplied);
+            v
+        }));
+    }
+
+    /// Saves the current transform, restore it with `pop_transform`.
+    pub fn push_transform(&mut self) {
+        self.transform.push();
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.transform.pop();
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.transform.translate(x, y);
+    }
+
+    /// Counter clockwise rotation in radians around the current origin.
+    pub fn rotate(&mut self, radians: f32) {
+        self.transform.rotate(radians);
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.transform.scale(x, y);
+    }
+
     ///Create in counter clockwise order.
     pub fn triangle(
         &mut self,
@@ -388,125 +457,447 @@ impl Renderer {
     /// Draws a solid rectangle with its top-left corner at `[x, y]` with size `[w, h]` (width going to
     /// the right, height going down).
     pub fn texture(&mut self, x: f32, y: f32, w: f32, h: f32, color: Vec4) {
-        //Bottom left, bottom right, top right.
-        //Top right, top left, bottom left.
-
-        //TODO: I want to mix and match floats and vecs when creating vertex data.
-        //Not sure how to do it. Right now it sucks bad.
-        // #[rustfmt::skip]
-        // let vertices = [
-        //     vertex!((x    , y    ), color, (0.0, 0.0)),
-        //     vertex!((x + w, y    ), color, (1.0, 0.0)),
-        //     vertex!((x + w, y + h), color, (1.0, 1.0)),
-        //     vertex!((x + w, y + h), color, (1.0, 1.0)),
-        //     vertex!((x    , y + h), color, (0.0, 1.0)),
-        //     vertex!((x    , y    ), color, (0.0, 0.0))
-        // ];
-
-        #[rustfmt::skip]
-        let vertices = [
-            vertex!((x    , y    ), color, (0.0, 1.0)),
-            vertex!((x + w, y    ), color, (1.0, 1.0)),
-            vertex!((x + w, y + h), color, (1.0, 0.0)),
-            vertex!((x + w, y + h), color, (1.0, 0.0)),
-            vertex!((x    , y + h), color, (0.0, 0.0)),
-            vertex!((x    , y    ), color, (0.0, 1.0))
-        ];
-        self.vertices.extend(vertices);
+        self.rect(Rect::new(x, y, w, h), Vec4::new(0.0, 1.0, 1.0, 0.0), color);
     }
 
     pub fn quad(&mut self, x: f32, y: f32, w: f32, h: f32, color: Vec4) {
+        self.set_texture(self.white);
+        self.rect(Rect::new(x, y, w, h), Vec4::default(), color);
+    }
+
+    /// Textured rectangle using the current texture.
+    /// `uv` holds the texture coordinates of the bottom left and top right corners.
+    pub fn rect(&mut self, rect: Rect, uv: Vec4, color: Vec4) {
+        if self.path == QuadPath::Instanced {
+            if self.flushed < self.vertices.len() {
+                self.flush();
+            }
+            if self.quads.full(&self.transform.current) {
+                self.flush();
+                self.quads.next_batch();
+            }
+            self.quads
+                .push(rect, uv, self.tint(color), self.transform.current);
+            return;
+        }
+
+        let Rect {
+            x,
+            y,
+            width: w,
+            height: h,
+        } = rect;
+
         //Bottom left, bottom right, top right.
         //Top right, top left, bottom left.
         #[rustfmt::skip]
         let vertices = [
-            vertex!((x    , y    ), color, (0.0, 0.0)),
-            vertex!((x + w, y    ), color, (0.0, 0.0)),
-            vertex!((x + w, y + h), color, (0.0, 0.0)),
-            vertex!((x + w, y + h), color, (0.0, 0.0)),
-            vertex!((x    , y + h), color, (0.0, 0.0)),
-            vertex!((x    , y    ), color, (0.0, 0.0))
+            vertex!((x    , y    ), color, (uv.x, uv.y)),
+            vertex!((x + w, y    ), color, (uv.z, uv.y)),
+            vertex!((x + w, y + h), color, (uv.z, uv.w)),
+            vertex!((x + w, y + h), color, (uv.z, uv.w)),
+            vertex!((x    , y + h), color, (uv.x, uv.w)),
+            vertex!((x    , y    ), color, (uv.x, uv.y))
         ];
-        self.vertices.extend(vertices);
+        self.submit(vertices);
     }
 
-    pub fn use_shader(&mut self, program: NativeProgram) {
-        unsafe {
-            self.shader = program;
-            self.gl.use_program(Some(self.shader));
-
-            //Update the projection matrix location.
-            self.projection_location = self
-                .gl
-                .get_uniform_location(self.shader, "projection")
-                .unwrap();
-
-            self.gl.uniform_matrix_4_f32_slice(
-                Some(&self.projection_location),
-                false,
-                self.projection.as_slice(),
-            );
+    /// Draws `src` of the texture into `dst`, `src` defaults to the whole image.
+    /// `src` is in pixels starting at the top left of the image.
+    pub fn image(&mut self, handle: TextureHandle, dst: Rect, src: Option<Rect>, tint: Vec4) {
+        let texture = *self.textures.get(handle);
+        self.set_texture(texture.texture);
+
+        let (tw, th) = (texture.width as f32, texture.height as f32);
+        let src = src.unwrap_or(Rect::new(0.0, 0.0, tw, th));
+        let (u0, u1) = (src.x / tw, (src.x + src.width) / tw);
+        let (v0, v1) = (src.y / th, (src.y + src.height) / th);
+        self.rect(dst, Vec4::new(u0, v1, u1, v0), tint);
+    }
+
+    pub fn sprite(&mut self, atlas: &SpriteAtlas, name: &str, dst: Rect, tint: Vec4) {
+        self.image(atlas.texture, dst, Some(atlas.get(name)), tint);
+    }
+
+    /// Draws the current frame of the animation.
+    pub fn animation(
+        &mut self,
+        atlas: &SpriteAtlas,
+        animation: &SpriteAnimation,
+        dst: Rect,
+        tint: Vec4,
+    ) {
+        self.sprite(atlas, &animation.frame().sprite, dst, tint);
+    }
+
+    /// Vertices submitted after this will sample from `texture`.
+    /// Only starts a new batch when the texture actually changes.
+    pub fn set_texture(&mut self, texture: NativeTexture) {
+        if self.texture != Some(texture) {
+            self.flush();
+            self.commands.push(Command::Texture(texture));
+            self.texture = Some(texture);
+        }
+    }
+
+    /// Vertices submitted after this use `mode`, other modes can be mixed within the same frame.
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        if self.blend != Some(mode) {
+            self.flush();
+            self.commands.push(Command::Blend(mode));
+            self.blend = Some(mode);
+        }
+    }
+
+    /// Adds any vertices or instances that haven't been recorded yet as a draw command.
+    pub fn flush(&mut self) {
+        if self.quads.pending() {
+            self.commands.push(Command::DrawInstanced {
+                instances: self.quads.flushed..self.quads.instances.len(),
+                batch: self.quads.batch,
+            });
+            self.quads.flushed = self.quads.instances.len();
+        }
+        if self.flushed < self.vertices.len() {
+            self.commands
+                .push(Command::Draw(self.flushed..self.vertices.len()));
+            self.flushed = self.vertices.len();
+        }
+    }
+
+    /// Clips everything drawn until the next `pop_clip` to the rectangle.
+    /// Nested clips are intersected with their parent.
+    /// Rotated rectangles can't use a scissor and fall back to the stencil buffer.
+    pub fn push_clip_rect(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        if !self.transform.current.is_axis_aligned() {
+            return self.push_clip_path(&[
+                Vec2::new(x, y),
+                Vec2::new(x + w, y),
+                Vec2::new(x + w, y + h),
+                Vec2::new(x, y + h),
+            ]);
+        }
+
+        //Scissors are in framebuffer pixels, targets are drawn 1:1 so they aren't scaled.
+        let scale = if self.target_depth == 0 {
+            self.scale
+        } else {
+            1.0
+        };
+        let min = self.transform.current.apply(Vec2::new(x, y)) * scale;
+        let max = self.transform.current.apply(Vec2::new(x + w, y + h)) * scale;
+        let rect = Rect::new(
+            min.x.min(max.x),
+            min.y.min(max.y),
+            (max.x - min.x).abs(),
+            (max.y - min.y).abs(),
+        );
+
+        self.flush();
+        self.commands.push(Command::PushClip(Clip::Rect(rect)));
+        self.clip_depth += 1;
+    }
+
+    pub fn push_clip_rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, radius: f32) {
+        self.push_clip_path(&rounded_rect(x, y, w, h, radius));
+    }
+
+    /// Clips to a convex polygon using the stencil buffer.
+    pub fn push_clip_path(&mut self, points: &[Vec2]) {
+        self.flush();
+        let start = self.vertices.len();
+        self.submit(fan(points));
+        self.flushed = self.vertices.len();
+        self.commands
+            .push(Command::PushClip(Clip::Stencil(start..self.vertices.len())));
+        self.clip_depth += 1;
+    }
+
+    pub fn pop_clip(&mut self) {
+        assert!(
+            self.clip_depth > 0,
+            "pop_clip was called without a matching push_clip"
+        );
+        self.flush();
+        self.commands.push(Command::PopClip);
+        self.clip_depth -= 1;
+    }
+
+    /// Everything drawn until `end_target` is rendered into `target` instead of the screen.
+    /// The target is cleared to transparent black when it's bound.
+    pub fn begin_target(&mut self, target: &RenderTarget) {
+        self.flush();
+        self.commands.push(Command::BeginTarget {
+            framebuffer: target.framebuffer,
+            width: target.width,
+            height: target.height,
+        });
+        self.target_depth += 1;
+    }
+
+    pub fn end_target(&mut self) {
+        assert!(
+            self.target_depth > 0,
+            "end_target was called without a matching begin_target"
+        );
+        self.flush();
+        self.commands.push(Command::EndTarget);
+        self.target_depth -= 1;
+    }
+
+    /// Uploads the projection to both the current shader and the instanced shader.
+    pub fn set_projection(&self, projection: &glm::Mat4x4) {
+        self.quads.shader.set_mat4("projection", projection);
+        self.shader.set_mat4("projection", projection);
+    }
+
+    /// Returns the previous shader.
+    pub fn use_shader(&mut self, shader: Shader) -> Shader {
+        shader.set_mat4("projection", &self.projection);
+        std::mem::replace(&mut self.shader, shader)
+    }
+
+    /// Recompiles shaders whenever their `Shader::files` change on disk, meant for development.
+    /// A shader that fails to compile logs the error and keeps the old program.
+    pub fn watch_shaders(&mut self) {
+        self.watcher = Some(ShaderWatcher::new(Vec::<PathBuf>::new()));
+        //Pick up edits made after the embedded copies were compiled.
+        self.reload_shaders(|_| true);
+    }
+
+    /// Called by `draw` while watching.
+    pub fn poll_shaders(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+
+        //Shaders can be swapped with `use_shader` at any time.
+        let shaders = [&self.shader, &self.quads.shader];
+        for file in shaders
+            .iter()
+            .flat_map(|s| s.files.iter().chain(&s.includes))
+        {
+            if let Some(directory) = file.parent() {
+                watcher.watch(directory);
+            }
+        }
+
+        let changed = watcher.poll();
+        if !changed.is_empty() {
+            self.reload_shaders(|file| changed.iter().any(|c| c == file));
         }
     }
 
+    fn reload_shaders(&mut self, changed: impl Fn(&Path) -> bool) {
+        for shader in [&mut self.shader, &mut self.quads.shader] {
+            if !shader
+                .files
+                .iter()
+                .chain(&shader.includes)
+                .any(|f| changed(f))
+            {
+                continue;
+            }
+            match shader.reload() {
+                Ok(()) => println!("Reloaded {:?}", shader.files),
+                Err(error) => eprintln!("{error}"),
+            }
+        }
+
+        //Uniforms don't survive a reload.
+        self.quads.shader.set_i32("transforms", 1);
+        self.set_projection(&self.projection);
+    }
+
     pub fn clear(&self) {
         unsafe {
             self.gl
-                .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+                .clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT);
         }
     }
 
     pub fn draw(&mut self) {
+        self.poll_shaders();
+        self.flush();
         unsafe {
-            //When replacing the entire data store, consider using glBufferSubData rather than completely recreating the data store with glBufferData. This avoids the cost of reallocating the data store.
-            if self.buffer_size != self.vertices.len() {
-                self.gl.buffer_data_u8_slice(
-                    glow::ARRAY_BUFFER,
-                    self.vertices.align_to::<u8>().1,
-                    glow::DYNAMIC_DRAW,
-                );
-                self.buffer_size = self.vertices.len();
-            } else {
-                self.gl.buffer_sub_data_u8_slice(
-                    glow::ARRAY_BUFFER,
-                    0,
-                    self.vertices.align_to::<u8>().1,
-                );
-            }
+            self.gl.bind_vertex_array(Some(self.vao));
+            let offset = self
+                .stream
+                .upload(self.gl, self.vertices.align_to::<u8>().1);
+            //Index of the first vertex inside of the buffer.
+            let base = offset / std::mem::size_of::<Vertex>();
+            self.quads.upload(self.gl);
+
+            //Framebuffer, viewport and clips to restore when a target ends.
+            let mut targets: Vec<(Option<NativeFramebuffer>, [i32; 4], ClipStack)> = Vec::new();
+            let mut framebuffer = None;
+            self.default_blend.apply(self.gl);
 
             // self.gl.draw_arrays(glow::LINES, 0, 2);
-            self.gl
-                .draw_arrays(glow::TRIANGLES, 0, self.vertices.len() as i32);
+            for command in &self.commands {
+                match command {
+                    Command::Draw(range) => self.gl.draw_arrays(
+                        glow::TRIANGLES,
+                        (base + range.start) as i32,
+                        range.len() as i32,
+                    ),
+                    Command::DrawInstanced { instances, batch } => {
+                        self.quads.draw(self.gl, instances.clone(), *batch);
+                        self.shader.bind();
+                        self.gl.bind_vertex_array(Some(self.vao));
+                    }
+                    Command::Texture(texture) => {
+                        self.gl.active_texture(glow::TEXTURE0);
+                        self.gl.bind_texture(glow::TEXTURE_2D, Some(*texture));
+                    }
+                    Command::Blend(mode) => mode.apply(self.gl),
+                    Command::PushClip(clip) => self.clips.push(self.gl, clip.clone(), base),
+                    Command::PopClip => self.clips.pop(self.gl, base),
+                    Command::BeginTarget {
+                        framebuffer: fbo,
+                        width,
+                        height,
+                    } => {
+                        let mut viewport = [0; 4];
+                        self.gl
+                            .get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+                        targets.push((framebuffer, viewport, std::mem::take(&mut self.clips)));
+                        self.clips.apply(self.gl);
+
+                        framebuffer = Some(*fbo);
+                        self.gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+                        self.gl.viewport(0, 0, *width, *height);
+                        self.set_projection(&glm::ortho(
+                            0.0,
+                            *width as f32,
+                            0.0,
+                            *height as f32,
+                            -1.0,
+                            1.0,
+                        ));
+
+                        let mut color = [0.0; 4];
+                        self.gl
+                            .get_parameter_f32_slice(glow::COLOR_CLEAR_VALUE, &mut color);
+                        self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                        self.clear();
+                        self.gl.clear_color(color[0], color[1], color[2], color[3]);
+                    }
+                    Command::EndTarget => {
+                        let (fbo, [x, y, w, h], clips) = targets.pop().unwrap();
+                        framebuffer = fbo;
+                        self.gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+                        self.gl.viewport(x, y, w, h);
+                        self.clips = clips;
+                        self.clips.apply(self.gl);
+
+                        if framebuffer.is_none() {
+                            self.set_projection(&self.projection);
+                        } else {
+                            self.set_projection(&glm::ortho(
+                                0.0, w as f32, 0.0, h as f32, -1.0, 1.0,
+                            ));
+                        }
+                    }
+                }
+            }
+
+            if !targets.is_empty() {
+                self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                let (_, [x, y, w, h], _) = targets.swap_remove(0);
+                self.gl.viewport(x, y, w, h);
+                self.set_projection(&self.projection);
+            }
+
+            //Unbalanced clips shouldn't leak into the next frame.
+            self.clips.reset(self.gl);
+            self.stream.fence(self.gl);
         }
     }
 
-    pub fn update(&mut self, width: i32, height: i32) {
+    /// Switches how vertices are sent to the GPU, see `Renderer::stream.stats` to compare them.
+    pub fn set_upload_strategy(&mut self, strategy: UploadStrategy) {
         unsafe {
-            self.projection = glm::ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0);
-            self.gl.uniform_matrix_4_f32_slice(
-                Some(&self.projection_location),
-                false,
-                self.projection.as_slice(),
-            );
-            self.gl.viewport(0, 0, width, height);
+            self.gl.bind_vertex_array(Some(self.vao));
+            self.stream.delete(self.gl);
+            self.stream = VertexStream::new(self.gl, strategy);
+            Vertex::bind_layout(self.gl);
         }
     }
 
+    /// Takes the framebuffer size in physical pixels.
+    pub fn update(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.update_projection();
+        unsafe { self.gl.viewport(0, 0, width, height) };
+    }
+
+    /// Use `glfw::Window::get_content_scale` and `WindowEvent::ContentScale`.
+    /// Anything recorded before the change is still in the old logical size.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+        self.update_projection();
+    }
+
+    fn update_projection(&mut self) {
+        let size = self.logical_size();
+        self.projection = glm::ortho(0.0, size.x, 0.0, size.y, -1.0, 1.0);
+        self.set_projection(&self.projection);
+    }
+
+    pub fn logical_size(&self) -> Vec2 {
+        self.to_logical(Vec2::new(self.width as f32, self.height as f32))
+    }
+
+    pub fn to_logical(&self, physical: Vec2) -> Vec2 {
+        physical * (1.0 / self.scale)
+    }
+
+    pub fn to_physical(&self, logical: Vec2) -> Vec2 {
+        logical * self.scale
+    }
+
+    /// Uses `BlendMode::Alpha` until a different mode is recorded.
     pub fn enable_blend(&mut self) {
-        unsafe {
-            self.gl.enable(glow::BLEND);
-            self.gl
-                .blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_CONSTANT_ALPHA);
-        }
+        self.default_blend = BlendMode::Alpha;
+        self.default_blend.apply(self.gl);
     }
 
     pub fn disable_blend(&mut self) {
+        self.default_blend = BlendMode::Replace;
+        self.default_blend.apply(self.gl);
+    }
+
+    /// Blends in linear space and encodes to sRGB when writing to the window.
+    /// Vertex colors are still given in sRGB and converted when they're submitted.
+    /// Load color textures with `TextureOptions::srgb` so they're sampled as linear.
+    pub fn set_srgb(&mut self, srgb: bool) {
+        self.srgb = srgb;
         unsafe {
-            self.gl.disable(glow::BLEND);
+            if srgb {
+                self.gl.enable(glow::FRAMEBUFFER_SRGB);
+            } else {
+                self.gl.disable(glow::FRAMEBUFFER_SRGB);
+            }
         }
     }
 
+    /// Takes an sRGB color like everything else.
+    pub fn set_clear_color(&mut self, color: Color) {
+        let c = if self.srgb { color.to_linear() } else { color };
+        unsafe { self.gl.clear_color(c.r, c.g, c.b, c.a) };
+    }
+
     pub fn reset(&mut self) {
         self.vertices.clear();
+        self.commands.clear();
+        self.flushed = 0;
+        self.clip_depth = 0;
+        self.target_depth = 0;
+        self.texture = None;
+        self.blend = None;
+        self.quads.reset();
+        self.transform.clear();
     }
 }
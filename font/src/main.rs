@@ -1,29 +1,50 @@
 #![feature(const_maybe_uninit_zeroed)]
 use font::*;
-use glfw::{Action, Context, Key, WindowEvent};
+use glfw::{Action, Key, WindowEvent};
 extern crate nalgebra_glm as glm;
 
 //https://www.khronos.org/opengl/wiki/Face_Culling
 //By default OpenGL uses counter-clockwise winding order.
 fn main() {
-    unsafe {
-        let (width, height, mut window, events, mut glfw, gl) = create_window();
-        let mut rd = Renderer::new(gl, width, height);
-
-        // let atlas = load_font(&rd, include_bytes!("../JetBrainsMono.ttf"));
-        let atlas = load_font(&rd, include_bytes!("../CascadiaMono.ttf"));
+    let mut surface = Surface::new();
+    let mut rd = surface.renderer();
+    if cfg!(debug_assertions) {
+        rd.watch_shaders();
+    }
 
-        rd.enable_blend();
-        // rd.texture(
-        //     0.0,
-        //     0.0,
-        //     width as f32,
-        //     atlas.height as f32,
-        //     Vec4::new(1.0, 1.0, 1.0, 1.0),
-        // );
+    // let atlas = load_font(&rd, include_bytes!("../JetBrainsMono.ttf"));
+    let mut atlas = unsafe { load_font(&rd, include_bytes!("../CascadiaMono.ttf")) };
+
+    rd.enable_blend();
+
+    surface.window.set_cursor_pos_polling(true);
+    surface.window.set_mouse_button_polling(true);
+    surface.window.set_scroll_polling(true);
+    let mut pan_zoom = PanZoom::new();
+    pan_zoom.smoothing = 0.05;
+    // rd.texture(
+    //     0.0,
+    //     0.0,
+    //     width as f32,
+    //     atlas.height as f32,
+    //     Vec4::new(1.0, 1.0, 1.0, 1.0),
+    // );
+
+    while let Some(mut frame) = surface.frame(&mut rd) {
+        for event in std::mem::take(&mut frame.events) {
+            match event {
+                WindowEvent::Key(Key::Escape, _, Action::Press, _) => frame.close(),
+                WindowEvent::Key(Key::R, _, Action::Press, _) => pan_zoom.reset(),
+                _ => pan_zoom.event(frame.rd, &event),
+            }
+        }
+        pan_zoom.update(frame.delta);
+        pan_zoom.apply(frame.rd);
 
+        //Recorded every frame so the atlas can follow scale changes.
+        frame.rd.reset();
         atlas.draw_text(
-            &mut rd,
+            frame.rd,
             // "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
             // "abcdefghijklmnopqrstuvwxyz",
             // "Let's check out this epic text! Wow it works so well.",
@@ -32,32 +53,5 @@ fn main() {
             200.0,
             (1.0, 1.0, 1.0, 1.0).into(),
         );
-
-        let (width, height) = window.get_framebuffer_size();
-
-        while !window.should_close() {
-            let _current_frame = glfw.get_time() as f32;
-
-            for (_, event) in glfw::flush_messages(&events) {
-                match event {
-                    WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                        window.set_should_close(true)
-                    }
-                    WindowEvent::Close => window.set_should_close(true),
-                    _ => {}
-                }
-            }
-
-            let (w, h) = window.get_framebuffer_size();
-            if width != w || height != h {
-                rd.update(w, h);
-            }
-
-            rd.clear();
-            rd.draw();
-
-            window.swap_buffers();
-            glfw.poll_events();
-        }
     }
 }
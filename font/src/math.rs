@@ -11,6 +11,19 @@ impl Vec2 {
     }
 }
 
+impl std::ops::Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f32) -> Vec2 {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl layout::Component for Vec2 {
+    const TYPE: layout::ComponentType = layout::ComponentType::F32;
+    const COUNT: u32 = 2;
+}
+
 impl Into<Vec2> for (f32, f32) {
     fn into(self) -> Vec2 {
         Vec2 {
@@ -35,6 +48,11 @@ impl Vec4 {
     }
 }
 
+impl layout::Component for Vec4 {
+    const TYPE: layout::ComponentType = layout::ComponentType::F32;
+    const COUNT: u32 = 4;
+}
+
 impl Into<Vec4> for (f32, f32, f32, f32) {
     fn into(self) -> Vec4 {
         Vec4 {
@@ -46,6 +64,168 @@ impl Into<Vec4> for (f32, f32, f32, f32) {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Copy)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the overlapping area of both rectangles.
+    /// Rectangles that don't overlap will return a zero sized rectangle.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let top = (self.y + self.height).min(other.y + other.height);
+        Rect::new(x, y, (right - x).max(0.0), (top - y).max(0.0))
+    }
+}
+
+/// 2D affine transform.
+/// ```text
+/// | a c x |
+/// | b d y |
+/// | 0 0 1 |
+/// ```
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        x: 0.0,
+        y: 0.0,
+    };
+
+    pub const fn translation(x: f32, y: f32) -> Self {
+        Self {
+            x,
+            y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Counter clockwise rotation in radians.
+    pub fn rotation(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            x: 0.0,
+            y: 0.0,
+        }
+    }
+
+    pub const fn scaling(x: f32, y: f32) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    #[inline]
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        Vec2::new(
+            self.a * point.x + self.c * point.y + self.x,
+            self.b * point.x + self.d * point.y + self.y,
+        )
+    }
+
+    /// True when rectangles stay rectangles, translation, scale and quarter turns.
+    pub fn is_axis_aligned(&self) -> bool {
+        //A quarter turn's cosine isn't exactly zero in f32.
+        let zero = |v: f32| v.abs() < 1e-6;
+        (zero(self.b) && zero(self.c)) || (zero(self.a) && zero(self.d))
+    }
+}
+
+/// The current transform and the ones saved by `push`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TransformStack {
+    pub current: Transform,
+    pub stack: Vec<Transform>,
+}
+
+impl TransformStack {
+    /// Saves the current transform, restore it with `pop`.
+    pub fn push(&mut self) {
+        self.stack.push(self.current);
+    }
+
+    pub fn pop(&mut self) {
+        self.current = self
+            .stack
+            .pop()
+            .expect("pop_transform was called without a matching push_transform");
+    }
+
+    /// Each call applies before the transforms that came earlier, like nested coordinate spaces.
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.current = self.current * Transform::translation(x, y);
+    }
+
+    /// Counter clockwise rotation in radians around the current origin.
+    pub fn rotate(&mut self, radians: f32) {
+        self.current = self.current * Transform::rotation(radians);
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.current = self.current * Transform::scaling(x, y);
+    }
+
+    pub fn clear(&mut self) {
+        self.current = Transform::IDENTITY;
+        self.stack.clear();
+    }
+}
+
+/// `parent * child` applies `child` first.
+impl std::ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            x: self.a * rhs.x + self.c * rhs.y + self.x,
+            y: self.b * rhs.x + self.d * rhs.y + self.y,
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! vec4 {
     () => {
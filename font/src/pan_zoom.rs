@@ -0,0 +1,149 @@
+use crate::*;
+use glfw::{Action, MouseButton, WindowEvent};
+
+/// Drag to pan and scroll to zoom around the cursor.
+///
+/// Replaces the `Renderer`'s projection, positions are still given in logical pixels
+/// with a bottom left origin but are now relative to the world instead of the window.
+/// ```rs
+/// for event in &frame.events {
+///     pan_zoom.event(frame.rd, event);
+/// }
+/// pan_zoom.update(frame.delta);
+/// pan_zoom.apply(frame.rd);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanZoom {
+    /// World position at the bottom left of the window.
+    pub offset: Vec2,
+    /// Logical pixels per world unit.
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// The zoom is multiplied by this for every step scrolled.
+    pub zoom_speed: f32,
+    /// Seconds, roughly how long it takes to get 63% of the way to the target. 0 snaps.
+    pub smoothing: f32,
+    pub pan_button: MouseButton,
+    /// Where `offset` and `zoom` are heading.
+    pub target_offset: Vec2,
+    pub target_zoom: f32,
+    dragging: bool,
+    /// Last cursor position in window space.
+    cursor: Option<Vec2>,
+}
+
+impl Default for PanZoom {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::default(),
+            zoom: 1.0,
+            min_zoom: 0.1,
+            max_zoom: 10.0,
+            zoom_speed: 1.1,
+            smoothing: 0.0,
+            pan_button: MouseButton::Button1,
+            target_offset: Vec2::default(),
+            target_zoom: 1.0,
+            dragging: false,
+            cursor: None,
+        }
+    }
+}
+
+impl PanZoom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// GLFW cursor positions are expected to be in physical pixels from the top left,
+    /// which is what Windows and X11 report.
+    pub fn event(&mut self, rd: &Renderer, event: &WindowEvent) {
+        match *event {
+            WindowEvent::MouseButton(button, action, _) if button == self.pan_button => {
+                self.dragging = action != Action::Release;
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let cursor = rd.to_logical(Vec2::new(x as f32, rd.height as f32 - y as f32));
+                if let (true, Some(last)) = (self.dragging, self.cursor) {
+                    self.target_offset.x -= (cursor.x - last.x) / self.target_zoom;
+                    self.target_offset.y -= (cursor.y - last.y) / self.target_zoom;
+                }
+                self.cursor = Some(cursor);
+            }
+            WindowEvent::Scroll(_, y) => {
+                let zoom = (self.target_zoom * self.zoom_speed.powf(y as f32))
+                    .clamp(self.min_zoom, self.max_zoom);
+                //Keep the point under the cursor in place.
+                let cursor = self.cursor.unwrap_or_default();
+                let world = self.target_to_world(cursor);
+                self.target_offset =
+                    Vec2::new(world.x - cursor.x / zoom, world.y - cursor.y / zoom);
+                self.target_zoom = zoom;
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves towards the targets, `delta` is the frame time in seconds.
+    pub fn update(&mut self, delta: f32) {
+        let t = if self.smoothing <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-delta / self.smoothing).exp()
+        };
+        self.zoom += (self.target_zoom - self.zoom) * t;
+        self.offset.x += (self.target_offset.x - self.offset.x) * t;
+        self.offset.y += (self.target_offset.y - self.offset.y) * t;
+    }
+
+    /// Sets the renderer's projection, needs to run again after it's resized.
+    pub fn apply(&self, rd: &mut Renderer) {
+        let size = rd.logical_size() * (1.0 / self.zoom);
+        rd.projection = glm::ortho(
+            self.offset.x,
+            self.offset.x + size.x,
+            self.offset.y,
+            self.offset.y + size.y,
+            -1.0,
+            1.0,
+        );
+        rd.set_projection(&rd.projection);
+    }
+
+    /// Snaps back to the window's own coordinates.
+    pub fn reset(&mut self) {
+        *self = Self {
+            min_zoom: self.min_zoom,
+            max_zoom: self.max_zoom,
+            zoom_speed: self.zoom_speed,
+            smoothing: self.smoothing,
+            pan_button: self.pan_button,
+            cursor: self.cursor,
+            ..Default::default()
+        };
+    }
+
+    /// Logical window position to world position.
+    pub fn to_world(&self, window: Vec2) -> Vec2 {
+        Vec2::new(
+            self.offset.x + window.x / self.zoom,
+            self.offset.y + window.y / self.zoom,
+        )
+    }
+
+    /// World position to logical window position.
+    pub fn to_window(&self, world: Vec2) -> Vec2 {
+        Vec2::new(
+            (world.x - self.offset.x) * self.zoom,
+            (world.y - self.offset.y) * self.zoom,
+        )
+    }
+
+    fn target_to_world(&self, window: Vec2) -> Vec2 {
+        Vec2::new(
+            self.target_offset.x + window.x / self.target_zoom,
+            self.target_offset.y + window.y / self.target_zoom,
+        )
+    }
+}
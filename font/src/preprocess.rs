@@ -0,0 +1,175 @@
+use crate::*;
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// Where `#include` looks for files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceSet {
+    /// Searched in order before `embedded`.
+    pub directories: Vec<PathBuf>,
+    pub embedded: Vec<(String, &'static str)>,
+}
+
+impl SourceSet {
+    /// Includes from `font/shaders` that are compiled into the binary.
+    pub fn builtin() -> Self {
+        Self::default()
+            .embed("common.glsl", include_str!("../shaders/common.glsl"))
+            .embed("vertex.glsl", include_str!("../shaders/vertex.glsl"))
+    }
+
+    pub fn directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        let directory = directory.into();
+        if !self.directories.contains(&directory) {
+            self.directories.push(directory);
+        }
+        self
+    }
+
+    pub fn embed(mut self, name: impl Into<String>, code: &'static str) -> Self {
+        self.embedded.push((name.into(), code));
+        self
+    }
+
+    /// Returns the name used in error messages, the code and the path if it came from disk.
+    pub fn read(&self, name: &str) -> Option<(String, Cow<'static, str>, Option<PathBuf>)> {
+        for directory in &self.directories {
+            let path = directory.join(name);
+            if let Ok(code) = std::fs::read_to_string(&path) {
+                return Some((path.display().to_string(), Cow::Owned(code), Some(path)));
+            }
+        }
+
+        self.embedded
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(n, code)| (n.clone(), Cow::Borrowed(*code), None))
+    }
+}
+
+/// GLSL after `#include`s have been expanded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preprocessed {
+    pub code: String,
+    /// File names indexed by the source string number used in the `#line` directives.
+    pub files: Vec<String>,
+    /// Included files that were read from disk.
+    pub includes: Vec<PathBuf>,
+}
+
+/// Expands `#include "file"` and adds `defines` after the `#version` line.
+/// Every file is included at most once, so include guards aren't needed.
+pub fn preprocess(
+    source: &Source,
+    sources: &SourceSet,
+    defines: &[(String, String)],
+) -> Result<Preprocessed, ShaderError> {
+    let mut pp = Preprocessor {
+        sources,
+        out: Preprocessed {
+            code: String::new(),
+            files: vec![source.name.to_string()],
+            includes: Vec::new(),
+        },
+        stack: vec![source.name.to_string()],
+    };
+
+    let lines: Vec<&str> = source.code.lines().collect();
+
+    //`#version` has to come first, so the defines go right after it.
+    let start = match lines
+        .iter()
+        .position(|l| l.trim_start().starts_with("#version"))
+    {
+        Some(version) => {
+            for line in &lines[..=version] {
+                pp.out.code.push_str(line);
+                pp.out.code.push('\n');
+            }
+            version + 1
+        }
+        None => 0,
+    };
+
+    for (name, value) in defines {
+        pp.out.code.push_str(&format!("#define {name} {value}\n"));
+    }
+    pp.out.code.push_str(&format!("#line {} 0\n", start + 1));
+
+    pp.expand(&lines[start..], start, 0)?;
+    Ok(pp.out)
+}
+
+struct Preprocessor<'a> {
+    sources: &'a SourceSet,
+    out: Preprocessed,
+    /// Files currently being expanded, used to catch recursive includes.
+    stack: Vec<String>,
+}
+
+impl Preprocessor<'_> {
+    /// `offset` is the number of lines before `lines` and `index` is the source string number.
+    fn expand(&mut self, lines: &[&str], offset: usize, index: usize) -> Result<(), ShaderError> {
+        let current = self.out.files[index].clone();
+        for (i, line) in lines.iter().enumerate() {
+            let number = offset + i + 1;
+            let error = |message: String| {
+                ShaderError::Preprocess(Diagnostic {
+                    file: current.clone(),
+                    line: Some(number as u32),
+                    message,
+                })
+            };
+
+            let Some(include) = line.trim_start().strip_prefix("#include") else {
+                self.out.code.push_str(line);
+                self.out.code.push('\n');
+                continue;
+            };
+
+            let name = include
+                .trim()
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .ok_or_else(|| error(format!("expected #include \"file\", found {line}")))?;
+            let (file, code, path) = self
+                .sources
+                .read(name)
+                .ok_or_else(|| error(format!("can't find include \"{name}\"")))?;
+
+            if self.stack.contains(&file) {
+                return Err(error(format!("\"{name}\" includes itself")));
+            }
+            if self.out.files.contains(&file) {
+                //Already included, keep the line numbers intact.
+                self.out.code.push('\n');
+                continue;
+            }
+
+            self.out.files.push(file.clone());
+            self.out.includes.extend(path);
+            let child = self.out.files.len() - 1;
+
+            //Included files can't change the version.
+            let lines: Vec<&str> = code
+                .lines()
+                .map(|l| {
+                    if l.trim_start().starts_with("#version") {
+                        ""
+                    } else {
+                        l
+                    }
+                })
+                .collect();
+
+            self.stack.push(file);
+            self.out.code.push_str(&format!("#line 1 {child}\n"));
+            self.expand(&lines, 0, child)?;
+            self.out
+                .code
+                .push_str(&format!("#line {} {index}\n", number + 1));
+            self.stack.pop();
+        }
+        Ok(())
+    }
+}
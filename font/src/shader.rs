@@ -0,0 +1,510 @@
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+}
+
+impl Stage {
+    pub fn gl(self) -> u32 {
+        match self {
+            Stage::Vertex => glow::VERTEX_SHADER,
+            Stage::Fragment => glow::FRAGMENT_SHADER,
+        }
+    }
+}
+
+/// One line of a compiler log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// `None` when the driver didn't print a line number.
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderError {
+    Compile {
+        stage: Stage,
+        diagnostics: Vec<Diagnostic>,
+    },
+    Link(String),
+    /// `glCreateShader` or `glCreateProgram` failed.
+    Create(String),
+    /// A source file couldn't be read.
+    Io(PathBuf, String),
+    /// Bad or missing `#include`.
+    Preprocess(Diagnostic),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Compile { stage, diagnostics } => {
+                writeln!(f, "failed to compile {stage:?} shader")?;
+                for diagnostic in diagnostics {
+                    writeln!(f, "{diagnostic}")?;
+                }
+                Ok(())
+            }
+            ShaderError::Link(log) => write!(f, "failed to link program: {log}"),
+            ShaderError::Create(error) => write!(f, "failed to create shader: {error}"),
+            ShaderError::Io(path, error) => write!(f, "failed to read {}: {error}", path.display()),
+            ShaderError::Preprocess(diagnostic) => write!(f, "{diagnostic}"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Shader source and the name used in error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Source<'a> {
+    pub name: &'a str,
+    pub code: &'a str,
+}
+
+impl<'a> Source<'a> {
+    pub const fn new(name: &'a str, code: &'a str) -> Self {
+        Self { name, code }
+    }
+}
+
+/// Preprocessor settings shared by every stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderOptions {
+    pub sources: SourceSet,
+    /// Added after `#version`, used to compile variants of the same shader.
+    pub defines: Vec<(String, String)>,
+}
+
+impl Default for ShaderOptions {
+    fn default() -> Self {
+        Self {
+            sources: SourceSet::builtin(),
+            defines: Vec::new(),
+        }
+    }
+}
+
+impl ShaderOptions {
+    pub fn define(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.defines.push((name.into(), value.to_string()));
+        self
+    }
+    pub fn sources(mut self, sources: SourceSet) -> Self {
+        self.sources = sources;
+        self
+    }
+}
+
+/// Creates a `Source` named after the file.
+/// ```rs
+/// let shader = Shader::new(gl, source!("../shaders/simple.vert"), source!("../shaders/text.frag"))?;
+/// ```
+#[macro_export]
+macro_rules! source {
+    ($path:literal) => {
+        $crate::Source::new($path, include_str!($path))
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attribute {
+    pub location: u32,
+    /// Array length, 1 for anything that isn't an array.
+    pub size: i32,
+    /// `glow::FLOAT_VEC2`, `glow::FLOAT_MAT4` etc.
+    pub ty: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uniform {
+    pub location: NativeUniformLocation,
+    pub size: i32,
+    pub ty: u32,
+}
+
+/// Linked program with reflected attributes and uniforms.
+pub struct Shader {
+    pub gl: &'static glow::Context,
+    pub program: NativeProgram,
+    pub attributes: HashMap<String, Attribute>,
+    /// Arrays are stored as both `name` and `name[0]`.
+    pub uniforms: HashMap<String, Uniform>,
+    /// Vertex and fragment files that `reload` reads from.
+    /// Set by `from_files`, embedded shaders can point this at their original files.
+    pub files: Vec<PathBuf>,
+    /// Files on disk pulled in with `#include`.
+    pub includes: Vec<PathBuf>,
+    pub options: ShaderOptions,
+}
+
+impl fmt::Debug for Shader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shader")
+            .field("program", &self.program)
+            .field("attributes", &self.attributes)
+            .field("uniforms", &self.uniforms)
+            .field("files", &self.files)
+            .field("includes", &self.includes)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Shader {
+    pub fn new(
+        gl: &'static glow::Context,
+        vertex: Source,
+        fragment: Source,
+    ) -> Result<Self, ShaderError> {
+        Self::with_options(gl, vertex, fragment, ShaderOptions::default())
+    }
+
+    pub fn with_options(
+        gl: &'static glow::Context,
+        vertex: Source,
+        fragment: Source,
+        options: ShaderOptions,
+    ) -> Result<Self, ShaderError> {
+        let vertex = preprocess(&vertex, &options.sources, &options.defines)?;
+        let fragment = preprocess(&fragment, &options.sources, &options.defines)?;
+        let program = link(
+            gl,
+            &[(Stage::Vertex, &vertex), (Stage::Fragment, &fragment)],
+        )?;
+
+        let mut includes = vertex.includes;
+        for path in fragment.includes {
+            if !includes.contains(&path) {
+                includes.push(path);
+            }
+        }
+
+        let mut shader = Self {
+            gl,
+            program,
+            attributes: HashMap::new(),
+            uniforms: HashMap::new(),
+            files: Vec::new(),
+            includes,
+            options,
+        };
+        shader.reflect();
+        Ok(shader)
+    }
+
+    /// Reads the sources at runtime so the shader can be reloaded.
+    /// Includes are looked up next to the files before `options.sources`.
+    pub fn from_files(
+        gl: &'static glow::Context,
+        vertex: impl AsRef<Path>,
+        fragment: impl AsRef<Path>,
+        options: ShaderOptions,
+    ) -> Result<Self, ShaderError> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map_err(|error| ShaderError::Io(path.to_path_buf(), error.to_string()))
+        };
+        let (vertex, fragment) = (vertex.as_ref(), fragment.as_ref());
+        let (vertex_code, fragment_code) = (read(vertex)?, read(fragment)?);
+        let (vertex_name, fragment_name) = (vertex.to_string_lossy(), fragment.to_string_lossy());
+
+        let mut sources = SourceSet::default();
+        for directory in [vertex.parent(), fragment.parent()].into_iter().flatten() {
+            sources = sources.directory(directory);
+        }
+        sources
+            .directories
+            .extend(options.sources.directories.iter().cloned());
+        sources.embedded = options.sources.embedded.clone();
+
+        let mut shader = Self::with_options(
+            gl,
+            Source::new(&vertex_name, &vertex_code),
+            Source::new(&fragment_name, &fragment_code),
+            options.clone().sources(sources),
+        )?;
+        shader.files = vec![vertex.to_path_buf(), fragment.to_path_buf()];
+        shader.options = options;
+        Ok(shader)
+    }
+
+    /// Recompiles from `files` and swaps in the new program.
+    /// On failure the old program is kept and still usable.
+    /// Uniform values aren't carried over.
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        let [vertex, fragment] = self.files.as_slice() else {
+            return Ok(());
+        };
+        let shader = Self::from_files(self.gl, vertex, fragment, self.options.clone())?;
+        std::mem::replace(self, shader).delete();
+        Ok(())
+    }
+
+    fn reflect(&mut self) {
+        let (gl, program) = (self.gl, self.program);
+        unsafe {
+            for i in 0..gl.get_active_attributes(program) {
+                let Some(active) = gl.get_active_attribute(program, i) else {
+                    continue;
+                };
+                //Built-ins like `gl_VertexID` don't have a location.
+                if let Some(location) = gl.get_attrib_location(program, &active.name) {
+                    self.attributes.insert(
+                        active.name,
+                        Attribute {
+                            location,
+                            size: active.size,
+                            ty: active.atype,
+                        },
+                    );
+                }
+            }
+
+            for i in 0..gl.get_active_uniforms(program) {
+                let Some(active) = gl.get_active_uniform(program, i) else {
+                    continue;
+                };
+                //Uniforms inside of blocks don't have a location.
+                let Some(location) = gl.get_uniform_location(program, &active.name) else {
+                    continue;
+                };
+                let uniform = Uniform {
+                    location,
+                    size: active.size,
+                    ty: active.utype,
+                };
+                if let Some(name) = active.name.strip_suffix("[0]") {
+                    self.uniforms.insert(name.to_string(), uniform.clone());
+                }
+                self.uniforms.insert(active.name, uniform);
+            }
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe { self.gl.use_program(Some(self.program)) };
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<u32> {
+        self.attributes.get(name).map(|a| a.location)
+    }
+
+    /// Uniforms that the compiler optimized away return `None`.
+    pub fn uniform(&self, name: &str) -> Option<&NativeUniformLocation> {
+        self.uniforms.get(name).map(|u| &u.location)
+    }
+
+    //Setters bind the program and silently skip unknown uniforms, just like a location of -1 would.
+
+    pub fn set_i32(&self, name: &str, value: i32) {
+        self.bind();
+        unsafe { self.gl.uniform_1_i32(self.uniform(name), value) };
+    }
+
+    pub fn set_f32(&self, name: &str, value: f32) {
+        self.bind();
+        unsafe { self.gl.uniform_1_f32(self.uniform(name), value) };
+    }
+
+    pub fn set_vec2(&self, name: &str, value: Vec2) {
+        self.bind();
+        unsafe { self.gl.uniform_2_f32(self.uniform(name), value.x, value.y) };
+    }
+
+    pub fn set_vec3(&self, name: &str, value: &glm::Vec3) {
+        self.bind();
+        unsafe {
+            self.gl
+                .uniform_3_f32_slice(self.uniform(name), value.as_slice())
+        };
+    }
+
+    pub fn set_vec4(&self, name: &str, value: Vec4) {
+        self.bind();
+        unsafe {
+            self.gl
+                .uniform_4_f32(self.uniform(name), value.x, value.y, value.z, value.w)
+        };
+    }
+
+    pub fn set_mat3(&self, name: &str, value: &glm::Mat3) {
+        self.bind();
+        unsafe {
+            self.gl
+                .uniform_matrix_3_f32_slice(self.uniform(name), false, value.as_slice())
+        };
+    }
+
+    pub fn set_mat4(&self, name: &str, value: &glm::Mat4) {
+        self.bind();
+        unsafe {
+            self.gl
+                .uniform_matrix_4_f32_slice(self.uniform(name), false, value.as_slice())
+        };
+    }
+
+    pub fn delete(self) {
+        unsafe { self.gl.delete_program(self.program) };
+    }
+}
+
+/// Compiles and links the stages, the shader objects are deleted afterwards.
+pub fn link(
+    gl: &glow::Context,
+    stages: &[(Stage, &Preprocessed)],
+) -> Result<NativeProgram, ShaderError> {
+    unsafe {
+        let program = gl.create_program().map_err(ShaderError::Create)?;
+
+        let mut shaders = Vec::new();
+        let mut result = Ok(());
+        for (stage, source) in stages {
+            match compile(gl, *stage, source) {
+                Ok(shader) => {
+                    gl.attach_shader(program, shader);
+                    shaders.push(shader);
+                }
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        if result.is_ok() {
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                result = Err(ShaderError::Link(gl.get_program_info_log(program)));
+            }
+        }
+
+        for shader in shaders {
+            gl.detach_shader(program, shader);
+            gl.delete_shader(shader);
+        }
+
+        match result {
+            Ok(()) => Ok(program),
+            Err(error) => {
+                gl.delete_program(program);
+                Err(error)
+            }
+        }
+    }
+}
+
+pub fn compile(
+    gl: &glow::Context,
+    stage: Stage,
+    source: &Preprocessed,
+) -> Result<NativeShader, ShaderError> {
+    unsafe {
+        let shader = gl.create_shader(stage.gl()).map_err(ShaderError::Create)?;
+        gl.shader_source(shader, &source.code);
+        gl.compile_shader(shader);
+
+        if gl.get_shader_compile_status(shader) {
+            return Ok(shader);
+        }
+
+        let log = gl.get_shader_info_log(shader);
+        gl.delete_shader(shader);
+        Err(ShaderError::Compile {
+            stage,
+            diagnostics: parse_log(&log, &source.files),
+        })
+    }
+}
+
+/// Parses the driver's info log into diagnostics.
+/// `files` maps the source string number at the start of each line to a file name.
+///
+/// Handles the common formats:
+/// - Mesa, Intel and AMD: `0:12(5): error: ...`
+/// - Nvidia: `0(12) : error C0000: ...`
+/// - Older AMD: `ERROR: 0:12: ...`
+pub fn parse_log(log: &str, files: &[impl AsRef<str>]) -> Vec<Diagnostic> {
+    let file = |index: usize| {
+        files
+            .get(index)
+            .or(files.first())
+            .map(|f| f.as_ref().to_string())
+            .unwrap_or_default()
+    };
+
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (prefix, rest) = match line.split_once(": ") {
+                Some((severity, rest))
+                    if severity.eq_ignore_ascii_case("error")
+                        || severity.eq_ignore_ascii_case("warning") =>
+                {
+                    (format!("{severity}: "), rest)
+                }
+                _ => (String::new(), line),
+            };
+
+            match location(rest) {
+                Some((index, line, message)) => Diagnostic {
+                    file: file(index),
+                    line: Some(line),
+                    message: format!("{prefix}{message}"),
+                },
+                None => Diagnostic {
+                    file: file(0),
+                    line: None,
+                    message: line.to_string(),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Splits `0:12(5): message` or `0(12) : message` into the source index, line and message.
+fn location(text: &str) -> Option<(usize, u32, &str)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+    let end = digits(text);
+    let index = text[..end].parse().ok()?;
+    let text = &text[end..];
+
+    let (line, text) = if let Some(text) = text.strip_prefix(':') {
+        let end = digits(text);
+        let line = text[..end].parse().ok()?;
+        let text = &text[end..];
+        //Skip the column.
+        let text = match text.strip_prefix('(') {
+            Some(text) => &text[text.find(')')? + 1..],
+            None => text,
+        };
+        (line, text)
+    } else if let Some(text) = text.strip_prefix('(') {
+        let end = digits(text);
+        let line = text[..end].parse().ok()?;
+        (line, text[end..].strip_prefix(')')?)
+    } else {
+        return None;
+    };
+
+    let message = text.trim_start().strip_prefix(':')?.trim();
+    Some((index, line, message))
+}
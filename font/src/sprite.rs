@@ -0,0 +1,208 @@
+use crate::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Space between sprites so linear filtering doesn't bleed into neighbours.
+const PADDING: u32 = 1;
+
+/// Many small images packed into one texture.
+#[derive(Debug)]
+pub struct SpriteAtlas {
+    pub texture: TextureHandle,
+    pub width: u32,
+    pub height: u32,
+    /// Sub-rectangles in pixels, starting at the top left of the texture.
+    pub sprites: HashMap<String, Rect>,
+}
+
+impl SpriteAtlas {
+    /// Packs every image inside of `path`. Sprites are named after the file stem.
+    pub fn from_directory(
+        gl: &glow::Context,
+        textures: &mut TextureRegistry,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> image::ImageResult<Self> {
+        let mut paths: Vec<_> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+            .collect();
+        paths.sort();
+
+        let mut images = Vec::new();
+        for path in paths {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            images.push((name, image::open(&path)?.to_rgba8()));
+        }
+
+        Ok(Self::from_images(gl, textures, images, options))
+    }
+
+    pub fn from_images(
+        gl: &glow::Context,
+        textures: &mut TextureRegistry,
+        images: Vec<(String, image::RgbaImage)>,
+        options: TextureOptions,
+    ) -> Self {
+        let sizes: Vec<(u32, u32)> = images.iter().map(|(_, im)| im.dimensions()).collect();
+        let (width, height, rects) = pack(&sizes, PADDING);
+
+        let mut atlas = image::RgbaImage::new(width, height);
+        let mut sprites = HashMap::new();
+        for ((name, im), rect) in images.into_iter().zip(rects) {
+            image::imageops::replace(&mut atlas, &im, rect.x as i64, rect.y as i64);
+            sprites.insert(name, rect);
+        }
+
+        Self {
+            texture: textures.insert_image(gl, &atlas, options),
+            width,
+            height,
+            sprites,
+        }
+    }
+
+    #[track_caller]
+    pub fn get(&self, name: &str) -> Rect {
+        match self.sprites.get(name) {
+            Some(rect) => *rect,
+            None => panic!("sprite '{name}' is not in the atlas"),
+        }
+    }
+}
+
+/// Shelf packer, sprites are sorted by height and placed left to right in rows.
+/// Returns the atlas size and a rectangle for each size in the same order.
+pub fn pack(sizes: &[(u32, u32)], padding: u32) -> (u32, u32, Vec<Rect>) {
+    let area: u32 = sizes
+        .iter()
+        .map(|(w, h)| (w + padding) * (h + padding))
+        .sum();
+    let widest = sizes.iter().map(|(w, _)| w + padding).max().unwrap_or(0);
+    let width = ((area as f32).sqrt().ceil() as u32)
+        .next_power_of_two()
+        .max(widest);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1));
+
+    let mut rects = vec![Rect::default(); sizes.len()];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+
+    for i in order {
+        let (w, h) = sizes[i];
+        if x + w > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        rects[i] = Rect::new(x as f32, y as f32, w as f32, h as f32);
+        x += w + padding;
+        row_height = row_height.max(h + padding);
+    }
+
+    (width, y + row_height, rects)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Stops on the last frame.
+    Once,
+    Loop,
+    /// Plays forwards then backwards.
+    PingPong,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Name of the sprite inside of the atlas.
+    pub sprite: String,
+    /// Seconds.
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    pub frames: Vec<Frame>,
+    pub mode: LoopMode,
+    /// Seconds since the animation started.
+    pub time: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(frames: &[(&str, f32)], mode: LoopMode) -> Self {
+        assert!(!frames.is_empty(), "animation needs at least one frame");
+        Self {
+            frames: frames
+                .iter()
+                .map(|(sprite, duration)| Frame {
+                    sprite: sprite.to_string(),
+                    duration: *duration,
+                })
+                .collect(),
+            mode,
+            time: 0.0,
+        }
+    }
+
+    /// Every frame with the same duration.
+    pub fn uniform(sprites: &[&str], duration: f32, mode: LoopMode) -> Self {
+        let frames: Vec<(&str, f32)> = sprites.iter().map(|s| (*s, duration)).collect();
+        Self::new(&frames, mode)
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.mode == LoopMode::Once && self.time >= self.duration()
+    }
+
+    pub fn index(&self) -> usize {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0;
+        }
+
+        let last = self.frames.len() - 1;
+        let time = match self.mode {
+            LoopMode::Once if self.time >= duration => return last,
+            LoopMode::Once => self.time,
+            LoopMode::Loop => self.time % duration,
+            LoopMode::PingPong => {
+                let time = self.time % (duration * 2.0);
+                if time >= duration {
+                    //Play backwards.
+                    return last - self.frame_at(time - duration);
+                }
+                time
+            }
+        };
+        self.frame_at(time)
+    }
+
+    fn frame_at(&self, time: f32) -> usize {
+        let mut end = 0.0;
+        for (i, frame) in self.frames.iter().enumerate() {
+            end += frame.duration;
+            if time < end {
+                return i;
+            }
+        }
+        self.frames.len() - 1
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frames[self.index()]
+    }
+}
@@ -0,0 +1,175 @@
+use crate::*;
+use glfw::{Context, WindowEvent};
+
+/// New framebuffer size in physical pixels and the content scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resize {
+    pub width: i32,
+    pub height: i32,
+    pub scale: f32,
+}
+
+pub type ResizeCallback = Box<dyn FnMut(&mut Renderer, Resize)>;
+
+/// Owns the window and keeps a `Renderer` in sync with its size and scale.
+///
+/// ```rs
+/// let mut surface = Surface::new();
+/// let mut rd = surface.renderer();
+/// while let Some(frame) = surface.frame(&mut rd) {
+///     frame.rd.reset();
+///     //Record commands...
+/// } //Dropping the frame draws and presents it.
+/// ```
+pub struct Surface {
+    pub window: glfw::Window,
+    pub events: std::sync::mpsc::Receiver<(f64, glfw::WindowEvent)>,
+    pub glfw: glfw::Glfw,
+    pub gl: &'static glow::Context,
+    /// Framebuffer size in physical pixels.
+    pub width: i32,
+    pub height: i32,
+    pub scale: f32,
+    /// Resized with the framebuffer, see `create_target`.
+    pub targets: Vec<RenderTarget>,
+    pub resize_callbacks: Vec<ResizeCallback>,
+    /// Time at the start of the last frame.
+    pub time: f64,
+}
+
+impl Surface {
+    pub fn new() -> Self {
+        let (width, height, mut window, events, glfw, gl) = create_window();
+        window.set_framebuffer_size_polling(true);
+        let scale = window.get_content_scale().0;
+        let time = glfw.get_time();
+
+        Self {
+            window,
+            events,
+            glfw,
+            gl,
+            width,
+            height,
+            scale,
+            targets: Vec::new(),
+            resize_callbacks: Vec::new(),
+            time,
+        }
+    }
+
+    /// Renderer that matches the current framebuffer size and scale.
+    pub fn renderer(&self) -> Renderer {
+        let mut rd = Renderer::new(self.gl, self.width, self.height);
+        rd.set_scale(self.scale);
+        rd
+    }
+
+    /// Called once per change, after the renderer and targets have been updated.
+    pub fn on_resize(&mut self, callback: impl FnMut(&mut Renderer, Resize) + 'static) {
+        self.resize_callbacks.push(Box::new(callback));
+    }
+
+    /// Framebuffer sized target, returns the index into `targets`.
+    pub fn create_target(&mut self, depth_stencil: bool) -> usize {
+        let target = RenderTarget::new(self.gl, self.width, self.height, depth_stencil);
+        self.targets.push(target);
+        self.targets.len() - 1
+    }
+
+    /// Polls events and applies any resize. Returns `None` once the window should close.
+    pub fn frame<'a>(&'a mut self, rd: &'a mut Renderer) -> Option<SurfaceFrame<'a>> {
+        self.glfw.poll_events();
+        if self.window.should_close() {
+            return None;
+        }
+
+        let (mut width, mut height, mut scale) = (self.width, self.height, self.scale);
+        let mut events = Vec::new();
+        for (_, event) in glfw::flush_messages(&self.events) {
+            match event {
+                WindowEvent::FramebufferSize(w, h) => (width, height) = (w, h),
+                WindowEvent::ContentScale(x, _) => scale = x,
+                _ => {}
+            }
+            events.push(event);
+        }
+
+        //Minimized windows report a 0x0 framebuffer, keep the last size until they're restored.
+        let resized = if (width, height, scale) != (self.width, self.height, self.scale)
+            && width > 0
+            && height > 0
+        {
+            Some(self.resize(rd, width, height, scale))
+        } else {
+            None
+        };
+
+        let time = self.glfw.get_time();
+        let delta = (time - self.time) as f32;
+        self.time = time;
+
+        Some(SurfaceFrame {
+            surface: self,
+            rd,
+            events,
+            resized,
+            delta,
+        })
+    }
+
+    fn resize(&mut self, rd: &mut Renderer, width: i32, height: i32, scale: f32) -> Resize {
+        self.width = width;
+        self.height = height;
+        if scale != self.scale {
+            self.scale = scale;
+            rd.set_scale(scale);
+        }
+        rd.update(width, height);
+        for target in &mut self.targets {
+            target.resize(self.gl, width, height);
+        }
+
+        let resize = Resize {
+            width,
+            height,
+            scale,
+        };
+        for callback in &mut self.resize_callbacks {
+            callback(rd, resize);
+        }
+        resize
+    }
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clears, draws and swaps buffers when it's dropped.
+/// Not to be confused with a sprite animation `Frame`.
+pub struct SurfaceFrame<'a> {
+    pub surface: &'a mut Surface,
+    pub rd: &'a mut Renderer,
+    pub events: Vec<WindowEvent>,
+    /// Set when the framebuffer size or scale changed since the last frame.
+    pub resized: Option<Resize>,
+    /// Seconds since the last frame.
+    pub delta: f32,
+}
+
+impl SurfaceFrame<'_> {
+    pub fn close(&mut self) {
+        self.surface.window.set_should_close(true);
+    }
+}
+
+impl Drop for SurfaceFrame<'_> {
+    fn drop(&mut self) {
+        self.rd.clear();
+        self.rd.draw();
+        self.surface.window.swap_buffers();
+    }
+}
@@ -0,0 +1,151 @@
+use crate::*;
+
+/// Offscreen framebuffer with a color texture and an optional depth/stencil buffer.
+#[derive(Debug)]
+pub struct RenderTarget {
+    pub framebuffer: NativeFramebuffer,
+    pub texture: NativeTexture,
+    pub depth_stencil: Option<NativeRenderbuffer>,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl RenderTarget {
+    pub fn new(gl: &glow::Context, width: i32, height: i32, depth_stencil: bool) -> Self {
+        unsafe {
+            let framebuffer = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+
+            let texture = gl.create_texture().unwrap();
+            let depth_stencil = if depth_stencil {
+                Some(gl.create_renderbuffer().unwrap())
+            } else {
+                None
+            };
+
+            let mut target = Self {
+                framebuffer,
+                texture,
+                depth_stencil,
+                width,
+                height,
+            };
+            target.allocate(gl);
+
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            if let Some(rbo) = depth_stencil {
+                gl.framebuffer_renderbuffer(
+                    glow::FRAMEBUFFER,
+                    glow::DEPTH_STENCIL_ATTACHMENT,
+                    glow::RENDERBUFFER,
+                    Some(rbo),
+                );
+            }
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                panic!("Framebuffer is incomplete: 0x{:X}", status);
+            }
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            target
+        }
+    }
+
+    /// (Re)allocates the storage of every attachment with the current size.
+    unsafe fn allocate(&mut self, gl: &glow::Context) {
+        gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_S,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_T,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA8 as i32,
+            self.width,
+            self.height,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            None,
+        );
+
+        if let Some(rbo) = self.depth_stencil {
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(rbo));
+            gl.renderbuffer_storage(
+                glow::RENDERBUFFER,
+                glow::DEPTH24_STENCIL8,
+                self.width,
+                self.height,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+        }
+    }
+
+    pub fn resize(&mut self, gl: &glow::Context, width: i32, height: i32) {
+        if self.width == width && self.height == height {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        unsafe { self.allocate(gl) };
+    }
+
+    /// Copies the color attachment into an image with a top left origin.
+    pub fn read_pixels(&self, gl: &glow::Context) -> image::RgbaImage {
+        let mut pixels = vec![0; (self.width * self.height * 4) as usize];
+        unsafe {
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        }
+
+        //OpenGL starts at the bottom left.
+        let mut image =
+            image::RgbaImage::from_raw(self.width as u32, self.height as u32, pixels).unwrap();
+        image::imageops::flip_vertical_in_place(&mut image);
+        image
+    }
+
+    pub fn delete(self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_texture(self.texture);
+            if let Some(rbo) = self.depth_stencil {
+                gl.delete_renderbuffer(rbo);
+            }
+        }
+    }
+}
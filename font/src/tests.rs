@@ -1,18 +1,18 @@
 pub use crate::*;
-use glfw::{Action, Context, Key, WindowEvent};
+use glfw::{Action, Key, WindowEvent};
 
 #[test]
 pub fn pixel_perfect() {
-    let (width, height, mut window, events, mut glfw, gl) = create_window();
-    let mut rd = Renderer::new(gl, width, height);
+    let mut surface = Surface::new();
+    let mut rd = surface.renderer();
+    let gl = surface.gl;
 
-    let simple = shader! {
-        include_str!("../shaders/simple.vert"),
-        include_str!("../shaders/simple.frag"),
-        Vec2 => 0,
-        Vec2 => 1,
-        Vec4 => 2
-    };
+    let simple = Shader::new(
+        gl,
+        source!("../shaders/simple.vert"),
+        source!("../shaders/simple.frag"),
+    )
+    .unwrap();
     rd.use_shader(simple);
 
     for i in 0..5 {
@@ -31,27 +31,471 @@ pub fn pixel_perfect() {
         );
     }
 
-    let (width, height) = window.get_framebuffer_size();
-    while !window.should_close() {
-        let _current_frame = glfw.get_time() as f32;
-
-        for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                WindowEvent::Key(Key::Escape, _, Action::Press, _) => window.set_should_close(true),
-                WindowEvent::Close => window.set_should_close(true),
-                _ => {}
+    while let Some(mut frame) = surface.frame(&mut rd) {
+        for event in std::mem::take(&mut frame.events) {
+            if let WindowEvent::Key(Key::Escape, _, Action::Press, _) = event {
+                frame.close();
             }
         }
+    }
+}
 
-        let (w, h) = window.get_framebuffer_size();
-        if width != w || height != h {
-            rd.update(w, h);
+#[test]
+pub fn render_target() {
+    let (width, height, _window, _events, _glfw, gl) = create_window();
+    let mut rd = Renderer::new(gl, width, height);
+
+    let simple = Shader::new(
+        gl,
+        source!("../shaders/simple.vert"),
+        source!("../shaders/simple.frag"),
+    )
+    .unwrap();
+    rd.use_shader(simple);
+
+    let target = RenderTarget::new(gl, 64, 64, true);
+    rd.begin_target(&target);
+    rd.quad(16.0, 16.0, 32.0, 32.0, hex(0xcc3e44));
+    rd.end_target();
+    rd.draw();
+
+    let image = target.read_pixels(gl);
+    assert_eq!(image.dimensions(), (64, 64));
+    assert_eq!(image.get_pixel(32, 32).0, [0xcc, 0x3e, 0x44, 0xff]);
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+
+    target.delete(gl);
+}
+
+#[test]
+pub fn sprite_packing() {
+    let sizes = [(32, 32), (64, 16), (16, 48), (8, 8), (128, 20)];
+    let (width, height, rects) = pack(&sizes, 1);
+    assert_eq!(rects.len(), sizes.len());
+
+    for (i, (a, &(w, h))) in rects.iter().zip(&sizes).enumerate() {
+        assert_eq!((a.width, a.height), (w as f32, h as f32));
+        assert!(a.x + a.width <= width as f32 && a.y + a.height <= height as f32);
+
+        for b in &rects[i + 1..] {
+            let overlap = a.intersect(b);
+            assert!(overlap.width == 0.0 || overlap.height == 0.0);
         }
+    }
+}
+
+#[test]
+pub fn sprite_animation() {
+    let mut anim = SpriteAnimation::uniform(&["a", "b", "c"], 0.25, LoopMode::PingPong);
+    let mut frames = Vec::new();
+    for _ in 0..6 {
+        frames.push(anim.frame().sprite.clone());
+        anim.update(0.25);
+    }
+    assert_eq!(frames, ["a", "b", "c", "c", "b", "a"]);
+
+    let mut anim = SpriteAnimation::uniform(&["a", "b"], 0.25, LoopMode::Once);
+    anim.update(1.0);
+    assert!(anim.finished());
+    assert_eq!(anim.frame().sprite, "b");
+}
+
+#[test]
+pub fn rect_intersect() {
+    let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+    let b = Rect::new(5.0, -5.0, 10.0, 10.0);
+    assert_eq!(a.intersect(&b), Rect::new(5.0, 0.0, 5.0, 5.0));
+    assert_eq!(b.intersect(&a), a.intersect(&b));
+
+    //Nested rectangles give the inner one.
+    let inner = Rect::new(2.0, 3.0, 4.0, 5.0);
+    assert_eq!(a.intersect(&inner), inner);
+    assert_eq!(inner.intersect(&a), inner);
+
+    //Disjoint and touching rectangles are empty, never negative.
+    let far = Rect::new(20.0, 20.0, 5.0, 5.0);
+    let empty = a.intersect(&far);
+    assert_eq!((empty.width, empty.height), (0.0, 0.0));
+    let touching = a.intersect(&Rect::new(10.0, 0.0, 5.0, 10.0));
+    assert_eq!((touching.width, touching.height), (0.0, 10.0));
+
+    //Intersecting with an empty result stays empty.
+    let empty = empty.intersect(&a);
+    assert_eq!((empty.width, empty.height), (0.0, 0.0));
+}
+
+#[test]
+pub fn clip_stack() {
+    let mut clips = ClipStack::default();
+    assert_eq!(clips.scissor(), None);
+
+    //Stencil clips don't affect the scissor.
+    clips.stack.push(Clip::Stencil(0..6));
+    clips.depth += 1;
+    assert_eq!(clips.scissor(), None);
+
+    //Nested rectangles scissor to their intersection.
+    clips
+        .stack
+        .push(Clip::Rect(Rect::new(0.0, 0.0, 100.0, 100.0)));
+    clips
+        .stack
+        .push(Clip::Rect(Rect::new(50.0, 25.0, 100.0, 50.0)));
+    clips.stack.push(Clip::Stencil(6..12));
+    clips.depth += 1;
+    assert_eq!(clips.scissor(), Some(Rect::new(50.0, 25.0, 50.0, 50.0)));
+
+    //A disjoint child scissors everything away instead of dropping the clip.
+    clips
+        .stack
+        .push(Clip::Rect(Rect::new(200.0, 200.0, 10.0, 10.0)));
+    let scissor = clips.scissor().unwrap();
+    assert_eq!((scissor.width, scissor.height), (0.0, 0.0));
+
+    //Popping restores each parent.
+    assert_eq!(
+        clips.take(),
+        Clip::Rect(Rect::new(200.0, 200.0, 10.0, 10.0))
+    );
+    assert_eq!(clips.scissor(), Some(Rect::new(50.0, 25.0, 50.0, 50.0)));
+    assert_eq!(clips.take(), Clip::Stencil(6..12));
+    assert_eq!(clips.depth, 1);
+    clips.take();
+    assert_eq!(clips.scissor(), Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+    clips.take();
+    assert_eq!(clips.scissor(), None);
+    clips.take();
+    assert_eq!(clips.depth, 0);
+    assert!(clips.stack.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "without a matching push_clip")]
+pub fn clip_stack_underflow() {
+    ClipStack::default().take();
+}
+
+#[test]
+pub fn transform_stack() {
+    let close = |a: Vec2, b: Vec2| {
+        assert!(
+            (a.x - b.x).abs() < 1e-5 && (a.y - b.y).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        )
+    };
+    let quarter = std::f32::consts::FRAC_PI_2;
+    let point = Vec2::new(1.0, 0.0);
+
+    //Later calls apply first, so this rotates the point then moves it.
+    let mut transforms = TransformStack::default();
+    transforms.translate(10.0, 0.0);
+    transforms.rotate(quarter);
+    close(transforms.current.apply(point), Vec2::new(10.0, 1.0));
+
+    //Moving first rotates the offset as well.
+    let mut transforms = TransformStack::default();
+    transforms.rotate(quarter);
+    transforms.translate(10.0, 0.0);
+    close(transforms.current.apply(point), Vec2::new(0.0, 11.0));
+
+    let mut transforms = TransformStack::default();
+    transforms.translate(5.0, 5.0);
+    transforms.scale(2.0, 3.0);
+    close(
+        transforms.current.apply(Vec2::new(1.0, 1.0)),
+        Vec2::new(7.0, 8.0),
+    );
+    assert!(transforms.current.is_axis_aligned());
+
+    //Popping restores the transform from before the push.
+    let saved = transforms.current;
+    transforms.push();
+    transforms.rotate(quarter / 2.0);
+    assert!(!transforms.current.is_axis_aligned());
+    transforms.pop();
+    assert_eq!(transforms.current, saved);
+    assert!(transforms.stack.is_empty());
+
+    //Quarter turns swap the axes, anything in between needs the stencil.
+    assert!(Transform::rotation(quarter).is_axis_aligned());
+    assert!(Transform::rotation(-quarter).is_axis_aligned());
+    assert!(!Transform::rotation(quarter / 2.0).is_axis_aligned());
+}
 
-        rd.clear();
-        rd.draw();
+#[test]
+#[should_panic(expected = "without a matching push_transform")]
+pub fn transform_stack_underflow() {
+    TransformStack::default().pop();
+}
+
+#[test]
+pub fn shader_log() {
+    let files = ["simple.vert", "common.glsl"];
+
+    //Mesa
+    let log = "0:12(5): error: `colour' undeclared\n1:3(1): warning: unused variable";
+    assert_eq!(
+        parse_log(log, &files),
+        [
+            Diagnostic {
+                file: "simple.vert".into(),
+                line: Some(12),
+                message: "error: `colour' undeclared".into(),
+            },
+            Diagnostic {
+                file: "common.glsl".into(),
+                line: Some(3),
+                message: "warning: unused variable".into(),
+            },
+        ]
+    );
+
+    //Nvidia
+    let log = "0(7) : error C1008: undefined variable \"colour\"";
+    let diagnostics = parse_log(log, &files);
+    assert_eq!(diagnostics[0].line, Some(7));
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "simple.vert:7: error C1008: undefined variable \"colour\""
+    );
+
+    //AMD
+    let log = "ERROR: 0:4: 'colour' : undeclared identifier\nERROR: 1 compilation errors.";
+    let diagnostics = parse_log(log, &files);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "simple.vert:4: ERROR: 'colour' : undeclared identifier"
+    );
+    assert_eq!(diagnostics[1].line, None);
+}
+
+#[test]
+pub fn shader_preprocess() {
+    let sources = SourceSet::default()
+        .embed(
+            "common.glsl",
+            "#include \"math.glsl\"\nfloat half(float x);",
+        )
+        .embed("math.glsl", "#version 330 core\nconst float PI = 3.14159;");
+    let source = Source::new(
+        "test.frag",
+        "#version 330 core\n#include \"common.glsl\"\n#include \"math.glsl\"\nvoid main() {}",
+    );
+    let defines = [("SHADOWS".to_string(), "1".to_string())];
+
+    let out = preprocess(&source, &sources, &defines).unwrap();
+    assert_eq!(out.files, ["test.frag", "common.glsl", "math.glsl"]);
+    assert_eq!(
+        out.code,
+        [
+            "#version 330 core",
+            "#define SHADOWS 1",
+            "#line 2 0",
+            "#line 1 1",
+            "#line 1 2",
+            "",
+            "const float PI = 3.14159;",
+            "#line 2 1",
+            "float half(float x);",
+            "#line 3 0",
+            "",
+            "void main() {}",
+            "",
+        ]
+        .join("\n")
+    );
 
-        window.swap_buffers();
-        glfw.poll_events();
+    //Errors inside of includes point at the included file.
+    let diagnostics = parse_log("1:2(1): error: `half' redeclared", &out.files);
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "common.glsl:2: error: `half' redeclared"
+    );
+
+    let missing = Source::new("test.frag", "\n#include \"missing.glsl\"");
+    assert_eq!(
+        preprocess(&missing, &sources, &[]).unwrap_err().to_string(),
+        "test.frag:2: can't find include \"missing.glsl\""
+    );
+}
+
+#[test]
+pub fn texture_registry() {
+    let texture = |id: u32| texture::Texture {
+        texture: NativeTexture(std::num::NonZeroU32::new(id).unwrap()),
+        width: 1,
+        height: 1,
+    };
+    let mut textures = TextureRegistry::new();
+    let a = textures.insert(texture(1));
+    let b = textures.insert(texture(2));
+    assert_eq!(textures.remove(a), Some(texture(1)));
+    assert_eq!(textures.remove(a), None);
+
+    //The slot is reused but the old handle doesn't reach the new texture.
+    let c = textures.insert(texture(3));
+    assert_eq!(c.index, a.index);
+    assert_ne!(c, a);
+    assert_eq!(textures.remove(a), None);
+    assert_eq!(textures.get(c).texture, texture(3).texture);
+    assert_eq!(textures.get(b).texture, texture(2).texture);
+
+    let stale = std::panic::catch_unwind(|| textures.get(a).texture);
+    assert!(stale.is_err());
+}
+
+#[test]
+pub fn premultiplied_alpha() {
+    let mut image = image::RgbaImage::from_pixel(2, 1, image::Rgba([255, 128, 0, 128]));
+    image.put_pixel(1, 0, image::Rgba([255, 255, 255, 0]));
+    premultiply_image(&mut image);
+    assert_eq!(image.get_pixel(0, 0).0, [128, 64, 0, 128]);
+    assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0, 0]);
+
+    let color = premultiply(Vec4::new(1.0, 0.5, 0.0, 0.5));
+    assert_eq!(color, Vec4::new(0.5, 0.25, 0.0, 0.5));
+}
+
+#[test]
+pub fn color() {
+    let close = |a: Color, b: Color| {
+        let (a, b) = (a.to_rgba8(), b.to_rgba8());
+        assert!(
+            a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1),
+            "{a:?} {b:?}"
+        );
+    };
+
+    assert_eq!("#f80".parse(), Ok(Color::hex(0xff8800)));
+    assert_eq!("#F808".parse(), Ok(Color::hex_rgba(0xff880088)));
+    assert_eq!("#cc3e44".parse(), Ok(Color::hex(0xcc3e44)));
+    assert_eq!("#cc3e4480".parse(), Ok(Color::hex_rgba(0xcc3e4480)));
+    assert_eq!("rgb(204, 62, 68)".parse(), Ok(Color::hex(0xcc3e44)));
+    assert_eq!(
+        "rgba(255, 0, 0, 0.5)".parse(),
+        Ok(Color::new(1.0, 0.0, 0.0, 0.5))
+    );
+    assert_eq!(
+        "rgb(100% 0% 0% / 50%)".parse(),
+        Ok(Color::new(1.0, 0.0, 0.0, 0.5))
+    );
+    assert_eq!(" Orange ".parse(), Ok(Color::hex(0xffa500)));
+    close("hsl(120, 100%, 25%)".parse().unwrap(), Color::hex(0x008000));
+    assert!("#12345".parse::<Color>().is_err());
+    assert!("#ggg".parse::<Color>().is_err());
+    assert!("rgb(1, 2)".parse::<Color>().is_err());
+    assert!("chartreuse".parse::<Color>().is_err());
+    assert_eq!(
+        "rgb(300, -20, 0)".parse(),
+        Ok(Color::new(1.0, 0.0, 0.0, 1.0))
+    );
+    assert_eq!(
+        "rgba(0, 0, 0, 150%)".parse(),
+        Ok(Color::new(0.0, 0.0, 0.0, 1.0))
+    );
+    close(
+        "hsl(120deg, 100%, 25%)".parse().unwrap(),
+        Color::hex(0x008000),
+    );
+    close("hsl(120, 200%, 25%)".parse().unwrap(), Color::hex(0x008000));
+    assert!("hsl(120, 100, 25)".parse::<Color>().is_err());
+    assert!("hsl(120%, 100%, 25%)".parse::<Color>().is_err());
+    assert!("rgb(255deg, 0, 0)".parse::<Color>().is_err());
+    assert!("rgba(0, 0, 0, 1deg)".parse::<Color>().is_err());
+    assert!("rgb(nan, 0, 0)".parse::<Color>().is_err());
+
+    //sRGB 0.5 is about 21% of the light.
+    assert!((srgb_to_linear(0.5) - 0.214).abs() < 0.001);
+    for i in 0..=255 {
+        let c = i as f32 / 255.0;
+        assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5);
     }
+
+    let color = Color::hex(0xcc3e44);
+    let (h, s, l) = color.to_hsl();
+    close(Color::hsl(h, s, l), color);
+    let (h, s, v) = color.to_hsv();
+    close(Color::hsv(h, s, v), color);
+    close(Color::hsv(240.0, 1.0, 1.0), Color::hex(0x0000ff));
+
+    let [l, a, b] = Color::WHITE.to_oklab();
+    assert!((l - 1.0).abs() < 1e-3 && a.abs() < 1e-3 && b.abs() < 1e-3);
+    let [l, a, b] = color.to_oklab();
+    close(Color::oklab(l, a, b), color);
+    close(Color::BLACK.mix(Color::WHITE, 0.0), Color::BLACK);
+    close(Color::BLACK.mix(Color::WHITE, 1.0), Color::WHITE);
+
+    assert_eq!(Vec4::from(Color::hex(0xff0000)), hex(0xff0000));
+}
+
+#[test]
+pub fn content_scale() {
+    let (width, height, _window, _events, _glfw, gl) = create_window();
+    let mut rd = Renderer::new(gl, width, height);
+    rd.set_scale(2.0);
+
+    let size = rd.logical_size();
+    assert_eq!((size.x * 2.0, size.y * 2.0), (width as f32, height as f32));
+    assert_eq!(rd.to_physical(Vec2::new(10.0, 5.0)), Vec2::new(20.0, 10.0));
+    assert_eq!(rd.to_logical(Vec2::new(20.0, 10.0)), Vec2::new(10.0, 5.0));
+
+    //Scissors are set in physical pixels.
+    rd.push_clip_rect(10.0, 10.0, 20.0, 20.0);
+    assert_eq!(
+        rd.commands.last(),
+        Some(&Command::PushClip(Clip::Rect(Rect::new(
+            20.0, 20.0, 40.0, 40.0
+        ))))
+    );
+    rd.pop_clip();
+}
+
+#[test]
+pub fn pan_zoom() {
+    let (width, height, _window, _events, _glfw, gl) = create_window();
+    let mut rd = Renderer::new(gl, width, height);
+    let mut pan_zoom = PanZoom::new();
+
+    //Zooming keeps the point under the cursor in place.
+    let cursor = Vec2::new(100.0, 50.0);
+    pan_zoom.event(&rd, &WindowEvent::CursorPos(100.0, (height - 50) as f64));
+    pan_zoom.event(&rd, &WindowEvent::Scroll(0.0, 2.0));
+    pan_zoom.update(0.016);
+    assert!((pan_zoom.zoom - 1.21).abs() < 1e-5);
+    let world = pan_zoom.to_world(cursor);
+    assert!((world.x - 100.0).abs() < 1e-3 && (world.y - 50.0).abs() < 1e-3);
+    let window = pan_zoom.to_window(world);
+    assert!((window.x - cursor.x).abs() < 1e-3 && (window.y - cursor.y).abs() < 1e-3);
+
+    //Dragging right moves the world with the cursor.
+    pan_zoom.event(
+        &rd,
+        &WindowEvent::MouseButton(
+            glfw::MouseButton::Button1,
+            Action::Press,
+            glfw::Modifiers::empty(),
+        ),
+    );
+    pan_zoom.event(&rd, &WindowEvent::CursorPos(221.0, (height - 50) as f64));
+    pan_zoom.update(0.016);
+    let world = pan_zoom.to_world(Vec2::new(221.0, 50.0));
+    assert!((world.x - 100.0).abs() < 1e-3);
+
+    pan_zoom.event(&rd, &WindowEvent::Scroll(0.0, 1000.0));
+    assert_eq!(pan_zoom.target_zoom, pan_zoom.max_zoom);
+
+    //Smoothing only moves part of the way.
+    pan_zoom.smoothing = 0.1;
+    pan_zoom.update(0.1);
+    assert!(pan_zoom.zoom > 1.21 && pan_zoom.zoom < pan_zoom.max_zoom);
+
+    pan_zoom.reset();
+    pan_zoom.apply(&mut rd);
+    let size = rd.logical_size();
+    assert_eq!(
+        rd.projection,
+        glm::ortho(0.0, size.x, 0.0, size.y, -1.0, 1.0)
+    );
 }
@@ -0,0 +1,243 @@
+use crate::*;
+use std::path::Path;
+
+/// Slots are reused after a delete, `generation` keeps old handles from
+/// reaching the texture that took their place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle {
+    pub index: usize,
+    pub generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub mipmaps: bool,
+    /// Store the texture as `SRGB8_ALPHA8` so sampling returns linear values.
+    pub srgb: bool,
+    /// Multiply color by alpha before uploading, draw with `BlendMode::Premultiplied`.
+    /// Avoids dark fringes when filtering between opaque and transparent texels.
+    pub premultiply: bool,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            filter: Filter::Linear,
+            wrap: Wrap::ClampToEdge,
+            mipmaps: false,
+            srgb: false,
+            premultiply: false,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+    pub fn premultiply(mut self, premultiply: bool) -> Self {
+        self.premultiply = premultiply;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Texture {
+    pub texture: NativeTexture,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TextureSlot {
+    pub texture: Option<Texture>,
+    /// Incremented every time the texture is removed.
+    pub generation: u32,
+}
+
+/// Owns every image texture and hands out handles to them.
+#[derive(Debug, Default)]
+pub struct TextureRegistry {
+    pub textures: Vec<TextureSlot>,
+}
+
+impl TextureRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load_file(
+        &mut self,
+        gl: &glow::Context,
+        path: impl AsRef<Path>,
+        options: TextureOptions,
+    ) -> image::ImageResult<TextureHandle> {
+        let image = image::open(path)?;
+        Ok(self.insert_image(gl, &image.to_rgba8(), options))
+    }
+
+    pub fn load(
+        &mut self,
+        gl: &glow::Context,
+        bytes: &[u8],
+        options: TextureOptions,
+    ) -> image::ImageResult<TextureHandle> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(self.insert_image(gl, &image.to_rgba8(), options))
+    }
+
+    pub fn insert_image(
+        &mut self,
+        gl: &glow::Context,
+        image: &image::RgbaImage,
+        options: TextureOptions,
+    ) -> TextureHandle {
+        let texture = upload(gl, image, options);
+        self.insert(Texture {
+            texture,
+            width: image.width() as i32,
+            height: image.height() as i32,
+        })
+    }
+
+    /// Registers a texture created somewhere else, like a `RenderTarget`.
+    pub fn insert(&mut self, texture: Texture) -> TextureHandle {
+        let index = match self.textures.iter().position(|t| t.texture.is_none()) {
+            Some(index) => index,
+            None => {
+                self.textures.push(TextureSlot::default());
+                self.textures.len() - 1
+            }
+        };
+        let slot = &mut self.textures[index];
+        slot.texture = Some(texture);
+        TextureHandle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    #[track_caller]
+    pub fn get(&self, handle: TextureHandle) -> &Texture {
+        self.textures
+            .get(handle.index)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.texture.as_ref())
+            .expect("texture handle was already deleted")
+    }
+
+    /// Unregisters the texture without deleting it.
+    /// `None` if the handle was already removed.
+    pub fn remove(&mut self, handle: TextureHandle) -> Option<Texture> {
+        let slot = self.textures.get_mut(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let texture = slot.texture.take()?;
+        slot.generation += 1;
+        Some(texture)
+    }
+
+    pub fn delete(&mut self, gl: &glow::Context, handle: TextureHandle) {
+        if let Some(texture) = self.remove(handle) {
+            unsafe { gl.delete_texture(texture.texture) };
+        }
+    }
+}
+
+pub fn upload(
+    gl: &glow::Context,
+    image: &image::RgbaImage,
+    options: TextureOptions,
+) -> NativeTexture {
+    let image = if options.premultiply {
+        let mut copy = image.clone();
+        premultiply_image(&mut copy);
+        std::borrow::Cow::Owned(copy)
+    } else {
+        std::borrow::Cow::Borrowed(image)
+    };
+
+    unsafe {
+        let texture = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+        let wrap = match options.wrap {
+            Wrap::Repeat => glow::REPEAT,
+            Wrap::MirroredRepeat => glow::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => glow::CLAMP_TO_EDGE,
+        };
+        let (min, mag) = match (options.filter, options.mipmaps) {
+            (Filter::Nearest, false) => (glow::NEAREST, glow::NEAREST),
+            (Filter::Nearest, true) => (glow::NEAREST_MIPMAP_NEAREST, glow::NEAREST),
+            (Filter::Linear, false) => (glow::LINEAR, glow::LINEAR),
+            (Filter::Linear, true) => (glow::LINEAR_MIPMAP_LINEAR, glow::LINEAR),
+        };
+
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, min as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, mag as i32);
+
+        let internal_format = if options.srgb {
+            glow::SRGB8_ALPHA8
+        } else {
+            glow::RGBA8
+        };
+
+        gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            image.width() as i32,
+            image.height() as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            Some(image.as_raw()),
+        );
+
+        if options.mipmaps {
+            gl.generate_mipmap(glow::TEXTURE_2D);
+        }
+
+        texture
+    }
+}
+
+pub fn premultiply_image(image: &mut image::RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let mul = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+        pixel.0 = [mul(r), mul(g), mul(b), a];
+    }
+}
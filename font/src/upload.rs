@@ -0,0 +1,263 @@
+use crate::*;
+use std::time::{Duration, Instant};
+
+/// Number of ring segments, the GPU can be reading two frames while we write the third.
+pub const SEGMENTS: usize = 3;
+
+/// Smallest ring segment in bytes.
+const MIN_SEGMENT: usize = 64 * 1024;
+
+/// How often `client_wait_sync` should give up and try again in nanoseconds.
+const WAIT_TIMEOUT: i32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadStrategy {
+    /// `glBufferData` when the size changes, `glBufferSubData` otherwise.
+    /// The driver may stall if the GPU is still reading the old data.
+    SubData,
+    /// Orphans the old store with `glBufferData(NULL)` before every `glBufferSubData`.
+    Orphan,
+    /// `glMapBufferRange` with `MAP_UNSYNCHRONIZED_BIT` into ring segments guarded by fences.
+    Ring,
+    /// Ring segments inside a persistently mapped buffer (`ARB_buffer_storage`).
+    /// Falls back to `Ring` when the extension isn't available.
+    Persistent,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UploadStats {
+    pub uploads: usize,
+    pub bytes: usize,
+    /// Times the buffer store was recreated.
+    pub reallocations: usize,
+    /// Times we had to wait on a fence before writing.
+    pub stalls: usize,
+    /// Time spent waiting on fences.
+    pub wait: Duration,
+    /// Total CPU time spent uploading, including `wait`.
+    pub time: Duration,
+}
+
+impl std::ops::AddAssign for UploadStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.uploads += rhs.uploads;
+        self.bytes += rhs.bytes;
+        self.reallocations += rhs.reallocations;
+        self.stalls += rhs.stalls;
+        self.wait += rhs.wait;
+        self.time += rhs.time;
+    }
+}
+
+pub fn persistent_mapping_supported(gl: &glow::Context) -> bool {
+    let version = gl.version();
+    (!version.is_embedded && (version.major, version.minor) >= (4, 4))
+        || gl.supported_extensions().contains("GL_ARB_buffer_storage")
+}
+
+/// Streams vertex data to the GPU once per frame.
+#[derive(Debug)]
+pub struct VertexStream {
+    pub strategy: UploadStrategy,
+    pub buffer: NativeBuffer,
+    /// Size of the buffer store in bytes.
+    pub capacity: usize,
+    /// Bytes per ring segment.
+    pub segment_size: usize,
+    pub segment: usize,
+    pub fences: [Option<NativeFence>; SEGMENTS],
+    /// Only used by `UploadStrategy::Persistent`.
+    pub mapped: *mut u8,
+    /// Statistics for the last upload.
+    pub stats: UploadStats,
+    /// Statistics since the stream was created.
+    pub total: UploadStats,
+}
+
+impl VertexStream {
+    /// Binds a new buffer to `ARRAY_BUFFER`.
+    pub fn new(gl: &glow::Context, strategy: UploadStrategy) -> Self {
+        let strategy = match strategy {
+            UploadStrategy::Persistent if !persistent_mapping_supported(gl) => UploadStrategy::Ring,
+            strategy => strategy,
+        };
+
+        unsafe {
+            let buffer = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+
+            Self {
+                strategy,
+                buffer,
+                capacity: 0,
+                segment_size: 0,
+                segment: 0,
+                fences: [None; SEGMENTS],
+                mapped: std::ptr::null_mut(),
+                stats: UploadStats::default(),
+                total: UploadStats::default(),
+            }
+        }
+    }
+
+    /// Uploads the data and returns the byte offset it was written to.
+    pub fn upload(&mut self, gl: &glow::Context, data: &[u8]) -> usize {
+        let now = Instant::now();
+        self.stats = UploadStats {
+            uploads: 1,
+            bytes: data.len(),
+            ..Default::default()
+        };
+
+        let offset = unsafe {
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            match self.strategy {
+                UploadStrategy::SubData => {
+                    //When replacing the entire data store, consider using glBufferSubData rather than completely recreating the data store with glBufferData. This avoids the cost of reallocating the data store.
+                    if self.capacity != data.len() {
+                        gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, data, glow::DYNAMIC_DRAW);
+                        self.capacity = data.len();
+                        self.stats.reallocations += 1;
+                    } else {
+                        gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, data);
+                    }
+                    0
+                }
+                UploadStrategy::Orphan => {
+                    if self.capacity < data.len() {
+                        self.capacity = data.len().next_power_of_two();
+                        self.stats.reallocations += 1;
+                    }
+                    gl.buffer_data_size(
+                        glow::ARRAY_BUFFER,
+                        self.capacity as i32,
+                        glow::STREAM_DRAW,
+                    );
+                    gl.buffer_sub_data_u8_slice(glow::ARRAY_BUFFER, 0, data);
+                    0
+                }
+                UploadStrategy::Ring | UploadStrategy::Persistent => self.write_segment(gl, data),
+            }
+        };
+
+        self.stats.time = now.elapsed();
+        self.total += self.stats;
+        offset
+    }
+
+    unsafe fn write_segment(&mut self, gl: &glow::Context, data: &[u8]) -> usize {
+        if self.segment_size < data.len() {
+            self.grow(gl, data.len());
+        }
+
+        self.segment = (self.segment + 1) % SEGMENTS;
+        self.wait(gl, self.segment);
+
+        let offset = self.segment * self.segment_size;
+        if data.is_empty() {
+            return offset;
+        }
+
+        if self.strategy == UploadStrategy::Persistent {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.mapped.add(offset), data.len());
+        } else {
+            let ptr = gl.map_buffer_range(
+                glow::ARRAY_BUFFER,
+                offset as i32,
+                data.len() as i32,
+                glow::MAP_WRITE_BIT | glow::MAP_UNSYNCHRONIZED_BIT | glow::MAP_INVALIDATE_RANGE_BIT,
+            );
+            assert!(!ptr.is_null(), "glMapBufferRange failed");
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+            gl.unmap_buffer(glow::ARRAY_BUFFER);
+        }
+
+        offset
+    }
+
+    unsafe fn grow(&mut self, gl: &glow::Context, size: usize) {
+        //The old fences guard a store that's about to be thrown away.
+        for i in 0..SEGMENTS {
+            self.wait(gl, i);
+        }
+
+        //Keep segments a multiple of the vertex size so offsets can be used as the first vertex.
+        self.segment_size = size.next_power_of_two().max(MIN_SEGMENT);
+        self.capacity = self.segment_size * SEGMENTS;
+        self.stats.reallocations += 1;
+
+        if self.strategy == UploadStrategy::Persistent {
+            //Buffer storage is immutable, so the buffer needs to be recreated.
+            if !self.mapped.is_null() {
+                gl.unmap_buffer(glow::ARRAY_BUFFER);
+            }
+            gl.delete_buffer(self.buffer);
+            self.buffer = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            Vertex::bind_layout(gl);
+
+            let flags = glow::MAP_WRITE_BIT | glow::MAP_PERSISTENT_BIT | glow::MAP_COHERENT_BIT;
+            gl.buffer_storage(glow::ARRAY_BUFFER, self.capacity as i32, None, flags);
+            self.mapped = gl.map_buffer_range(glow::ARRAY_BUFFER, 0, self.capacity as i32, flags);
+            assert!(!self.mapped.is_null(), "glMapBufferRange failed");
+        } else {
+            gl.buffer_data_size(glow::ARRAY_BUFFER, self.capacity as i32, glow::STREAM_DRAW);
+        }
+    }
+
+    /// Blocks until the GPU is done reading the segment.
+    unsafe fn wait(&mut self, gl: &glow::Context, segment: usize) {
+        let Some(fence) = self.fences[segment].take() else {
+            return;
+        };
+
+        let now = Instant::now();
+        let signaled = |status| {
+            matches!(
+                status,
+                glow::ALREADY_SIGNALED | glow::CONDITION_SATISFIED | glow::WAIT_FAILED
+            )
+        };
+
+        let mut status = gl.client_wait_sync(fence, 0, 0);
+        if !signaled(status) {
+            self.stats.stalls += 1;
+            while !signaled(status) {
+                status = gl.client_wait_sync(fence, glow::SYNC_FLUSH_COMMANDS_BIT, WAIT_TIMEOUT);
+            }
+        }
+        self.stats.wait += now.elapsed();
+        gl.delete_sync(fence);
+    }
+
+    /// Call after the draw calls that read the last upload have been submitted.
+    pub fn fence(&mut self, gl: &glow::Context) {
+        if !matches!(
+            self.strategy,
+            UploadStrategy::Ring | UploadStrategy::Persistent
+        ) {
+            return;
+        }
+
+        unsafe {
+            if let Some(old) = self.fences[self.segment].take() {
+                gl.delete_sync(old);
+            }
+            self.fences[self.segment] = gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).ok();
+        }
+    }
+
+    pub fn delete(&mut self, gl: &glow::Context) {
+        unsafe {
+            for fence in self.fences.iter_mut().filter_map(|f| f.take()) {
+                gl.delete_sync(fence);
+            }
+            if !self.mapped.is_null() {
+                gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+                gl.unmap_buffer(glow::ARRAY_BUFFER);
+                self.mapped = std::ptr::null_mut();
+            }
+            gl.delete_buffer(self.buffer);
+        }
+    }
+}
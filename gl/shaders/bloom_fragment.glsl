@@ -0,0 +1,37 @@
+#version 330 core
+// Dual filter blur, the application defines DOWNSAMPLE or UPSAMPLE after the version.
+// https://community.arm.com/cfs-file/__key/communityserver-blogs-components-weblogfiles/00-00-00-20-66/siggraph2015_2D00_mmg_2D00_marius_2D00_notes.pdf
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D source;
+// Size of a texel of `source`.
+uniform vec2 texel;
+// Spreads the samples further apart for a wider glow.
+uniform float radius;
+
+void main()
+{
+	vec2 uv = TexCoord;
+	vec2 h = texel * 0.5 * radius;
+#ifdef DOWNSAMPLE
+	vec3 sum = texture(source, uv).rgb * 4.0;
+	sum += texture(source, uv - h).rgb;
+	sum += texture(source, uv + h).rgb;
+	sum += texture(source, uv + vec2(h.x, -h.y)).rgb;
+	sum += texture(source, uv - vec2(h.x, -h.y)).rgb;
+	FragColor = vec4(sum / 8.0, 1.0);
+#endif
+#ifdef UPSAMPLE
+	vec3 sum = texture(source, uv + vec2(-h.x * 2.0, 0.0)).rgb;
+	sum += texture(source, uv + vec2(-h.x, h.y)).rgb * 2.0;
+	sum += texture(source, uv + vec2(0.0, h.y * 2.0)).rgb;
+	sum += texture(source, uv + vec2(h.x, h.y)).rgb * 2.0;
+	sum += texture(source, uv + vec2(h.x * 2.0, 0.0)).rgb;
+	sum += texture(source, uv + vec2(h.x, -h.y)).rgb * 2.0;
+	sum += texture(source, uv + vec2(0.0, -h.y * 2.0)).rgb;
+	sum += texture(source, uv + vec2(-h.x, -h.y)).rgb * 2.0;
+	FragColor = vec4(sum / 12.0, 1.0);
+#endif
+}
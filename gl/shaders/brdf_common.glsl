@@ -0,0 +1,51 @@
+const float PI = 3.14159265359;
+
+// http://holger.dammertz.org/stuff/notes_HammersleyOnHemisphere.html
+float radical_inverse(uint bits)
+{
+	bits = (bits << 16u) | (bits >> 16u);
+	bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+	bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+	bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+	bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+	return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint n)
+{
+	return vec2(float(i) / float(n), radical_inverse(i));
+}
+
+// Half vector around N distributed like GGX.
+vec3 importance_sample_ggx(vec2 xi, vec3 N, float roughness)
+{
+	float a = roughness * roughness;
+	float phi = 2.0 * PI * xi.x;
+	float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+	float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+	vec3 H = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+	vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+	vec3 tangent = normalize(cross(up, N));
+	vec3 bitangent = cross(N, tangent);
+	return normalize(tangent * H.x + bitangent * H.y + N * H.z);
+}
+
+float distribution_ggx(float NdotH, float roughness)
+{
+	float a = roughness * roughness;
+	float a2 = a * a;
+	float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+// k is remapped differently for direct and image based lighting.
+float geometry_schlick_ggx(float NdotV, float k)
+{
+	return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+float geometry_smith(float NdotV, float NdotL, float k)
+{
+	return geometry_schlick_ggx(NdotV, k) * geometry_schlick_ggx(NdotL, k);
+}
@@ -0,0 +1,41 @@
+#version 330 core
+// brdf_common.glsl is inserted after the version.
+out vec2 FragColor;
+
+in vec2 TexCoord;
+
+const uint SAMPLE_COUNT = 1024u;
+
+// Scale and bias to F0 of the split sum approximation, indexed by NdotV and roughness.
+// https://cdn2.unrealengine.com/Resources/files/2013SiggraphPresentationsNotes-26915738.pdf
+vec2 integrate_brdf(float NdotV, float roughness)
+{
+	vec3 V = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+	vec3 N = vec3(0.0, 0.0, 1.0);
+	float k = roughness * roughness / 2.0;
+
+	float a = 0.0;
+	float b = 0.0;
+	for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+		vec2 xi = hammersley(i, SAMPLE_COUNT);
+		vec3 H = importance_sample_ggx(xi, N, roughness);
+		vec3 L = normalize(2.0 * dot(V, H) * H - V);
+
+		float NdotL = max(L.z, 0.0);
+		float NdotH = max(H.z, 0.0);
+		float VdotH = max(dot(V, H), 0.0);
+		if (NdotL > 0.0) {
+			float G = geometry_smith(NdotV, NdotL, k);
+			float G_vis = (G * VdotH) / (NdotH * NdotV);
+			float Fc = pow(1.0 - VdotH, 5.0);
+			a += (1.0 - Fc) * G_vis;
+			b += Fc * G_vis;
+		}
+	}
+	return vec2(a, b) / float(SAMPLE_COUNT);
+}
+
+void main()
+{
+	FragColor = integrate_brdf(TexCoord.x, TexCoord.y);
+}
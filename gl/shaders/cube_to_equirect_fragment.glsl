@@ -0,0 +1,17 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform samplerCube environment;
+
+const float PI = 3.14159265359;
+
+// Inverse of sample_spherical in equirect_fragment.glsl.
+void main()
+{
+	float phi = (TexCoord.x - 0.5) * 2.0 * PI;
+	float theta = (TexCoord.y - 0.5) * PI;
+	vec3 direction = vec3(cos(theta) * cos(phi), sin(theta), cos(theta) * sin(phi));
+	FragColor = vec4(textureLod(environment, direction, 0.0).rgb, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 LocalPos;
+
+uniform sampler2D equirectangular;
+
+const vec2 invAtan = vec2(0.1591, 0.3183);
+
+vec2 sample_spherical(vec3 v)
+{
+	vec2 uv = vec2(atan(v.z, v.x), asin(v.y));
+	return uv * invAtan + 0.5;
+}
+
+void main()
+{
+	vec2 uv = sample_spherical(normalize(LocalPos));
+	FragColor = vec4(texture(equirectangular, uv).rgb, 1.0);
+}
@@ -1,13 +1,183 @@
 #version 330 core
+// MAX_LIGHTS and shadows_common.glsl are inserted by the application, see `with_light_limit`
+// and `with_shadows`.
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+#define NO_ENVIRONMENT 0
+#define REFLECT 1
+#define REFRACT 2
+
+#define NO_PARALLAX 0
+#define STEEP_PARALLAX 1
+#define PARALLAX_OCCLUSION 2
+
 out vec4 FragColor;
 
+in vec3 FragPos;
+in vec3 Normal;
 in vec2 TexCoord;
+in vec4 Tangent;
+
+// Matches `Light::std140`, the type is in position.w.
+struct Light {
+	vec4 position;
+	vec4 direction;
+	vec4 color;
+	vec4 attenuation;
+	vec4 cone;
+};
+
+layout (std140) uniform Lights {
+	vec3 ambient;
+	int light_count;
+	Light lights[MAX_LIGHTS];
+};
+
+struct Material {
+	sampler2D diffuse;
+	sampler2D specular;
+	sampler2D normal;
+	sampler2D emission;
+	// White is deepest.
+	sampler2D displacement;
+	vec3 diffuse_color;
+	vec3 specular_color;
+	vec3 emission_color;
+	float shininess;
+	// See `EnvironmentMapping`.
+	int environment;
+	float environment_amount;
+	float refraction_ratio;
+	// See `Parallax`.
+	int parallax;
+	float height_scale;
+	float min_layers;
+	float max_layers;
+};
 
-uniform sampler2D texture1;
-uniform sampler2D texture2;
+uniform Material material;
+uniform vec3 camera_position;
+uniform samplerCube environment_map;
+
+// Tangent frame from screen space derivatives, for meshes without tangents.
+// http://www.thetenthplanet.de/archives/1180
+mat3 cotangent_frame(vec3 N, vec3 p, vec2 uv)
+{
+	vec3 dp1 = dFdx(p);
+	vec3 dp2 = dFdy(p);
+	vec2 duv1 = dFdx(uv);
+	vec2 duv2 = dFdy(uv);
+
+	vec3 dp2perp = cross(dp2, N);
+	vec3 dp1perp = cross(N, dp1);
+	vec3 T = dp2perp * duv1.x + dp1perp * duv2.x;
+	vec3 B = dp2perp * duv1.y + dp1perp * duv2.y;
+
+	float invmax = inversesqrt(max(dot(T, T), dot(B, B)));
+	return mat3(T * invmax, B * invmax, N);
+}
+
+// Tangent frame from the vertex tangents, see `generate_tangents`.
+// Falls back to screen space derivatives for meshes without them.
+mat3 tangent_frame(vec3 N, vec3 p, vec2 uv)
+{
+	// Outside of the branch, derivatives need uniform control flow.
+	mat3 fallback = cotangent_frame(N, p, uv);
+	vec3 T = Tangent.xyz - N * dot(N, Tangent.xyz);
+	if (dot(T, T) < 1e-8) {
+		return fallback;
+	}
+	T = normalize(T);
+	return mat3(T, Tangent.w * cross(N, T), N);
+}
+
+// Walks the view ray into the displacement map one layer at a time until it's below the
+// surface. V is in tangent space and points towards the camera.
+// https://learnopengl.com/Advanced-Lighting/Parallax-Mapping
+vec2 parallax_uv(vec2 uv, vec3 V)
+{
+	// Looking straight at the surface needs fewer layers.
+	float layers = mix(material.max_layers, material.min_layers, abs(V.z));
+	float layer_depth = 1.0 / layers;
+	vec2 step = V.xy / max(V.z, 0.05) * material.height_scale / layers;
+	vec2 dx = dFdx(uv);
+	vec2 dy = dFdy(uv);
+
+	vec2 current = uv;
+	float depth = textureGrad(material.displacement, current, dx, dy).r;
+	float current_depth = 0.0;
+	for (int i = 0; i < int(layers) && current_depth < depth; i++) {
+		current -= step;
+		depth = textureGrad(material.displacement, current, dx, dy).r;
+		current_depth += layer_depth;
+	}
+	if (material.parallax == STEEP_PARALLAX) {
+		return current;
+	}
+
+	// Occlusion mapping interpolates between the layers on either side of the surface.
+	vec2 previous = current + step;
+	float after = depth - current_depth;
+	float before = textureGrad(material.displacement, previous, dx, dy).r - current_depth + layer_depth;
+	return mix(current, previous, after / (after - before));
+}
 
 void main()
 {
-	// linearly interpolate between both textures (80% container, 20% awesomeface)
-	FragColor = mix(texture(texture1, TexCoord), texture(texture2, TexCoord), 0.2);
-}
\ No newline at end of file
+	vec3 geometric_normal = normalize(Normal);
+	mat3 TBN = tangent_frame(geometric_normal, FragPos, TexCoord);
+	vec3 V = normalize(camera_position - FragPos);
+	vec2 uv = TexCoord;
+	if (material.parallax != NO_PARALLAX) {
+		uv = parallax_uv(TexCoord, normalize(transpose(TBN) * V));
+	}
+
+	// Diffuse and emission maps are sRGB, lighting is done in linear HDR.
+	vec3 albedo = pow(texture(material.diffuse, uv).rgb, vec3(2.2)) * material.diffuse_color;
+	vec3 specular_color = texture(material.specular, uv).rgb * material.specular_color;
+	vec3 emission = pow(texture(material.emission, uv).rgb, vec3(2.2)) * material.emission_color;
+
+	vec3 tangent_normal = texture(material.normal, uv).xyz * 2.0 - 1.0;
+	vec3 N = normalize(TBN * tangent_normal);
+
+	vec3 color = ambient * albedo;
+	for (int i = 0; i < light_count; i++) {
+		Light light = lights[i];
+		int kind = int(light.position.w);
+
+		vec3 L;
+		float attenuation = 1.0;
+		if (kind == DIRECTIONAL) {
+			L = -light.direction.xyz;
+		} else {
+			vec3 to_light = light.position.xyz - FragPos;
+			float d = length(to_light);
+			L = to_light / d;
+			vec3 k = light.attenuation.xyz;
+			attenuation = 1.0 / (k.x + k.y * d + k.z * d * d);
+		}
+		if (kind == SPOT) {
+			// cone.x and cone.y are the cosines of the inner and outer angles
+			float theta = dot(L, -light.direction.xyz);
+			attenuation *= clamp((theta - light.cone.y) / (light.cone.x - light.cone.y), 0.0, 1.0);
+		}
+		attenuation *= shadow(i, FragPos, dot(geometric_normal, L), light.position.xyz);
+
+		vec3 H = normalize(L + V);
+		float diffuse = max(dot(N, L), 0.0);
+		float specular = diffuse > 0.0 ? pow(max(dot(N, H), 0.0), material.shininess) : 0.0;
+		color += light.color.rgb * attenuation * (diffuse * albedo + specular * specular_color);
+	}
+
+	if (material.environment != NO_ENVIRONMENT) {
+		vec3 direction = material.environment == REFLECT
+			? reflect(-V, N)
+			: refract(-V, N, material.refraction_ratio);
+		vec3 environment = texture(environment_map, direction).rgb;
+		color = mix(color, environment, material.environment_amount);
+	}
+
+	FragColor = vec4((color + emission) * cascade_tint(FragPos), 1.0);
+}
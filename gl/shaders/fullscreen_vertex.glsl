@@ -0,0 +1,10 @@
+#version 330 core
+// One triangle that covers the screen, draw 3 vertices with any vertex array bound.
+out vec2 TexCoord;
+
+void main()
+{
+	vec2 uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+	TexCoord = uv;
+	gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,49 @@
+#version 330 core
+// Fast approximate anti-aliasing on tone mapped, gamma corrected colors.
+// Blurs along the direction of edges found from the luma of the neighbours.
+// https://developer.download.nvidia.com/assets/gamedev/files/sdk/11/FXAA_WhitePaper.pdf
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2D source;
+uniform vec2 texel;
+// Longest blur in pixels.
+uniform float span_max;
+// Keep the direction from collapsing to zero on flat areas.
+uniform float reduce_mul;
+uniform float reduce_min;
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main()
+{
+	vec2 uv = TexCoord;
+	vec3 rgb_m = texture(source, uv).rgb;
+	float luma_nw = dot(texture(source, uv + vec2(-1.0, 1.0) * texel).rgb, LUMA);
+	float luma_ne = dot(texture(source, uv + vec2(1.0, 1.0) * texel).rgb, LUMA);
+	float luma_sw = dot(texture(source, uv + vec2(-1.0, -1.0) * texel).rgb, LUMA);
+	float luma_se = dot(texture(source, uv + vec2(1.0, -1.0) * texel).rgb, LUMA);
+	float luma_m = dot(rgb_m, LUMA);
+	float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+	float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+	// Perpendicular to the gradient, along the edge.
+	vec2 direction = vec2(
+		-((luma_nw + luma_ne) - (luma_sw + luma_se)),
+		(luma_nw + luma_sw) - (luma_ne + luma_se)
+	);
+	float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * reduce_mul, reduce_min);
+	float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+	direction = clamp(direction * scale, vec2(-span_max), vec2(span_max)) * texel;
+
+	vec3 rgb_a = 0.5 * (
+		texture(source, uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+		texture(source, uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+	vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+		texture(source, uv - direction * 0.5).rgb +
+		texture(source, uv + direction * 0.5).rgb);
+	// The wider blur crossed into something else, use the narrow one.
+	float luma_b = dot(rgb_b, LUMA);
+	FragColor = vec4(luma_b < luma_min || luma_b > luma_max ? rgb_a : rgb_b, 1.0);
+}
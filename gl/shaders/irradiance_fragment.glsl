@@ -0,0 +1,30 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 LocalPos;
+
+uniform samplerCube environment;
+
+const float PI = 3.14159265359;
+
+// Cosine weighted average of the hemisphere around the normal.
+void main()
+{
+	vec3 N = normalize(LocalPos);
+	vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+	vec3 right = normalize(cross(up, N));
+	up = normalize(cross(N, right));
+
+	vec3 irradiance = vec3(0.0);
+	float sample_delta = 0.025;
+	float samples = 0.0;
+	for (float phi = 0.0; phi < 2.0 * PI; phi += sample_delta) {
+		for (float theta = 0.0; theta < 0.5 * PI; theta += sample_delta) {
+			vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+			vec3 direction = tangent.x * right + tangent.y * up + tangent.z * N;
+			irradiance += texture(environment, direction).rgb * cos(theta) * sin(theta);
+			samples++;
+		}
+	}
+	FragColor = vec4(PI * irradiance / samples, 1.0);
+}
@@ -0,0 +1,156 @@
+#version 330 core
+// MAX_LIGHTS, brdf_common.glsl and shadows_common.glsl are inserted after the version.
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+out vec4 FragColor;
+
+in vec3 FragPos;
+in vec3 Normal;
+in vec2 TexCoord;
+in vec4 Tangent;
+
+// Same block as fragment.glsl.
+struct Light {
+	vec4 position;
+	vec4 direction;
+	vec4 color;
+	vec4 attenuation;
+	vec4 cone;
+};
+
+layout (std140) uniform Lights {
+	vec3 ambient;
+	int light_count;
+	Light lights[MAX_LIGHTS];
+};
+
+// Maps are multiplied with the factors, metallic and roughness are read from red.
+struct Material {
+	sampler2D albedo;
+	sampler2D normal;
+	sampler2D metallic;
+	sampler2D roughness;
+	sampler2D ao;
+	vec3 albedo_color;
+	float metallic_factor;
+	float roughness_factor;
+	float ao_factor;
+};
+
+uniform Material material;
+uniform vec3 camera_position;
+
+uniform samplerCube irradiance_map;
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+uniform float prefiltered_mips;
+
+mat3 cotangent_frame(vec3 N, vec3 p, vec2 uv)
+{
+	vec3 dp1 = dFdx(p);
+	vec3 dp2 = dFdy(p);
+	vec2 duv1 = dFdx(uv);
+	vec2 duv2 = dFdy(uv);
+
+	vec3 dp2perp = cross(dp2, N);
+	vec3 dp1perp = cross(N, dp1);
+	vec3 T = dp2perp * duv1.x + dp1perp * duv2.x;
+	vec3 B = dp2perp * duv1.y + dp1perp * duv2.y;
+
+	float invmax = inversesqrt(max(dot(T, T), dot(B, B)));
+	return mat3(T * invmax, B * invmax, N);
+}
+
+// Tangent frame from the vertex tangents, see `generate_tangents`.
+// Falls back to screen space derivatives for meshes without them.
+mat3 tangent_frame(vec3 N, vec3 p, vec2 uv)
+{
+	// Outside of the branch, derivatives need uniform control flow.
+	mat3 fallback = cotangent_frame(N, p, uv);
+	vec3 T = Tangent.xyz - N * dot(N, Tangent.xyz);
+	if (dot(T, T) < 1e-8) {
+		return fallback;
+	}
+	T = normalize(T);
+	return mat3(T, Tangent.w * cross(N, T), N);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 F0)
+{
+	return F0 + (1.0 - F0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 F0, float roughness)
+{
+	return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+void main()
+{
+	// Albedo textures are sRGB.
+	vec3 albedo = pow(texture(material.albedo, TexCoord).rgb, vec3(2.2)) * material.albedo_color;
+	float metallic = texture(material.metallic, TexCoord).r * material.metallic_factor;
+	float roughness = texture(material.roughness, TexCoord).r * material.roughness_factor;
+	float ao = texture(material.ao, TexCoord).r * material.ao_factor;
+
+	vec3 geometric_normal = normalize(Normal);
+	vec3 tangent_normal = texture(material.normal, TexCoord).xyz * 2.0 - 1.0;
+	vec3 N = normalize(tangent_frame(geometric_normal, FragPos, TexCoord) * tangent_normal);
+	vec3 V = normalize(camera_position - FragPos);
+	vec3 R = reflect(-V, N);
+	float NdotV = max(dot(N, V), 0.0);
+
+	// Dielectrics reflect about 4% head on.
+	vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+	vec3 Lo = vec3(0.0);
+	for (int i = 0; i < light_count; i++) {
+		Light light = lights[i];
+		int kind = int(light.position.w);
+
+		vec3 L;
+		float attenuation = 1.0;
+		if (kind == DIRECTIONAL) {
+			L = -light.direction.xyz;
+		} else {
+			vec3 to_light = light.position.xyz - FragPos;
+			float d = length(to_light);
+			L = to_light / d;
+			vec3 k = light.attenuation.xyz;
+			attenuation = 1.0 / (k.x + k.y * d + k.z * d * d);
+		}
+		if (kind == SPOT) {
+			float theta = dot(L, -light.direction.xyz);
+			attenuation *= clamp((theta - light.cone.y) / (light.cone.x - light.cone.y), 0.0, 1.0);
+		}
+		attenuation *= shadow(i, FragPos, dot(geometric_normal, L), light.position.xyz);
+
+		vec3 H = normalize(V + L);
+		float NdotL = max(dot(N, L), 0.0);
+		float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+
+		float D = distribution_ggx(max(dot(N, H), 0.0), roughness);
+		float G = geometry_smith(NdotV, NdotL, k);
+		vec3 F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+		vec3 specular = D * G * F / (4.0 * NdotV * NdotL + 0.0001);
+
+		// Metals have no diffuse.
+		vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+		Lo += (kD * albedo / PI + specular) * light.color.rgb * attenuation * NdotL;
+	}
+
+	// Image based lighting with the split sum approximation.
+	vec3 F = fresnel_schlick_roughness(NdotV, F0, roughness);
+	vec3 kD = (1.0 - F) * (1.0 - metallic);
+	vec3 diffuse = texture(irradiance_map, N).rgb * albedo;
+
+	vec3 prefiltered = textureLod(prefiltered_map, R, roughness * (prefiltered_mips - 1.0)).rgb;
+	vec2 brdf = texture(brdf_lut, vec2(NdotV, roughness)).rg;
+	vec3 specular = prefiltered * (F * brdf.x + brdf.y);
+
+	vec3 color = (kD * diffuse + specular) * ao + ambient * albedo * ao + Lo;
+	// Linear HDR, `PostChain` tone maps and gamma corrects.
+	FragColor = vec4(color * cascade_tint(FragPos), 1.0);
+}
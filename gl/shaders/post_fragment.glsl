@@ -0,0 +1,105 @@
+#version 330 core
+// One pass of `PostChain`, the application defines which one after the version.
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+// The output of the previous pass.
+uniform sampler2D source;
+
+#ifdef BRIGHT_PASS
+uniform float threshold;
+// Colors within `knee` below the threshold fade in instead of cutting off.
+uniform float knee;
+#endif
+
+#ifdef COMPOSITE
+uniform sampler2D bloom;
+uniform float intensity;
+#endif
+
+#ifdef EXPOSURE
+uniform float exposure;
+#endif
+
+#ifdef TONE_MAP
+// Matches `ToneMapper`.
+#define REINHARD 0
+#define ACES 1
+#define FILMIC 2
+uniform int tone_mapper;
+
+// Krzysztof Narkowicz's fit of the ACES curve.
+// https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+vec3 aces(vec3 x)
+{
+	x *= 0.6;
+	return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// John Hable's Uncharted 2 curve.
+// http://filmicworlds.com/blog/filmic-tonemapping-operators/
+vec3 hable(vec3 x)
+{
+	const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+	return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 filmic(vec3 x)
+{
+	const float white = 11.2;
+	return hable(x * 2.0) / hable(vec3(white));
+}
+#endif
+
+#ifdef GAMMA
+uniform float gamma;
+#endif
+
+#ifdef VIGNETTE
+uniform float intensity;
+// Distance from the center where the darkening starts and how far it takes to reach full strength.
+uniform float radius;
+uniform float softness;
+#endif
+
+void main()
+{
+	vec3 color = texture(source, TexCoord).rgb;
+
+#ifdef BRIGHT_PASS
+	float brightness = max(color.r, max(color.g, color.b));
+	float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+	soft = soft * soft / (4.0 * knee + 0.0001);
+	color *= max(soft, brightness - threshold) / max(brightness, 0.0001);
+#endif
+
+#ifdef COMPOSITE
+	color += texture(bloom, TexCoord).rgb * intensity;
+#endif
+
+#ifdef EXPOSURE
+	color *= exposure;
+#endif
+
+#ifdef TONE_MAP
+	if (tone_mapper == REINHARD) {
+		color = color / (color + vec3(1.0));
+	} else if (tone_mapper == ACES) {
+		color = aces(color);
+	} else {
+		color = filmic(color);
+	}
+#endif
+
+#ifdef GAMMA
+	color = pow(max(color, vec3(0.0)), vec3(1.0 / gamma));
+#endif
+
+#ifdef VIGNETTE
+	float d = distance(TexCoord, vec2(0.5));
+	color *= 1.0 - smoothstep(radius, radius + softness, d) * intensity;
+#endif
+
+	FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,42 @@
+#version 330 core
+// brdf_common.glsl is inserted after the version.
+out vec4 FragColor;
+
+in vec3 LocalPos;
+
+uniform samplerCube environment;
+uniform float roughness;
+// Size of a face of the environment at mip 0.
+uniform float resolution;
+
+const uint SAMPLE_COUNT = 1024u;
+
+// Assumes the view direction is the normal, so there's no stretching at grazing angles.
+void main()
+{
+	vec3 N = normalize(LocalPos);
+	vec3 V = N;
+
+	vec3 color = vec3(0.0);
+	float weight = 0.0;
+	for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+		vec2 xi = hammersley(i, SAMPLE_COUNT);
+		vec3 H = importance_sample_ggx(xi, N, roughness);
+		vec3 L = normalize(2.0 * dot(V, H) * H - V);
+
+		float NdotL = max(dot(N, L), 0.0);
+		if (NdotL > 0.0) {
+			// Sample a blurrier mip for unlikely directions to avoid bright dots.
+			// https://chetanjags.wordpress.com/2015/08/26/image-based-lighting/
+			float NdotH = max(dot(N, H), 0.0);
+			float pdf = distribution_ggx(NdotH, roughness) * NdotH / (4.0 * NdotH) + 0.0001;
+			float texel = 4.0 * PI / (6.0 * resolution * resolution);
+			float sample_angle = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+			float mip = roughness == 0.0 ? 0.0 : 0.5 * log2(sample_angle / texel);
+
+			color += textureLod(environment, L, mip).rgb * NdotL;
+			weight += NdotL;
+		}
+	}
+	FragColor = vec4(color / weight, 1.0);
+}
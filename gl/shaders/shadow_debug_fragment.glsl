@@ -0,0 +1,26 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoord;
+
+uniform sampler2DArray cascades;
+uniform samplerCube point_map;
+// 0 shows `layer` of the cascades, 1 the point light cube unwrapped like an equirectangular map.
+uniform int mode;
+uniform int layer;
+
+const float PI = 3.14159265359;
+
+void main()
+{
+	float depth;
+	if (mode == 0) {
+		depth = texture(cascades, vec3(TexCoord, layer)).r;
+	} else {
+		float phi = (TexCoord.x - 0.5) * 2.0 * PI;
+		float theta = (TexCoord.y - 0.5) * PI;
+		vec3 direction = vec3(cos(theta) * cos(phi), sin(theta), cos(theta) * sin(phi));
+		depth = texture(point_map, direction).r;
+	}
+	FragColor = vec4(vec3(depth), 1.0);
+}
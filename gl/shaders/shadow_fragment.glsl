@@ -0,0 +1,13 @@
+#version 330 core
+in vec3 FragPos;
+
+// Point lights store the distance to the light over far_plane, so every face of the cube
+// compares the same thing. Directional lights keep the orthographic depth.
+uniform bool linear_depth;
+uniform vec3 light_position;
+uniform float far_plane;
+
+void main()
+{
+	gl_FragDepth = linear_depth ? length(FragPos - light_position) / far_plane : gl_FragCoord.z;
+}
@@ -0,0 +1,15 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+out vec3 FragPos;
+
+uniform mat4 model;
+// Projection and view of the light, or of one cube face for point lights.
+uniform mat4 light_space;
+
+void main()
+{
+	vec4 world = model * vec4(aPos, 1.0);
+	FragPos = world.xyz;
+	gl_Position = light_space * world;
+}
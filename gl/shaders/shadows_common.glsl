@@ -0,0 +1,123 @@
+// Shadow maps rendered by `Shadows`, MAX_CASCADES is defined by the application.
+uniform sampler2DArray shadow_cascades;
+uniform mat4 cascade_matrices[MAX_CASCADES];
+// View space distance where each cascade ends and the depth range of its projection.
+uniform float cascade_far[MAX_CASCADES];
+uniform float cascade_depth[MAX_CASCADES];
+uniform int cascade_count;
+
+// Distance to the light over point_shadow_far.
+uniform samplerCube point_shadow_map;
+uniform float point_shadow_far;
+
+// Index of the light casting each kind of shadow plus one, 0 is none.
+uniform int directional_shadow;
+uniform int point_shadow;
+
+// In world units.
+uniform float shadow_constant_bias;
+uniform float shadow_slope_bias;
+uniform float shadow_max_bias;
+uniform int shadow_pcf_radius;
+uniform bool show_cascades;
+
+uniform mat4 view;
+
+// Grows with the angle between the surface and the light, so slopes don't shadow themselves.
+float shadow_bias(float NdotL)
+{
+	float cos_theta = clamp(NdotL, 0.001, 1.0);
+	float tan_theta = sqrt(1.0 - cos_theta * cos_theta) / cos_theta;
+	return shadow_constant_bias + min(shadow_slope_bias * tan_theta, shadow_max_bias);
+}
+
+int shadow_cascade(vec3 world)
+{
+	float depth = -(view * vec4(world, 1.0)).z;
+	for (int i = 0; i < cascade_count - 1; i++) {
+		if (depth < cascade_far[i]) {
+			return i;
+		}
+	}
+	return cascade_count - 1;
+}
+
+float cascade_shadow(vec3 world, float NdotL)
+{
+	int cascade = shadow_cascade(world);
+	vec4 light_space = cascade_matrices[cascade] * vec4(world, 1.0);
+	vec3 p = light_space.xyz / light_space.w * 0.5 + 0.5;
+	// Past the far plane of the light.
+	if (p.z > 1.0) {
+		return 1.0;
+	}
+	float current = p.z - shadow_bias(NdotL) / cascade_depth[cascade];
+
+	vec2 texel = 1.0 / vec2(textureSize(shadow_cascades, 0).xy);
+	float lit = 0.0;
+	for (int x = -shadow_pcf_radius; x <= shadow_pcf_radius; x++) {
+		for (int y = -shadow_pcf_radius; y <= shadow_pcf_radius; y++) {
+			vec2 uv = p.xy + vec2(x, y) * texel;
+			float closest = texture(shadow_cascades, vec3(uv, cascade)).r;
+			lit += current > closest ? 0.0 : 1.0;
+		}
+	}
+	float width = float(shadow_pcf_radius * 2 + 1);
+	return lit / (width * width);
+}
+
+// Spread out so neighbouring samples don't land on the same texel.
+const vec3 point_offsets[20] = vec3[](
+	vec3(1, 1, 1), vec3(1, -1, 1), vec3(-1, -1, 1), vec3(-1, 1, 1),
+	vec3(1, 1, -1), vec3(1, -1, -1), vec3(-1, -1, -1), vec3(-1, 1, -1),
+	vec3(1, 1, 0), vec3(1, -1, 0), vec3(-1, -1, 0), vec3(-1, 1, 0),
+	vec3(1, 0, 1), vec3(-1, 0, 1), vec3(1, 0, -1), vec3(-1, 0, -1),
+	vec3(0, 1, 1), vec3(0, -1, 1), vec3(0, -1, -1), vec3(0, 1, -1)
+);
+
+float point_light_shadow(vec3 world, float NdotL, vec3 light_position)
+{
+	vec3 to_fragment = world - light_position;
+	float current = length(to_fragment);
+	if (current > point_shadow_far) {
+		return 1.0;
+	}
+	current -= shadow_bias(NdotL);
+	if (shadow_pcf_radius == 0) {
+		return current > texture(point_shadow_map, to_fragment).r * point_shadow_far ? 0.0 : 1.0;
+	}
+
+	// Softer further from the camera, where a texel covers more of the screen anyway.
+	float camera_distance = -(view * vec4(world, 1.0)).z;
+	float radius = float(shadow_pcf_radius) * (1.0 + camera_distance / point_shadow_far) / 25.0;
+	float lit = 0.0;
+	for (int i = 0; i < 20; i++) {
+		float closest = texture(point_shadow_map, to_fragment + point_offsets[i] * radius).r;
+		lit += current > closest * point_shadow_far ? 0.0 : 1.0;
+	}
+	return lit / 20.0;
+}
+
+// 1 is fully lit. NdotL should use the geometric normal, the maps only saw the geometry.
+float shadow(int light, vec3 world, float NdotL, vec3 light_position)
+{
+	if (light + 1 == directional_shadow && cascade_count > 0) {
+		return cascade_shadow(world, NdotL);
+	}
+	if (light + 1 == point_shadow) {
+		return point_light_shadow(world, NdotL, light_position);
+	}
+	return 1.0;
+}
+
+// Colors each cascade differently when `show_cascades` is set.
+vec3 cascade_tint(vec3 world)
+{
+	if (!show_cascades || directional_shadow == 0 || cascade_count == 0) {
+		return vec3(1.0);
+	}
+	const vec3 tints[4] = vec3[](
+		vec3(1.0, 0.5, 0.5), vec3(0.5, 1.0, 0.5), vec3(0.5, 0.5, 1.0), vec3(1.0, 1.0, 0.5)
+	);
+	return tints[shadow_cascade(world) % 4];
+}
@@ -0,0 +1,12 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 TexCoord;
+
+uniform samplerCube skybox;
+uniform float lod;
+
+void main()
+{
+	FragColor = vec4(textureLod(skybox, TexCoord, lod).rgb, 1.0);
+}
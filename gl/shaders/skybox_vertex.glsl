@@ -0,0 +1,16 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+out vec3 TexCoord;
+
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+	TexCoord = aPos;
+	// Only the rotation of the view, so the sky never gets any closer.
+	vec4 position = projection * mat4(mat3(view)) * vec4(aPos, 1.0);
+	// z = w puts it on the far plane, depth 1.0 after the divide.
+	gl_Position = position.xyww;
+}
@@ -1,8 +1,13 @@
 #version 330 core
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec3 aNormal;
+layout (location = 3) in vec4 aTangent;
 
+out vec3 FragPos;
+out vec3 Normal;
 out vec2 TexCoord;
+out vec4 Tangent;
 
 uniform mat4 model;
 uniform mat4 view;
@@ -10,6 +15,12 @@ uniform mat4 projection;
 
 void main()
 {
-	gl_Position = projection * view * model * vec4(aPos, 1.0);
-	TexCoord = vec2(aTexCoord.x, aTexCoord.y);
-}
\ No newline at end of file
+	vec4 world = model * vec4(aPos, 1.0);
+	gl_Position = projection * view * world;
+	FragPos = world.xyz;
+	// non-uniform scales would skew the normal with the model matrix
+	Normal = mat3(transpose(inverse(model))) * aNormal;
+	TexCoord = aTexCoord;
+	// tangents lie in the surface, so they follow the model matrix itself
+	Tangent = vec4(mat3(model) * aTangent.xyz, aTangent.w);
+}
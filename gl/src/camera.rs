@@ -0,0 +1,342 @@
+//! Cameras and the controllers that move them. Angles are in radians.
+use crate::*;
+use glfw::{Action, Key, MouseButton, WindowEvent};
+
+/// Unit vector, a yaw of -90 degrees looks down -Z.
+pub fn direction(yaw: f32, pitch: f32) -> glm::Vec3 {
+    glm::vec3(
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    )
+}
+
+/// Inverse of `direction`.
+pub fn yaw_pitch(direction: &glm::Vec3) -> (f32, f32) {
+    let direction = direction.normalize();
+    (
+        direction.z.atan2(direction.x),
+        direction.y.clamp(-1.0, 1.0).asin(),
+    )
+}
+
+/// How far to move towards a target this frame, independent of the frame rate.
+/// `smoothing` is roughly the seconds it takes to get 63% of the way there, 0 snaps.
+pub fn smoothing_factor(smoothing: f32, delta: f32) -> f32 {
+    if smoothing <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-delta / smoothing).exp()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub position: glm::Vec3,
+    /// Unit vector the camera looks along.
+    pub front: glm::Vec3,
+    pub up: glm::Vec3,
+    pub projection: Projection,
+}
+
+impl Camera {
+    /// Uses the viewport's aspect ratio.
+    pub fn perspective(fov_y: f32, znear: f32, zfar: f32) -> Self {
+        Self::new(Projection::Perspective {
+            yfov: fov_y,
+            aspect: None,
+            znear,
+            zfar: Some(zfar),
+        })
+    }
+
+    /// `xmag` and `ymag` are half of the visible width and height.
+    pub fn orthographic(xmag: f32, ymag: f32, znear: f32, zfar: f32) -> Self {
+        Self::new(Projection::Orthographic {
+            xmag,
+            ymag,
+            znear,
+            zfar,
+        })
+    }
+
+    /// At the origin looking down -Z.
+    pub fn new(projection: Projection) -> Self {
+        Self {
+            position: glm::Vec3::zeros(),
+            front: glm::vec3(0.0, 0.0, -1.0),
+            up: glm::vec3(0.0, 1.0, 0.0),
+            projection,
+        }
+    }
+
+    pub fn look_at(&mut self, target: &glm::Vec3) {
+        self.front = (target - self.position).normalize();
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        self.front.cross(&self.up).normalize()
+    }
+
+    pub fn view(&self) -> glm::Mat4 {
+        glm::look_at(&self.position, &(self.position + self.front), &self.up)
+    }
+
+    pub fn projection(&self, aspect: f32) -> glm::Mat4 {
+        self.projection.matrix(aspect)
+    }
+
+    pub fn view_projection(&self, aspect: f32) -> glm::Mat4 {
+        self.projection(aspect) * self.view()
+    }
+}
+
+/// Feed it every window event and call `update` once per frame.
+pub trait CameraController {
+    fn event(&mut self, event: &WindowEvent);
+    /// `delta` is the frame time in seconds.
+    fn update(&mut self, camera: &mut Camera, delta: f32);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyBindings {
+    pub forward: Key,
+    pub back: Key,
+    pub left: Key,
+    pub right: Key,
+    pub up: Key,
+    pub down: Key,
+    /// Held to move `FlyController::fast_multiplier` times faster.
+    pub fast: Key,
+}
+
+impl Default for FlyBindings {
+    fn default() -> Self {
+        Self {
+            forward: Key::W,
+            back: Key::S,
+            left: Key::A,
+            right: Key::D,
+            up: Key::Space,
+            down: Key::LeftControl,
+            fast: Key::LeftShift,
+        }
+    }
+}
+
+/// First person mouse look, moves along where the camera is facing.
+/// Expects a disabled cursor, see `glfw::Window::set_cursor_mode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyController {
+    pub bindings: FlyBindings,
+    /// Units per second.
+    pub speed: f32,
+    pub fast_multiplier: f32,
+    /// Radians per pixel of mouse movement.
+    pub sensitivity: f32,
+    /// Pitch is clamped to `-pitch_limit..=pitch_limit`.
+    pub pitch_limit: f32,
+    /// Seconds, see `smoothing_factor`. Applies to looking and movement.
+    pub smoothing: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// The smoothed yaw, pitch and velocity.
+    current: (f32, f32),
+    velocity: glm::Vec3,
+    pressed: Vec<Key>,
+    cursor: Option<(f32, f32)>,
+}
+
+impl FlyController {
+    /// Starts facing the same way as the camera.
+    pub fn new(camera: &Camera) -> Self {
+        let (yaw, pitch) = yaw_pitch(&camera.front);
+        Self {
+            bindings: FlyBindings::default(),
+            speed: 5.0,
+            fast_multiplier: 3.0,
+            sensitivity: 0.1f32.to_radians(),
+            pitch_limit: 89.9f32.to_radians(),
+            smoothing: 0.0,
+            yaw,
+            pitch,
+            current: (yaw, pitch),
+            velocity: glm::Vec3::zeros(),
+            pressed: Vec::new(),
+            cursor: None,
+        }
+    }
+
+    pub fn pressed(&self, key: Key) -> bool {
+        self.pressed.contains(&key)
+    }
+}
+
+impl CameraController for FlyController {
+    fn event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(key, _, Action::Press, _) if !self.pressed(key) => {
+                self.pressed.push(key)
+            }
+            WindowEvent::Key(key, _, Action::Release, _) => self.pressed.retain(|&k| k != key),
+            //Keys released while unfocused never send a release.
+            WindowEvent::Focus(false) => self.pressed.clear(),
+            WindowEvent::CursorPos(x, y) => {
+                let (x, y) = (x as f32, y as f32);
+                //Skip the jump from wherever the cursor was before the first event.
+                if let Some((last_x, last_y)) = self.cursor {
+                    self.yaw += (x - last_x) * self.sensitivity;
+                    //Reversed since y-coordinates go from bottom to top.
+                    self.pitch += (last_y - y) * self.sensitivity;
+                    self.pitch = self.pitch.clamp(-self.pitch_limit, self.pitch_limit);
+                }
+                self.cursor = Some((x, y));
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, delta: f32) {
+        let t = smoothing_factor(self.smoothing, delta);
+        self.current.0 += (self.yaw - self.current.0) * t;
+        self.current.1 += (self.pitch - self.current.1) * t;
+        camera.front = direction(self.current.0, self.current.1);
+
+        let b = self.bindings;
+        let axis = |positive: Key, negative: Key| {
+            self.pressed(positive) as i32 as f32 - self.pressed(negative) as i32 as f32
+        };
+        let wish = camera.front * axis(b.forward, b.back)
+            + camera.right() * axis(b.right, b.left)
+            + camera.up * axis(b.up, b.down);
+
+        let mut target = glm::Vec3::zeros();
+        if wish != glm::Vec3::zeros() {
+            let fast = if self.pressed(b.fast) {
+                self.fast_multiplier
+            } else {
+                1.0
+            };
+            target = wish.normalize() * self.speed * fast;
+        }
+        self.velocity += (target - self.velocity) * t;
+        camera.position += self.velocity * delta;
+    }
+}
+
+/// Rotates around and zooms towards a target point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrbitController {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    /// Angle of the camera above the target.
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub pitch_limit: f32,
+    /// Radians per pixel while dragging with `rotate_button`.
+    pub sensitivity: f32,
+    /// The distance is divided by this for every step scrolled.
+    pub zoom_speed: f32,
+    /// Fraction of the distance moved per pixel while dragging with `pan_button`.
+    pub pan_speed: f32,
+    /// Seconds, see `smoothing_factor`.
+    pub smoothing: f32,
+    pub rotate_button: MouseButton,
+    pub pan_button: MouseButton,
+    /// The smoothed target, distance, yaw and pitch.
+    current: (glm::Vec3, f32, f32, f32),
+    rotating: bool,
+    panning: bool,
+    cursor: Option<(f32, f32)>,
+}
+
+impl OrbitController {
+    /// Looks at the target from `distance` units down +Z.
+    pub fn new(target: glm::Vec3, distance: f32) -> Self {
+        Self::with_angles(target, distance, -90f32.to_radians(), 0.0)
+    }
+
+    /// Orbits the point `distance` units in front of the camera without moving it.
+    pub fn from_camera(camera: &Camera, distance: f32) -> Self {
+        let (yaw, pitch) = yaw_pitch(&camera.front);
+        let target = camera.position + camera.front.normalize() * distance;
+        Self::with_angles(target, distance, yaw, -pitch)
+    }
+
+    fn with_angles(target: glm::Vec3, distance: f32, yaw: f32, pitch: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw,
+            pitch,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            pitch_limit: 89.0f32.to_radians(),
+            sensitivity: 0.3f32.to_radians(),
+            zoom_speed: 1.1,
+            pan_speed: 0.002,
+            smoothing: 0.0,
+            rotate_button: MouseButton::Button1,
+            pan_button: MouseButton::Button3,
+            current: (target, distance, yaw, pitch),
+            rotating: false,
+            panning: false,
+            cursor: None,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::MouseButton(button, action, _) => {
+                let down = action != Action::Release;
+                if button == self.rotate_button {
+                    self.rotating = down;
+                }
+                if button == self.pan_button {
+                    self.panning = down;
+                }
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let (x, y) = (x as f32, y as f32);
+                if let Some((last_x, last_y)) = self.cursor {
+                    let (dx, dy) = (x - last_x, y - last_y);
+                    if self.rotating {
+                        self.yaw += dx * self.sensitivity;
+                        //Dragging down raises the camera above the target.
+                        self.pitch += dy * self.sensitivity;
+                        self.pitch = self.pitch.clamp(-self.pitch_limit, self.pitch_limit);
+                    }
+                    if self.panning {
+                        let front = direction(self.yaw, -self.pitch);
+                        let right = front.cross(&glm::vec3(0.0, 1.0, 0.0)).normalize();
+                        let up = right.cross(&front);
+                        let scale = self.distance * self.pan_speed;
+                        self.target += (up * dy - right * dx) * scale;
+                    }
+                }
+                self.cursor = Some((x, y));
+            }
+            WindowEvent::Scroll(_, y) => {
+                self.distance /= self.zoom_speed.powf(y as f32);
+                self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&mut self, camera: &mut Camera, delta: f32) {
+        let t = smoothing_factor(self.smoothing, delta);
+        let (target, distance, yaw, pitch) = &mut self.current;
+        *target += (self.target - *target) * t;
+        *distance += (self.distance - *distance) * t;
+        *yaw += (self.yaw - *yaw) * t;
+        *pitch += (self.pitch - *pitch) * t;
+
+        camera.front = direction(*yaw, -*pitch);
+        camera.position = *target - camera.front * *distance;
+    }
+}
@@ -0,0 +1,573 @@
+//! Cubemap textures, converting them to and from equirectangular images, and drawing them as a sky.
+use crate::*;
+use std::path::PathBuf;
+
+/// Positions at location 0 with `view` and `projection` uniforms, outputs `LocalPos`.
+pub const CUBE_VERTEX: &str = include_str!("../shaders/cube_vertex.glsl");
+/// One triangle covering the viewport that outputs `TexCoord`, no vertex attributes.
+pub const FULLSCREEN_VERTEX: &str = include_str!("../shaders/fullscreen_vertex.glsl");
+const EQUIRECT_FRAGMENT: &str = include_str!("../shaders/equirect_fragment.glsl");
+const CUBE_TO_EQUIRECT_FRAGMENT: &str = include_str!("../shaders/cube_to_equirect_fragment.glsl");
+const SKYBOX_VERTEX: &str = include_str!("../shaders/skybox_vertex.glsl");
+const SKYBOX_FRAGMENT: &str = include_str!("../shaders/skybox_fragment.glsl");
+
+/// File names of the faces in the order of `TEXTURE_CUBE_MAP_POSITIVE_X + i`.
+pub const FACE_NAMES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+
+/// `dir/right.jpg`, `dir/left.jpg` and so on for `from_faces`.
+pub fn face_paths(dir: &Path, extension: &str) -> [PathBuf; 6] {
+    FACE_NAMES.map(|name| dir.join(format!("{}.{}", name, extension)))
+}
+
+/// Levels in a full mip chain down to 1x1.
+pub fn mip_levels(size: u32) -> u32 {
+    32 - size.max(1).leading_zeros()
+}
+
+/// Faces are in the order of `TEXTURE_CUBE_MAP_POSITIVE_X + i`: +X, -X, +Y, -Y, +Z, -Z.
+#[derive(Debug)]
+pub struct Cubemap {
+    pub texture: NativeTexture,
+    /// Width and height of a face at level 0.
+    pub size: u32,
+    /// Mip levels that can be sampled.
+    pub levels: u32,
+}
+
+impl Cubemap {
+    /// Empty RGBA16F cubemap, clamped and linearly filtered. Left bound.
+    pub fn new(gl: &Context, size: u32, levels: u32) -> Self {
+        Self::with_format(gl, size, levels, glow::RGBA16F)
+    }
+
+    fn with_format(gl: &Context, size: u32, levels: u32, internal_format: u32) -> Self {
+        unsafe {
+            let texture = gl.create_texture().unwrap();
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(texture));
+            for wrap in [
+                glow::TEXTURE_WRAP_S,
+                glow::TEXTURE_WRAP_T,
+                glow::TEXTURE_WRAP_R,
+            ] {
+                gl.tex_parameter_i32(glow::TEXTURE_CUBE_MAP, wrap, glow::CLAMP_TO_EDGE as i32);
+            }
+            let min = if levels > 1 {
+                glow::LINEAR_MIPMAP_LINEAR
+            } else {
+                glow::LINEAR
+            };
+            gl.tex_parameter_i32(glow::TEXTURE_CUBE_MAP, glow::TEXTURE_MIN_FILTER, min as i32);
+            gl.tex_parameter_i32(
+                glow::TEXTURE_CUBE_MAP,
+                glow::TEXTURE_MAG_FILTER,
+                glow::LINEAR as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_CUBE_MAP,
+                glow::TEXTURE_MAX_LEVEL,
+                levels.saturating_sub(1) as i32,
+            );
+
+            let cubemap = Self {
+                texture,
+                size,
+                levels,
+            };
+            for level in 0..levels {
+                let size = cubemap.level_size(level) as i32;
+                for face in 0..6 {
+                    gl.tex_image_2d(
+                        glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                        level as i32,
+                        internal_format as i32,
+                        size,
+                        size,
+                        0,
+                        glow::RGB,
+                        glow::FLOAT,
+                        None,
+                    );
+                }
+            }
+            cubemap
+        }
+    }
+
+    /// Six square images of the same size, see `face_paths`. Stored as sRGB so they're sampled as linear colors.
+    pub fn from_faces(gl: &Context, paths: &[PathBuf; 6]) -> Result<Self, String> {
+        let mut images = Vec::new();
+        for path in paths {
+            let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            images.push(image.into_rgb8());
+        }
+        let size = images[0].width();
+        for (path, image) in paths.iter().zip(&images) {
+            if image.dimensions() != (size, size) {
+                return Err(format!(
+                    "{}: expected {}x{}, found {}x{}",
+                    path.display(),
+                    size,
+                    size,
+                    image.width(),
+                    image.height()
+                ));
+            }
+        }
+
+        let cubemap = Self::with_format(gl, size, mip_levels(size), glow::SRGB8);
+        unsafe {
+            //Rows of RGB bytes aren't always a multiple of 4.
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            for (face, image) in images.iter().enumerate() {
+                gl.tex_sub_image_2d(
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    0,
+                    0,
+                    0,
+                    size as i32,
+                    size as i32,
+                    glow::RGB,
+                    glow::UNSIGNED_BYTE,
+                    PixelUnpackData::Slice(image),
+                );
+            }
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+        }
+        cubemap.generate_mipmaps(gl);
+        Ok(cubemap)
+    }
+
+    /// Projects an equirectangular image onto `size` pixel faces. `.hdr` files keep their range.
+    ///
+    /// Changes the bound program.
+    pub fn from_equirectangular(gl: &Context, path: &Path, size: u32) -> Result<Self, String> {
+        let hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+        let (width, height, rgb) = if hdr {
+            decode_hdr(path)?
+        } else {
+            let image = image::open(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+                .into_rgb32f();
+            (image.width(), image.height(), image.into_raw())
+        };
+        Self::from_equirectangular_data(gl, width, height, &rgb, size)
+    }
+
+    /// Same as `from_equirectangular` with RGB floats from the top left.
+    pub fn from_equirectangular_data(
+        gl: &Context,
+        width: u32,
+        height: u32,
+        rgb: &[f32],
+        size: u32,
+    ) -> Result<Self, String> {
+        //Flipped so the top of the image is at v = 1.
+        let rgb: Vec<f32> = rgb
+            .chunks(width as usize * 3)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        unsafe {
+            let program = try_program(gl, CUBE_VERTEX, EQUIRECT_FRAGMENT)?;
+            let equirectangular = create_texture_2d(gl, glow::RGB16F, width, height, Some(&rgb));
+            let cubemap = Self::new(gl, size, mip_levels(size));
+
+            let capture = Capture::new(gl);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(equirectangular));
+            capture.render_faces(gl, program, &cubemap, 0);
+            capture.delete(gl);
+
+            cubemap.generate_mipmaps(gl);
+            gl.delete_texture(equirectangular);
+            gl.delete_program(program);
+            Ok(cubemap)
+        }
+    }
+
+    /// `width` by `height` RGB floats from the top left, ready for `write_hdr`.
+    ///
+    /// Changes the bound program.
+    pub fn to_equirectangular(
+        &self,
+        gl: &Context,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<f32>, String> {
+        unsafe {
+            let program = try_program(gl, FULLSCREEN_VERTEX, CUBE_TO_EQUIRECT_FRAGMENT)?;
+            let texture = create_texture_2d(gl, glow::RGBA32F, width, height, None);
+
+            let capture = Capture::new(gl);
+            self.bind(gl, 0);
+            capture.render_fullscreen(gl, program, texture, width, height);
+            capture.delete(gl);
+
+            let mut rgb = vec![0.0f32; (width * height * 3) as usize];
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.get_tex_image(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGB,
+                glow::FLOAT,
+                PixelPackData::Slice(rgb.align_to_mut::<u8>().1),
+            );
+            gl.delete_texture(texture);
+            gl.delete_program(program);
+
+            Ok(rgb
+                .chunks(width as usize * 3)
+                .rev()
+                .flatten()
+                .copied()
+                .collect())
+        }
+    }
+
+    pub fn level_size(&self, level: u32) -> u32 {
+        (self.size >> level).max(1)
+    }
+
+    /// `rgb` is the six faces of `level` as tightly packed floats, in face order.
+    pub fn upload(&self, gl: &Context, level: u32, rgb: &[f32]) {
+        let size = self.level_size(level);
+        let face_len = (size * size * 3) as usize;
+        assert_eq!(rgb.len(), face_len * 6);
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(self.texture));
+            for (face, data) in rgb.chunks(face_len).enumerate() {
+                gl.tex_sub_image_2d(
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    level as i32,
+                    0,
+                    0,
+                    size as i32,
+                    size as i32,
+                    glow::RGB,
+                    glow::FLOAT,
+                    PixelUnpackData::Slice(data.align_to::<u8>().1),
+                );
+            }
+        }
+    }
+
+    /// Inverse of `upload`.
+    pub fn download(&self, gl: &Context, level: u32) -> Vec<f32> {
+        let size = self.level_size(level);
+        let face_len = (size * size * 3) as usize;
+        let mut rgb = vec![0.0f32; face_len * 6];
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(self.texture));
+            for (face, data) in rgb.chunks_mut(face_len).enumerate() {
+                gl.get_tex_image(
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    level as i32,
+                    glow::RGB,
+                    glow::FLOAT,
+                    PixelPackData::Slice(data.align_to_mut::<u8>().1),
+                );
+            }
+        }
+        rgb
+    }
+
+    /// Fills the other levels from level 0.
+    pub fn generate_mipmaps(&self, gl: &Context) {
+        unsafe {
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(self.texture));
+            gl.generate_mipmap(glow::TEXTURE_CUBE_MAP);
+        }
+    }
+
+    /// Leaves `unit` active.
+    pub fn bind(&self, gl: &Context, unit: u32) {
+        unsafe {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            gl.bind_texture(glow::TEXTURE_CUBE_MAP, Some(self.texture));
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe { gl.delete_texture(self.texture) };
+    }
+}
+
+/// Clamped and linearly filtered, `rgb` is tightly packed RGB floats. Left bound.
+pub fn create_texture_2d(
+    gl: &Context,
+    internal_format: u32,
+    width: u32,
+    height: u32,
+    rgb: Option<&[f32]>,
+) -> NativeTexture {
+    unsafe {
+        let texture = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        for (parameter, value) in [
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_MIN_FILTER, glow::LINEAR),
+            (glow::TEXTURE_MAG_FILTER, glow::LINEAR),
+        ] {
+            gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, value as i32);
+        }
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            internal_format as i32,
+            width as i32,
+            height as i32,
+            0,
+            glow::RGB,
+            glow::FLOAT,
+            rgb.map(|rgb| rgb.align_to::<u8>().1),
+        );
+        texture
+    }
+}
+
+/// Positions of a cube from -1 to 1 at location 0, 36 vertices facing outwards.
+#[derive(Debug)]
+pub struct UnitCube {
+    pub vao: NativeVertexArray,
+    pub vbo: NativeBuffer,
+}
+
+impl UnitCube {
+    pub fn new(gl: &Context) -> Self {
+        let mut positions: Vec<f32> = Vec::with_capacity(36 * 3);
+        for axis in 0..3 {
+            for sign in [1.0, -1.0] {
+                //Counter-clockwise seen from outside, flipped on the negative side.
+                let mut corners = [
+                    (-1.0, -1.0),
+                    (1.0, -1.0),
+                    (1.0, 1.0),
+                    (-1.0, -1.0),
+                    (1.0, 1.0),
+                    (-1.0, 1.0),
+                ];
+                if sign < 0.0 {
+                    corners.reverse();
+                }
+                for (u, v) in corners {
+                    let mut p = [0.0; 3];
+                    p[axis] = sign;
+                    p[(axis + 1) % 3] = u;
+                    p[(axis + 2) % 3] = v;
+                    positions.extend(p);
+                }
+            }
+        }
+
+        unsafe {
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+            let vbo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, buffer(&positions), glow::STATIC_DRAW);
+            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 12, 0);
+            gl.enable_vertex_attrib_array(0);
+            gl.bind_vertex_array(None);
+            Self { vao, vbo }
+        }
+    }
+
+    pub fn draw(&self, gl: &Context) {
+        unsafe {
+            gl.bind_vertex_array(Some(self.vao));
+            gl.draw_arrays(glow::TRIANGLES, 0, 36);
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_vertex_array(self.vao);
+            gl.delete_buffer(self.vbo);
+        }
+    }
+}
+
+/// Framebuffer for rendering into textures. Saves the state it changes and `delete` restores it.
+#[derive(Debug)]
+pub struct Capture {
+    pub framebuffer: NativeFramebuffer,
+    pub cube: UnitCube,
+    viewport: [i32; 4],
+    depth_test: bool,
+}
+
+impl Capture {
+    pub fn new(gl: &Context) -> Self {
+        unsafe {
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+            let depth_test = gl.is_enabled(glow::DEPTH_TEST);
+            gl.disable(glow::DEPTH_TEST);
+            gl.enable(glow::TEXTURE_CUBE_MAP_SEAMLESS);
+
+            let framebuffer = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            Self {
+                framebuffer,
+                cube: UnitCube::new(gl),
+                viewport,
+                depth_test,
+            }
+        }
+    }
+
+    /// Draws the inside of the cube into each face of `cubemap` at `level` with a
+    /// `CUBE_VERTEX` program. The textures it samples need to be bound already.
+    pub fn render_faces(
+        &self,
+        gl: &Context,
+        program: NativeProgram,
+        cubemap: &Cubemap,
+        level: u32,
+    ) {
+        unsafe {
+            gl.use_program(Some(program));
+            let projection = glm::perspective(1.0, PI / 2.0, 0.1, 10.0);
+            let projection_location = gl.get_uniform_location(program, "projection");
+            gl.uniform_matrix_4_f32_slice(
+                projection_location.as_ref(),
+                false,
+                projection.as_slice(),
+            );
+            let view_location = gl.get_uniform_location(program, "view");
+
+            let size = cubemap.level_size(level) as i32;
+            gl.viewport(0, 0, size, size);
+            for (face, view) in capture_views().iter().enumerate() {
+                gl.uniform_matrix_4_f32_slice(view_location.as_ref(), false, view.as_slice());
+                gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    glow::COLOR_ATTACHMENT0,
+                    glow::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    Some(cubemap.texture),
+                    level as i32,
+                );
+                gl.clear(glow::COLOR_BUFFER_BIT);
+                self.cube.draw(gl);
+            }
+        }
+    }
+
+    /// Draws a `FULLSCREEN_VERTEX` program into level 0 of a 2D texture.
+    pub fn render_fullscreen(
+        &self,
+        gl: &Context,
+        program: NativeProgram,
+        texture: NativeTexture,
+        width: u32,
+        height: u32,
+    ) {
+        unsafe {
+            gl.use_program(Some(program));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            //Core profiles need some vertex array bound, even without attributes.
+            gl.bind_vertex_array(Some(self.cube.vao));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.delete_framebuffer(self.framebuffer);
+            let [x, y, width, height] = self.viewport;
+            gl.viewport(x, y, width, height);
+            if self.depth_test {
+                gl.enable(glow::DEPTH_TEST);
+            }
+        }
+        self.cube.delete(gl);
+    }
+}
+
+/// Views from the origin through each face, in the order of `TEXTURE_CUBE_MAP_POSITIVE_X + i`.
+/// Cubemap faces are upside down compared to the rest of OpenGL, hence the flipped ups.
+pub fn capture_views() -> [glm::Mat4; 6] {
+    let eye = glm::Vec3::zeros();
+    let view = |x: f32, y: f32, z: f32, up: glm::Vec3| glm::look_at(&eye, &glm::vec3(x, y, z), &up);
+    let down = glm::vec3(0.0, -1.0, 0.0);
+    [
+        view(1.0, 0.0, 0.0, down),
+        view(-1.0, 0.0, 0.0, down),
+        view(0.0, 1.0, 0.0, glm::vec3(0.0, 0.0, 1.0)),
+        view(0.0, -1.0, 0.0, glm::vec3(0.0, 0.0, -1.0)),
+        view(0.0, 0.0, 1.0, down),
+        view(0.0, 0.0, -1.0, down),
+    ]
+}
+
+/// Draws a cubemap around the camera at the far plane.
+///
+/// Draw it after the opaque geometry so the depth test skips every covered pixel.
+#[derive(Debug)]
+pub struct Skybox {
+    pub program: NativeProgram,
+    pub cube: UnitCube,
+    /// Mip level to sample, higher is blurrier.
+    pub lod: f32,
+}
+
+impl Skybox {
+    /// Changes the bound program.
+    pub fn new(gl: &Context) -> Result<Self, String> {
+        let program = unsafe { try_program(gl, SKYBOX_VERTEX, SKYBOX_FRAGMENT)? };
+        Ok(Self {
+            program,
+            cube: UnitCube::new(gl),
+            lod: 0.0,
+        })
+    }
+
+    /// Uses texture unit 0 and changes the bound program and vertex array.
+    pub fn draw(&self, gl: &Context, cubemap: &Cubemap, view: &glm::Mat4, projection: &glm::Mat4) {
+        unsafe {
+            gl.use_program(Some(self.program));
+            let uniform = |name: &str| gl.get_uniform_location(self.program, name);
+            gl.uniform_matrix_4_f32_slice(uniform("view").as_ref(), false, view.as_slice());
+            gl.uniform_matrix_4_f32_slice(
+                uniform("projection").as_ref(),
+                false,
+                projection.as_slice(),
+            );
+            gl.uniform_1_f32(uniform("lod").as_ref(), self.lod);
+            cubemap.bind(gl, 0);
+
+            //The depth buffer is cleared to 1.0, which LESS would reject.
+            let depth_func = gl.get_parameter_i32(glow::DEPTH_FUNC) as u32;
+            let cull_face = gl.is_enabled(glow::CULL_FACE);
+            gl.depth_func(glow::LEQUAL);
+            //Seen from the inside.
+            gl.disable(glow::CULL_FACE);
+
+            self.cube.draw(gl);
+
+            gl.depth_func(depth_func);
+            if cull_face {
+                gl.enable(glow::CULL_FACE);
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe { gl.delete_program(self.program) };
+        self.cube.delete(gl);
+    }
+}
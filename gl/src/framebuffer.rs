@@ -0,0 +1,333 @@
+//! Offscreen framebuffers.
+use crate::*;
+use std::num::NonZeroU32;
+
+/// The draw framebuffer and viewport, so offscreen passes can put them back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderState {
+    /// `None` is the window.
+    pub framebuffer: Option<NativeFramebuffer>,
+    pub viewport: [i32; 4],
+}
+
+impl RenderState {
+    pub fn save(gl: &Context) -> Self {
+        unsafe {
+            let binding = gl.get_parameter_i32(glow::DRAW_FRAMEBUFFER_BINDING);
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+            Self {
+                framebuffer: NonZeroU32::new(binding as u32).map(NativeFramebuffer),
+                viewport,
+            }
+        }
+    }
+
+    pub fn restore(&self, gl: &Context) {
+        let [x, y, width, height] = self.viewport;
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, self.framebuffer);
+            gl.viewport(x, y, width, height);
+        }
+    }
+}
+
+/// `Err` with the name of the problem if the bound framebuffer can't be drawn to.
+pub fn framebuffer_status(gl: &Context) -> Result<(), String> {
+    let status = unsafe { gl.check_framebuffer_status(glow::FRAMEBUFFER) };
+    let error = match status {
+        glow::FRAMEBUFFER_COMPLETE => return Ok(()),
+        glow::FRAMEBUFFER_UNDEFINED => "FRAMEBUFFER_UNDEFINED",
+        glow::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+        glow::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => {
+            "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT"
+        }
+        glow::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+        glow::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+        glow::FRAMEBUFFER_UNSUPPORTED => "FRAMEBUFFER_UNSUPPORTED",
+        glow::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+        glow::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+        _ => return Err(format!("Framebuffer status {:#x}", status)),
+    };
+    Err(error.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepthLayout {
+    /// `sampler2D`
+    Single,
+    /// `sampler2DArray` with this many layers.
+    Array(u32),
+    /// `samplerCube`, the layers are the faces.
+    Cube,
+}
+
+/// Depth only framebuffer for shadow maps. 32 bit float depth, sampled without comparison.
+/// Lookups outside of 2D maps read 1.0, the far plane.
+#[derive(Debug)]
+pub struct DepthMap {
+    pub framebuffer: NativeFramebuffer,
+    pub texture: NativeTexture,
+    /// Width and height of every layer.
+    pub size: u32,
+    pub layout: DepthLayout,
+}
+
+impl DepthMap {
+    pub fn new(gl: &Context, size: u32, layout: DepthLayout) -> Result<Self, String> {
+        let map = unsafe {
+            let texture = gl.create_texture()?;
+            let framebuffer = gl.create_framebuffer()?;
+            Self {
+                framebuffer,
+                texture,
+                size,
+                layout,
+            }
+        };
+
+        let target = map.target();
+        let size = size as i32;
+        unsafe {
+            gl.bind_texture(target, Some(map.texture));
+            match layout {
+                DepthLayout::Single => gl.tex_image_2d(
+                    target,
+                    0,
+                    glow::DEPTH_COMPONENT32F as i32,
+                    size,
+                    size,
+                    0,
+                    glow::DEPTH_COMPONENT,
+                    glow::FLOAT,
+                    None,
+                ),
+                DepthLayout::Array(layers) => gl.tex_image_3d(
+                    target,
+                    0,
+                    glow::DEPTH_COMPONENT32F as i32,
+                    size,
+                    size,
+                    layers as i32,
+                    0,
+                    glow::DEPTH_COMPONENT,
+                    glow::FLOAT,
+                    None,
+                ),
+                DepthLayout::Cube => {
+                    for face in 0..6 {
+                        gl.tex_image_2d(
+                            glow::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                            0,
+                            glow::DEPTH_COMPONENT32F as i32,
+                            size,
+                            size,
+                            0,
+                            glow::DEPTH_COMPONENT,
+                            glow::FLOAT,
+                            None,
+                        );
+                    }
+                }
+            }
+
+            //PCF is done in the shader, so neighbouring texels aren't blended.
+            gl.tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, glow::NEAREST as i32);
+            gl.tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, glow::NEAREST as i32);
+            let wrap = if layout == DepthLayout::Cube {
+                glow::CLAMP_TO_EDGE
+            } else {
+                gl.tex_parameter_f32_slice(target, glow::TEXTURE_BORDER_COLOR, &[1.0; 4]);
+                glow::CLAMP_TO_BORDER
+            };
+            for parameter in [
+                glow::TEXTURE_WRAP_S,
+                glow::TEXTURE_WRAP_T,
+                glow::TEXTURE_WRAP_R,
+            ] {
+                gl.tex_parameter_i32(target, parameter, wrap as i32);
+            }
+
+            //No color attachment to draw to or read from.
+            let state = RenderState::save(gl);
+            map.attach(gl, 0);
+            gl.draw_buffer(glow::NONE);
+            gl.read_buffer(glow::NONE);
+            let status = framebuffer_status(gl);
+            state.restore(gl);
+            if let Err(error) = status {
+                map.delete(gl);
+                return Err(error);
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn target(&self) -> u32 {
+        match self.layout {
+            DepthLayout::Single => glow::TEXTURE_2D,
+            DepthLayout::Array(_) => glow::TEXTURE_2D_ARRAY,
+            DepthLayout::Cube => glow::TEXTURE_CUBE_MAP,
+        }
+    }
+
+    pub fn layers(&self) -> u32 {
+        match self.layout {
+            DepthLayout::Single => 1,
+            DepthLayout::Array(layers) => layers,
+            DepthLayout::Cube => 6,
+        }
+    }
+
+    /// Binds the framebuffer with `layer` attached, sets the viewport to it and clears it.
+    /// For cubes the layers are the faces in `TEXTURE_CUBE_MAP_POSITIVE_X + i` order.
+    pub fn begin(&self, gl: &Context, layer: u32) {
+        assert!(layer < self.layers());
+        unsafe {
+            self.attach(gl, layer);
+            gl.viewport(0, 0, self.size as i32, self.size as i32);
+            gl.clear(glow::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    unsafe fn attach(&self, gl: &Context, layer: u32) {
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+        match self.layout {
+            DepthLayout::Single => gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::TEXTURE_2D,
+                Some(self.texture),
+                0,
+            ),
+            DepthLayout::Array(_) => gl.framebuffer_texture_layer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                Some(self.texture),
+                0,
+                layer as i32,
+            ),
+            DepthLayout::Cube => gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                Some(self.texture),
+                0,
+            ),
+        }
+    }
+
+    /// Leaves `unit` active.
+    pub fn bind(&self, gl: &Context, unit: u32) {
+        unsafe {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            gl.bind_texture(self.target(), Some(self.texture));
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_texture(self.texture);
+        }
+    }
+}
+
+/// Framebuffer with one clamped, linearly filtered 2D color texture and an optional depth buffer.
+#[derive(Debug)]
+pub struct RenderTarget {
+    pub framebuffer: NativeFramebuffer,
+    pub texture: NativeTexture,
+    pub depth: Option<NativeRenderbuffer>,
+    pub width: u32,
+    pub height: u32,
+    pub internal_format: u32,
+}
+
+impl RenderTarget {
+    pub fn new(
+        gl: &Context,
+        width: u32,
+        height: u32,
+        internal_format: u32,
+        depth: bool,
+    ) -> Result<Self, String> {
+        unsafe {
+            let state = RenderState::save(gl);
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            let texture = create_texture_2d(gl, internal_format, width, height, None);
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            let depth = if depth {
+                let renderbuffer = gl.create_renderbuffer()?;
+                gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+                gl.renderbuffer_storage(
+                    glow::RENDERBUFFER,
+                    glow::DEPTH_COMPONENT24,
+                    width as i32,
+                    height as i32,
+                );
+                gl.framebuffer_renderbuffer(
+                    glow::FRAMEBUFFER,
+                    glow::DEPTH_ATTACHMENT,
+                    glow::RENDERBUFFER,
+                    Some(renderbuffer),
+                );
+                gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+                Some(renderbuffer)
+            } else {
+                None
+            };
+
+            let target = Self {
+                framebuffer,
+                texture,
+                depth,
+                width,
+                height,
+                internal_format,
+            };
+            let status = framebuffer_status(gl);
+            state.restore(gl);
+            match status {
+                Ok(()) => Ok(target),
+                Err(error) => {
+                    target.delete(gl);
+                    Err(error)
+                }
+            }
+        }
+    }
+
+    /// Recreates the texture and depth buffer, their contents are lost.
+    pub fn resize(&mut self, gl: &Context, width: u32, height: u32) -> Result<(), String> {
+        let depth = self.depth.is_some();
+        let resized = Self::new(gl, width, height, self.internal_format, depth)?;
+        std::mem::replace(self, resized).delete(gl);
+        Ok(())
+    }
+
+    /// Binds the framebuffer and sets the viewport to cover it.
+    pub fn begin(&self, gl: &Context) {
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+            gl.viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_framebuffer(self.framebuffer);
+            gl.delete_texture(self.texture);
+            if let Some(depth) = self.depth {
+                gl.delete_renderbuffer(depth);
+            }
+        }
+    }
+}
@@ -0,0 +1,1058 @@
+//! glTF 2.0 import, both `.gltf` with external or embedded buffers and binary `.glb`.
+//!
+//! https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+use crate::json::{self, Json};
+use crate::*;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfNode {
+    pub name: String,
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into `Gltf::meshes`.
+    pub mesh: Option<usize>,
+    /// Index into `Gltf::cameras`.
+    pub camera: Option<usize>,
+}
+
+impl GltfNode {
+    /// Translation * rotation * scale.
+    pub fn local_matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fragments with less alpha than the cutoff are discarded.
+    Mask(f32),
+    Blend,
+}
+
+/// Texture index into `Gltf::textures` and the UV set it uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureSlot {
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+/// Metallic-roughness material, factors are multiplied with their textures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+    pub name: String,
+    /// Linear RGBA.
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<TextureSlot>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness is in green and metallic in blue.
+    pub metallic_roughness_texture: Option<TextureSlot>,
+    pub normal_texture: Option<TextureSlot>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureSlot>,
+    pub occlusion_strength: f32,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<TextureSlot>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: [1.0; 4],
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: [0.0; 3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageSource {
+    /// External file, already resolved relative to the `.gltf`.
+    Path(PathBuf),
+    /// Data URI or buffer view.
+    Embedded { mime_type: String, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfImage {
+    pub name: String,
+    pub source: ImageSource,
+}
+
+impl GltfImage {
+    pub fn decode(&self) -> Result<image::RgbaImage, String> {
+        let image = match &self.source {
+            ImageSource::Path(path) => image::open(path),
+            ImageSource::Embedded { data, .. } => image::load_from_memory(data),
+        };
+        image
+            .map(|image| image.into_rgba8())
+            .map_err(|e| format!("image '{}': {}", self.name, e))
+    }
+}
+
+/// GL filter and wrap modes, glTF uses the same enum values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureSampler {
+    pub mag_filter: u32,
+    pub min_filter: u32,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+impl Default for TextureSampler {
+    fn default() -> Self {
+        Self {
+            mag_filter: glow::LINEAR,
+            min_filter: glow::LINEAR_MIPMAP_LINEAR,
+            wrap_s: glow::REPEAT,
+            wrap_t: glow::REPEAT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GltfTexture {
+    /// Index into `Gltf::images`.
+    pub image: usize,
+    pub sampler: TextureSampler,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// `yfov` is in radians. Without an aspect ratio the viewport's should be used.
+    /// Without `zfar` the projection is infinite.
+    Perspective {
+        yfov: f32,
+        aspect: Option<f32>,
+        znear: f32,
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+impl Projection {
+    pub fn matrix(&self, viewport_aspect: f32) -> glm::Mat4 {
+        match *self {
+            Projection::Perspective {
+                yfov,
+                aspect,
+                znear,
+                zfar: Some(zfar),
+            } => glm::perspective(aspect.unwrap_or(viewport_aspect), yfov, znear, zfar),
+            Projection::Perspective {
+                yfov,
+                aspect,
+                znear,
+                zfar: None,
+            } => glm::infinite_perspective_rh_no(aspect.unwrap_or(viewport_aspect), yfov, znear),
+            Projection::Orthographic {
+                xmag,
+                ymag,
+                znear,
+                zfar,
+            } => glm::ortho(-xmag, xmag, -ymag, ymag, znear, zfar),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GltfCamera {
+    pub name: String,
+    pub projection: Projection,
+}
+
+/// Everything in the file, indices match the glTF arrays.
+/// Meshes have one submesh per primitive, their materials index into `materials`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Gltf {
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of the default scene.
+    pub roots: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<GltfImage>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl Gltf {
+    /// World matrix of every node, nodes outside of the default scene are left as identity.
+    pub fn world_matrices(&self) -> Vec<glm::Mat4> {
+        let mut world = vec![glm::Mat4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, glm::Mat4)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, glm::Mat4::identity()))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            world[node] = parent * self.nodes[node].local_matrix();
+            stack.extend(self.nodes[node].children.iter().map(|&c| (c, world[node])));
+        }
+        world
+    }
+
+    /// Nodes of the default scene that have a mesh, with their world matrices.
+    pub fn mesh_nodes(&self) -> Vec<(usize, glm::Mat4)> {
+        let world = self.world_matrices();
+        let mut nodes = Vec::new();
+        let mut stack = self.roots.clone();
+        while let Some(node) = stack.pop() {
+            if self.nodes[node].mesh.is_some() {
+                nodes.push((node, world[node]));
+            }
+            stack.extend(&self.nodes[node].children);
+        }
+        nodes
+    }
+}
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON: u32 = 0x4E4F534A;
+const GLB_BIN: u32 = 0x004E4942;
+
+/// Loads a `.gltf` or `.glb`, external buffers and images are resolved relative to the file.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<Gltf, ModelError> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let bytes =
+        std::fs::read(path).map_err(|e| ModelError::Io(path.to_path_buf(), e.to_string()))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let result = if bytes.starts_with(GLB_MAGIC) {
+        parse_glb(&bytes, directory)
+    } else {
+        std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(|json| parse_gltf(json, None, directory))
+    };
+    result.map_err(|message| ModelError::Format {
+        file: name,
+        message,
+    })
+}
+
+pub fn parse_glb(bytes: &[u8], directory: &Path) -> Result<Gltf, String> {
+    let u32_at = |offset: usize| -> Result<u32, String> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| "truncated glb".to_string())
+    };
+
+    if !bytes.starts_with(GLB_MAGIC) {
+        return Err("missing glb header".to_string());
+    }
+    if u32_at(4)? != 2 {
+        return Err(format!("unsupported glb version {}", u32_at(4)?));
+    }
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    let length = (u32_at(8)? as usize).min(bytes.len());
+    while offset + 8 <= length {
+        let chunk_length = u32_at(offset)? as usize;
+        let start = offset + 8;
+        let chunk = bytes
+            .get(start..start + chunk_length)
+            .ok_or_else(|| "truncated glb chunk".to_string())?;
+        match u32_at(offset + 4)? {
+            GLB_JSON => json = Some(std::str::from_utf8(chunk).map_err(|e| e.to_string())?),
+            GLB_BIN => bin = Some(chunk),
+            //Unknown chunks must be ignored.
+            _ => {}
+        }
+        //Chunks are padded to 4 bytes.
+        offset = start + chunk_length.div_ceil(4) * 4;
+    }
+
+    parse_gltf(json.ok_or("glb has no JSON chunk")?, bin, directory)
+}
+
+/// `bin` is the binary chunk of a `.glb`, used by the buffer without a `uri`.
+pub fn parse_gltf(source: &str, bin: Option<&[u8]>, directory: &Path) -> Result<Gltf, String> {
+    let root = json::parse(source)?;
+    let version = root.get("asset").get("version").as_str().unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("unsupported glTF version '{}'", version));
+    }
+
+    let mut buffers: Vec<Vec<u8>> = Vec::new();
+    for (i, buffer) in root.get("buffers").as_array().iter().enumerate() {
+        let data = match buffer.get("uri").as_str() {
+            Some(uri) => read_uri(uri, directory)?,
+            None => bin
+                .ok_or_else(|| format!("buffer {} has no uri and there is no binary chunk", i))?
+                .to_vec(),
+        };
+        let length = buffer.get("byteLength").as_usize().unwrap_or(0);
+        if data.len() < length {
+            return Err(format!(
+                "buffer {} is {} bytes, expected {}",
+                i,
+                data.len(),
+                length
+            ));
+        }
+        buffers.push(data);
+    }
+
+    let reader = Reader {
+        root: &root,
+        buffers: &buffers,
+    };
+
+    let mut gltf = Gltf::default();
+
+    for (i, node) in root.get("nodes").as_array().iter().enumerate() {
+        let (translation, rotation, scale) = match node.get("matrix").as_floats::<16>() {
+            //Column major, decomposing doesn't handle shear.
+            Some(m) => {
+                let matrix = glm::make_mat4(&m);
+                let translation = glm::vec3(m[12], m[13], m[14]);
+                let scale = glm::vec3(
+                    glm::length(&glm::vec3(m[0], m[1], m[2])),
+                    glm::length(&glm::vec3(m[4], m[5], m[6])),
+                    glm::length(&glm::vec3(m[8], m[9], m[10])),
+                );
+                let rotation = glm::to_quat(&(matrix * glm::scaling(&scale.map(|s| 1.0 / s))));
+                (translation, rotation, scale)
+            }
+            None => {
+                let t = node.get("translation").as_floats::<3>().unwrap_or([0.0; 3]);
+                let r = node
+                    .get("rotation")
+                    .as_floats::<4>()
+                    .unwrap_or([0.0, 0.0, 0.0, 1.0]);
+                let s = node.get("scale").as_floats::<3>().unwrap_or([1.0; 3]);
+                (
+                    glm::Vec3::from(t),
+                    glm::quat(r[0], r[1], r[2], r[3]),
+                    glm::Vec3::from(s),
+                )
+            }
+        };
+
+        gltf.nodes.push(GltfNode {
+            name: name(node, "node", i),
+            translation,
+            rotation,
+            scale,
+            parent: None,
+            children: node
+                .get("children")
+                .as_array()
+                .iter()
+                .filter_map(|c| c.as_usize())
+                .collect(),
+            mesh: node.get("mesh").as_usize(),
+            camera: node.get("camera").as_usize(),
+        });
+    }
+
+    //Parents are implied by the children lists.
+    for i in 0..gltf.nodes.len() {
+        for child in gltf.nodes[i].children.clone() {
+            let node = gltf
+                .nodes
+                .get_mut(child)
+                .ok_or_else(|| format!("node {} has a missing child {}", i, child))?;
+            if node.parent.is_some() {
+                return Err(format!("node {} has more than one parent", child));
+            }
+            node.parent = Some(i);
+        }
+    }
+    for i in 0..gltf.nodes.len() {
+        let mut parent = gltf.nodes[i].parent;
+        for _ in 0..gltf.nodes.len() {
+            match parent {
+                Some(p) if p == i => return Err(format!("node {} is its own ancestor", i)),
+                Some(p) => parent = gltf.nodes[p].parent,
+                None => break,
+            }
+        }
+    }
+
+    let scenes = root.get("scenes").as_array();
+    let scene = root.get("scene").as_usize().unwrap_or(0);
+    gltf.roots = match scenes.get(scene) {
+        Some(scene) => scene
+            .get("nodes")
+            .as_array()
+            .iter()
+            .filter_map(|n| n.as_usize())
+            .collect(),
+        //Without scenes every parentless node is a root.
+        None => (0..gltf.nodes.len())
+            .filter(|&i| gltf.nodes[i].parent.is_none())
+            .collect(),
+    };
+    //Catches cycles, which would also have a node with two parents or no root.
+    for &root in &gltf.roots {
+        if gltf.nodes.get(root).is_none_or(|n| n.parent.is_some()) {
+            return Err(format!("scene root {} is missing or has a parent", root));
+        }
+    }
+
+    for (i, mesh) in root.get("meshes").as_array().iter().enumerate() {
+        gltf.meshes.push(reader.mesh(mesh, i)?);
+    }
+
+    for (i, material) in root.get("materials").as_array().iter().enumerate() {
+        gltf.materials.push(self::material(material, i));
+    }
+
+    let samplers = root.get("samplers").as_array();
+    for (i, texture) in root.get("textures").as_array().iter().enumerate() {
+        let image = texture
+            .get("source")
+            .as_usize()
+            .ok_or_else(|| format!("texture {} has no source", i))?;
+        let mut sampler = TextureSampler::default();
+        if let Some(json) = texture
+            .get("sampler")
+            .as_usize()
+            .and_then(|s| samplers.get(s))
+        {
+            let get =
+                |key: &str, default: u32| json.get(key).as_usize().map_or(default, |v| v as u32);
+            sampler = TextureSampler {
+                mag_filter: get("magFilter", sampler.mag_filter),
+                min_filter: get("minFilter", sampler.min_filter),
+                wrap_s: get("wrapS", sampler.wrap_s),
+                wrap_t: get("wrapT", sampler.wrap_t),
+            };
+        }
+        gltf.textures.push(GltfTexture { image, sampler });
+    }
+
+    for (i, image) in root.get("images").as_array().iter().enumerate() {
+        let source = if let Some(uri) = image.get("uri").as_str() {
+            if let Some(data) = uri.strip_prefix("data:") {
+                ImageSource::Embedded {
+                    mime_type: data.split([';', ',']).next().unwrap_or("").to_string(),
+                    data: read_uri(uri, directory)?,
+                }
+            } else {
+                ImageSource::Path(directory.join(percent_decode(uri)))
+            }
+        } else {
+            let view = image
+                .get("bufferView")
+                .as_usize()
+                .ok_or_else(|| format!("image {} has no uri or bufferView", i))?;
+            ImageSource::Embedded {
+                mime_type: image.get("mimeType").as_str().unwrap_or("").to_string(),
+                data: reader.view(view)?.0.to_vec(),
+            }
+        };
+        gltf.images.push(GltfImage {
+            name: name(image, "image", i),
+            source,
+        });
+    }
+
+    for (i, camera) in root.get("cameras").as_array().iter().enumerate() {
+        let projection = match camera.get("type").as_str() {
+            Some("perspective") => {
+                let p = camera.get("perspective");
+                Projection::Perspective {
+                    yfov: p
+                        .get("yfov")
+                        .as_f32()
+                        .ok_or("perspective camera has no yfov")?,
+                    aspect: p.get("aspectRatio").as_f32(),
+                    znear: p
+                        .get("znear")
+                        .as_f32()
+                        .ok_or("perspective camera has no znear")?,
+                    zfar: p.get("zfar").as_f32(),
+                }
+            }
+            Some("orthographic") => {
+                let o = camera.get("orthographic");
+                let get = |key: &str| {
+                    o.get(key)
+                        .as_f32()
+                        .ok_or_else(|| format!("orthographic camera has no {}", key))
+                };
+                Projection::Orthographic {
+                    xmag: get("xmag")?,
+                    ymag: get("ymag")?,
+                    znear: get("znear")?,
+                    zfar: get("zfar")?,
+                }
+            }
+            other => return Err(format!("camera {} has an unknown type {:?}", i, other)),
+        };
+        gltf.cameras.push(GltfCamera {
+            name: name(camera, "camera", i),
+            projection,
+        });
+    }
+
+    //Check references up front so drawing can index without checking.
+    for (i, node) in gltf.nodes.iter().enumerate() {
+        if node.mesh.is_some_and(|m| m >= gltf.meshes.len())
+            || node.camera.is_some_and(|c| c >= gltf.cameras.len())
+        {
+            return Err(format!("node {} references a missing mesh or camera", i));
+        }
+    }
+    for mesh in &gltf.meshes {
+        if mesh
+            .submeshes
+            .iter()
+            .any(|s| s.material.is_some_and(|m| m >= gltf.materials.len()))
+        {
+            return Err(format!(
+                "mesh '{}' references a missing material",
+                mesh.name
+            ));
+        }
+    }
+    if let Some(texture) = gltf.textures.iter().find(|t| t.image >= gltf.images.len()) {
+        return Err(format!(
+            "texture references missing image {}",
+            texture.image
+        ));
+    }
+
+    Ok(gltf)
+}
+
+fn name(json: &Json, kind: &str, index: usize) -> String {
+    match json.get("name").as_str() {
+        Some(name) => name.to_string(),
+        None => format!("{} {}", kind, index),
+    }
+}
+
+fn material(json: &Json, index: usize) -> PbrMaterial {
+    let slot = |json: &Json| {
+        Some(TextureSlot {
+            texture: json.get("index").as_usize()?,
+            tex_coord: json.get("texCoord").as_usize().unwrap_or(0) as u32,
+        })
+    };
+    let pbr = json.get("pbrMetallicRoughness");
+    let default = PbrMaterial::default();
+
+    PbrMaterial {
+        name: name(json, "material", index),
+        base_color: pbr
+            .get("baseColorFactor")
+            .as_floats()
+            .unwrap_or(default.base_color),
+        base_color_texture: slot(pbr.get("baseColorTexture")),
+        metallic: pbr
+            .get("metallicFactor")
+            .as_f32()
+            .unwrap_or(default.metallic),
+        roughness: pbr
+            .get("roughnessFactor")
+            .as_f32()
+            .unwrap_or(default.roughness),
+        metallic_roughness_texture: slot(pbr.get("metallicRoughnessTexture")),
+        normal_texture: slot(json.get("normalTexture")),
+        normal_scale: json
+            .get("normalTexture")
+            .get("scale")
+            .as_f32()
+            .unwrap_or(1.0),
+        occlusion_texture: slot(json.get("occlusionTexture")),
+        occlusion_strength: json
+            .get("occlusionTexture")
+            .get("strength")
+            .as_f32()
+            .unwrap_or(1.0),
+        emissive: json
+            .get("emissiveFactor")
+            .as_floats()
+            .unwrap_or(default.emissive),
+        emissive_texture: slot(json.get("emissiveTexture")),
+        alpha_mode: match json.get("alphaMode").as_str() {
+            Some("MASK") => AlphaMode::Mask(json.get("alphaCutoff").as_f32().unwrap_or(0.5)),
+            Some("BLEND") => AlphaMode::Blend,
+            _ => AlphaMode::Opaque,
+        },
+        double_sided: json.get("doubleSided").as_bool().unwrap_or(false),
+    }
+}
+
+/// Element layout of an accessor, see `Reader::accessor`.
+struct Accessor<'a> {
+    /// `None` without a buffer view.
+    bytes: Option<&'a [u8]>,
+    offset: usize,
+    stride: usize,
+    /// Bytes per component.
+    size: usize,
+    /// `componentType`
+    ty: u32,
+    normalized: bool,
+    count: usize,
+}
+
+impl Accessor<'_> {
+    /// Little endian bytes of a component, zeros without a buffer view.
+    fn component(&self, element: usize, component: usize) -> &[u8] {
+        match self.bytes {
+            Some(bytes) => {
+                let at = self.offset + element * self.stride + component * self.size;
+                &bytes[at..at + self.size]
+            }
+            None => &[0; 4][..self.size],
+        }
+    }
+}
+
+struct Reader<'a> {
+    root: &'a Json,
+    buffers: &'a [Vec<u8>],
+}
+
+impl Reader<'_> {
+    /// Bytes of a buffer view and its stride.
+    fn view(&self, index: usize) -> Result<(&[u8], Option<usize>), String> {
+        let view = self.root.get("bufferViews").as_array().get(index);
+        let view = view.ok_or_else(|| format!("missing buffer view {}", index))?;
+        let buffer = view.get("buffer").as_usize().unwrap_or(0);
+        let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+        let length = view.get("byteLength").as_usize().unwrap_or(0);
+        let bytes = self
+            .buffers
+            .get(buffer)
+            .zip(offset.checked_add(length))
+            .and_then(|(b, end)| b.get(offset..end))
+            .ok_or_else(|| format!("buffer view {} is out of bounds", index))?;
+        Ok((bytes, view.get("byteStride").as_usize()))
+    }
+
+    /// Checks an accessor with `N` components per element against its buffer view.
+    fn accessor<const N: usize>(&self, index: usize) -> Result<Accessor<'_>, String> {
+        let accessor = self.root.get("accessors").as_array().get(index);
+        let accessor = accessor.ok_or_else(|| format!("missing accessor {}", index))?;
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            other => {
+                return Err(format!(
+                    "accessor {} has unsupported type {:?}",
+                    index, other
+                ))
+            }
+        };
+        if components != N {
+            return Err(format!(
+                "accessor {} has {} components, expected {}",
+                index, components, N
+            ));
+        }
+        if !accessor.get("sparse").is_null() {
+            return Err(format!(
+                "accessor {} is sparse, which isn't supported",
+                index
+            ));
+        }
+
+        let count = accessor.get("count").as_usize().unwrap_or(0);
+        let ty = accessor.get("componentType").as_usize().unwrap_or(0) as u32;
+        let normalized = accessor.get("normalized").as_bool().unwrap_or(false);
+        let size = match ty {
+            glow::BYTE | glow::UNSIGNED_BYTE => 1,
+            glow::SHORT | glow::UNSIGNED_SHORT => 2,
+            glow::UNSIGNED_INT | glow::FLOAT => 4,
+            _ => return Err(format!("accessor {} has component type {}", index, ty)),
+        };
+
+        //Accessors without a buffer view are all zeros.
+        let Some(view) = accessor.get("bufferView").as_usize() else {
+            return Ok(Accessor {
+                bytes: None,
+                offset: 0,
+                stride: 0,
+                size,
+                ty,
+                normalized,
+                count,
+            });
+        };
+        let (bytes, stride) = self.view(view)?;
+        let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+        let stride = stride.unwrap_or(size * N);
+        //Sizes come straight from the file, so a huge count mustn't wrap around.
+        let end = match count {
+            0 => Some(0),
+            _ => stride
+                .checked_mul(count - 1)
+                .and_then(|end| end.checked_add(offset))
+                .and_then(|end| end.checked_add(size * N)),
+        };
+        if end.is_none_or(|end| end > bytes.len()) {
+            return Err(format!("accessor {} is out of bounds", index));
+        }
+        Ok(Accessor {
+            bytes: Some(bytes),
+            offset,
+            stride,
+            size,
+            ty,
+            normalized,
+            count,
+        })
+    }
+
+    /// Reads every element of an accessor as floats, normalized integers are mapped to 0..1 or -1..1.
+    fn floats<const N: usize>(&self, index: usize) -> Result<Vec<[f32; N]>, String> {
+        let accessor = self.accessor::<N>(index)?;
+        let read = |b: &[u8]| -> f32 {
+            match (accessor.ty, accessor.normalized) {
+                (glow::FLOAT, _) => f32::from_le_bytes(b.try_into().unwrap()),
+                (glow::BYTE, false) => b[0] as i8 as f32,
+                (glow::BYTE, true) => (b[0] as i8 as f32 / 127.0).max(-1.0),
+                (glow::UNSIGNED_BYTE, false) => b[0] as f32,
+                (glow::UNSIGNED_BYTE, true) => b[0] as f32 / 255.0,
+                (glow::SHORT, false) => i16::from_le_bytes([b[0], b[1]]) as f32,
+                (glow::SHORT, true) => {
+                    (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0).max(-1.0)
+                }
+                (glow::UNSIGNED_SHORT, false) => u16::from_le_bytes([b[0], b[1]]) as f32,
+                (glow::UNSIGNED_SHORT, true) => u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0,
+                (_, false) => u32::from_le_bytes(b.try_into().unwrap()) as f32,
+                (_, true) => u32::from_le_bytes(b.try_into().unwrap()) as f32 / u32::MAX as f32,
+            }
+        };
+
+        Ok((0..accessor.count)
+            .map(|i| {
+                let mut element = [0.0; N];
+                for (c, value) in element.iter_mut().enumerate() {
+                    *value = read(accessor.component(i, c));
+                }
+                element
+            })
+            .collect())
+    }
+
+    /// Reads a scalar accessor of unsigned integers exactly, floats lose precision above 2^24.
+    fn integers(&self, index: usize) -> Result<Vec<u32>, String> {
+        let accessor = self.accessor::<1>(index)?;
+        let read = match (accessor.ty, accessor.normalized) {
+            (glow::UNSIGNED_BYTE, false) => |b: &[u8]| b[0] as u32,
+            (glow::UNSIGNED_SHORT, false) => |b: &[u8]| u16::from_le_bytes([b[0], b[1]]) as u32,
+            (glow::UNSIGNED_INT, false) => |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap()),
+            _ => return Err(format!("accessor {} isn't unsigned integers", index)),
+        };
+        Ok((0..accessor.count)
+            .map(|i| read(accessor.component(i, 0)))
+            .collect())
+    }
+
+    /// Every primitive becomes a submesh of one mesh.
+    fn mesh(&self, json: &Json, index: usize) -> Result<Mesh, String> {
+        let mut mesh = Mesh {
+            name: name(json, "mesh", index),
+            ..Default::default()
+        };
+
+        for primitive in json.get("primitives").as_array() {
+            let attributes = primitive.get("attributes");
+            let accessor = |key: &str| attributes.get(key).as_usize();
+            let positions = self.floats::<3>(
+                accessor("POSITION").ok_or_else(|| format!("mesh {} has no POSITION", index))?,
+            )?;
+            let normals = match accessor("NORMAL") {
+                Some(a) => Some(self.floats::<3>(a)?),
+                None => None,
+            };
+            let uvs = match accessor("TEXCOORD_0") {
+                Some(a) => self.floats::<2>(a)?,
+                None => vec![[0.0; 2]; positions.len()],
+            };
+            //Only used with the normals they were made for.
+            let tangents = match (accessor("TANGENT"), &normals) {
+                (Some(a), Some(_)) => Some(self.floats::<4>(a)?),
+                _ => None,
+            };
+            if normals.as_ref().is_some_and(|n| n.len() != positions.len())
+                || tangents
+                    .as_ref()
+                    .is_some_and(|t| t.len() != positions.len())
+                || uvs.len() != positions.len()
+            {
+                return Err(format!(
+                    "mesh {} has attributes of different lengths",
+                    index
+                ));
+            }
+
+            let mut indices: Vec<u32> = match primitive.get("indices").as_usize() {
+                Some(a) => self.integers(a)?,
+                None => (0..positions.len() as u32).collect(),
+            };
+            if let Some(i) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+                return Err(format!("mesh {} has index {} out of range", index, i));
+            }
+
+            //Strips and fans are turned into lists, points and lines can't be drawn as triangles.
+            indices = match primitive.get("mode").as_usize().unwrap_or(4) as u32 {
+                glow::TRIANGLES => indices,
+                glow::TRIANGLE_STRIP => (2..indices.len())
+                    .flat_map(|i| match i % 2 {
+                        0 => [indices[i - 2], indices[i - 1], indices[i]],
+                        _ => [indices[i - 1], indices[i - 2], indices[i]],
+                    })
+                    .collect(),
+                glow::TRIANGLE_FAN => (2..indices.len())
+                    .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+                    .collect(),
+                mode => return Err(format!("mesh {} uses unsupported mode {}", index, mode)),
+            };
+
+            let base = mesh.vertices.len() as u32;
+            let mut vertices: Vec<ModelVertex> = positions
+                .iter()
+                .zip(&uvs)
+                .map(|(&position, &uv)| ModelVertex {
+                    position,
+                    uv,
+                    ..Default::default()
+                })
+                .collect();
+            match normals {
+                Some(normals) => {
+                    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                        vertex.normal = normal;
+                    }
+                }
+                //The spec asks for flat normals, shared vertices get the average instead.
+                None => {
+                    for triangle in indices.chunks_exact(3) {
+                        let p = |i: usize| glm::Vec3::from(positions[triangle[i] as usize]);
+                        let normal = glm::cross(&(p(1) - p(0)), &(p(2) - p(0)));
+                        for &i in triangle {
+                            let n = &mut vertices[i as usize].normal;
+                            *n = [n[0] + normal.x, n[1] + normal.y, n[2] + normal.z];
+                        }
+                    }
+                    for vertex in &mut vertices {
+                        let n = glm::Vec3::from(vertex.normal);
+                        if n != glm::Vec3::zeros() {
+                            vertex.normal = glm::normalize(&n).into();
+                        }
+                    }
+                }
+            }
+
+            //The spec asks for MikkTSpace when they're missing, these only approximate it.
+            match tangents {
+                Some(tangents) => {
+                    for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                        vertex.tangent = tangent;
+                    }
+                }
+                None => generate_tangents(&mut vertices, &mut indices),
+            }
+
+            let start = mesh.indices.len() as u32;
+            mesh.vertices.extend(vertices);
+            mesh.indices.extend(indices.iter().map(|i| i + base));
+            mesh.submeshes.push(Submesh {
+                material: primitive.get("material").as_usize(),
+                indices: start..mesh.indices.len() as u32,
+            });
+        }
+
+        Ok(mesh)
+    }
+}
+
+/// Reads a `data:` URI or a file relative to `directory`.
+fn read_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, String> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (header, payload) = data.split_once(',').ok_or("invalid data uri")?;
+            if !header.ends_with(";base64") {
+                return Err("only base64 data uris are supported".to_string());
+            }
+            base64(payload)
+        }
+        None => {
+            let path = directory.join(percent_decode(uri));
+            std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+    }
+}
+
+/// URIs are percent encoded, mostly for spaces.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn base64(text: &str) -> Result<Vec<u8>, String> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Ok(c - b'A'),
+        b'a'..=b'z' => Ok(c - b'a' + 26),
+        b'0'..=b'9' => Ok(c - b'0' + 52),
+        b'+' | b'-' => Ok(62),
+        b'/' | b'_' => Ok(63),
+        _ => Err(format!("invalid base64 character '{}'", c as char)),
+    };
+
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        bits = bits << 6 | value(c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// GPU buffers and textures for a `Gltf`.
+#[derive(Debug)]
+pub struct GpuGltf {
+    pub meshes: Vec<GpuMesh>,
+    /// One per `Gltf::textures`, `None` if the image couldn't be decoded.
+    pub textures: Vec<Option<NativeTexture>>,
+}
+
+impl GpuGltf {
+    /// Images that fail to decode are logged and skipped.
+    pub fn new(gl: &Context, gltf: &Gltf) -> Self {
+        let meshes = gltf.meshes.iter().map(|m| GpuMesh::new(gl, m)).collect();
+        let images: Vec<Option<image::RgbaImage>> = gltf
+            .images
+            .iter()
+            .map(|image| image.decode().map_err(|error| eprintln!("{}", error)).ok())
+            .collect();
+
+        let textures = gltf
+            .textures
+            .iter()
+            .map(|texture| {
+                let image = images[texture.image].as_ref()?;
+                Some(unsafe { upload_texture(gl, image, texture.sampler) })
+            })
+            .collect();
+
+        Self { meshes, textures }
+    }
+
+    /// Draws every node of the default scene that has a mesh.
+    /// `material` gets the node's world matrix and the submesh before each draw.
+    pub fn draw(&self, gl: &Context, gltf: &Gltf, mut material: impl FnMut(&glm::Mat4, &Submesh)) {
+        for (node, world) in gltf.mesh_nodes() {
+            if let Some(mesh) = gltf.nodes[node].mesh {
+                self.meshes[mesh].draw(gl, |submesh| material(&world, submesh));
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        for mesh in self.meshes {
+            mesh.delete(gl);
+        }
+        for texture in self.textures.into_iter().flatten() {
+            unsafe { gl.delete_texture(texture) };
+        }
+    }
+}
+
+/// glTF's UV origin is the top left like the images, so nothing is flipped.
+unsafe fn upload_texture(
+    gl: &Context,
+    image: &image::RgbaImage,
+    sampler: TextureSampler,
+) -> NativeTexture {
+    let texture = gl.create_texture().unwrap();
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_WRAP_S,
+        sampler.wrap_s as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_WRAP_T,
+        sampler.wrap_t as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MIN_FILTER,
+        sampler.min_filter as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MAG_FILTER,
+        sampler.mag_filter as i32,
+    );
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        glow::RGBA8 as i32,
+        image.width() as i32,
+        image.height() as i32,
+        0,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        Some(image),
+    );
+    gl.generate_mipmap(glow::TEXTURE_2D);
+    texture
+}
@@ -0,0 +1,547 @@
+//! Blinn-Phong lights in a uniform buffer and the materials they shade.
+//!
+//! `shaders/fragment.glsl` declares the matching `Lights` block.
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Size of the `lights` array in the shader, see `with_light_limit`.
+pub const MAX_LIGHTS: usize = 16;
+
+/// Uniform buffer binding point of the `Lights` block.
+pub const LIGHTS_BINDING: u32 = 0;
+
+/// Texture units used by `PhongMaterial::bind`.
+pub const DIFFUSE_UNIT: u32 = 0;
+pub const SPECULAR_UNIT: u32 = 1;
+pub const NORMAL_UNIT: u32 = 2;
+pub const EMISSION_UNIT: u32 = 3;
+/// The cubemap sampled by `EnvironmentMapping`, bound by the application.
+pub const ENVIRONMENT_UNIT: u32 = 4;
+pub const DISPLACEMENT_UNIT: u32 = 5;
+
+/// `1 / (constant + linear * d + quadratic * d^2)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// Falls to roughly 1% of the light at `range`.
+    pub fn range(range: f32) -> Self {
+        Self {
+            constant: 1.0,
+            linear: 4.5 / range,
+            quadratic: 75.0 / (range * range),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> f32 {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+}
+
+/// Colors are linear and get multiplied by the intensity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional {
+        /// Where the light is shining towards.
+        direction: glm::Vec3,
+        color: glm::Vec3,
+        intensity: f32,
+    },
+    Point {
+        position: glm::Vec3,
+        color: glm::Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    /// Full intensity inside of `inner`, fading out until `outer`. Both are half angles in radians.
+    Spot {
+        position: glm::Vec3,
+        direction: glm::Vec3,
+        color: glm::Vec3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner: f32,
+        outer: f32,
+    },
+}
+
+impl Light {
+    /// Matches the `Light` struct in the shader, five `vec4`s.
+    fn std140(&self, out: &mut Vec<u8>) {
+        let zero = glm::Vec3::zeros();
+        let none = Attenuation {
+            constant: 1.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+        let (kind, position, direction, color, intensity, attenuation, cone) = match *self {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => (0.0, zero, direction, color, intensity, none, [0.0; 2]),
+            Light::Point {
+                position,
+                color,
+                intensity,
+                attenuation,
+            } => (1.0, position, zero, color, intensity, attenuation, [0.0; 2]),
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                attenuation,
+                inner,
+                outer,
+            } => (
+                2.0,
+                position,
+                direction,
+                color,
+                intensity,
+                attenuation,
+                [inner.cos(), outer.cos()],
+            ),
+        };
+        let direction = direction.try_normalize(0.0).unwrap_or(zero);
+        let color = color * intensity;
+        let a = attenuation;
+        for value in [
+            position.x,
+            position.y,
+            position.z,
+            kind, //
+            direction.x,
+            direction.y,
+            direction.z,
+            0.0, //
+            color.x,
+            color.y,
+            color.z,
+            0.0, //
+            a.constant,
+            a.linear,
+            a.quadratic,
+            0.0, //
+            cone[0],
+            cone[1],
+            0.0,
+            0.0,
+        ] {
+            out.extend_from_slice(&value.to_ne_bytes());
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LightError {
+    /// More lights than the shader was compiled for.
+    TooMany { count: usize, max: usize },
+}
+
+impl fmt::Display for LightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightError::TooMany { count, max } => {
+                write!(f, "{} lights exceeds the limit of {}", count, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LightError {}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Lights {
+    /// Linear color added to everything.
+    pub ambient: glm::Vec3,
+    /// At most `MAX_LIGHTS`, checked by `push` and `LightBuffer::upload`.
+    pub lights: Vec<Light>,
+}
+
+impl Lights {
+    pub fn new(ambient: glm::Vec3) -> Self {
+        Self {
+            ambient,
+            lights: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the light.
+    pub fn push(&mut self, light: Light) -> Result<usize, LightError> {
+        if self.lights.len() >= MAX_LIGHTS {
+            return Err(LightError::TooMany {
+                count: self.lights.len() + 1,
+                max: MAX_LIGHTS,
+            });
+        }
+        self.lights.push(light);
+        Ok(self.lights.len() - 1)
+    }
+
+    /// Contents of the `Lights` uniform block, always sized for `MAX_LIGHTS`.
+    pub fn std140(&self) -> Result<Vec<u8>, LightError> {
+        if self.lights.len() > MAX_LIGHTS {
+            return Err(LightError::TooMany {
+                count: self.lights.len(),
+                max: MAX_LIGHTS,
+            });
+        }
+        let mut out = Vec::with_capacity(LightBuffer::SIZE);
+        for value in [self.ambient.x, self.ambient.y, self.ambient.z] {
+            out.extend_from_slice(&value.to_ne_bytes());
+        }
+        out.extend_from_slice(&(self.lights.len() as i32).to_ne_bytes());
+        for light in &self.lights {
+            light.std140(&mut out);
+        }
+        out.resize(LightBuffer::SIZE, 0);
+        Ok(out)
+    }
+}
+
+/// Inserts `#define MAX_LIGHTS` after the `#version` line.
+pub fn with_light_limit(source: &str) -> String {
+    after_version(source, &format!("#define MAX_LIGHTS {}\n", MAX_LIGHTS))
+}
+
+/// Inserts `text` after the `#version` line, since nothing can come before it.
+pub fn after_version(source: &str, text: &str) -> String {
+    match source.find('\n') {
+        Some(end) if source.starts_with("#version") => {
+            format!("{}{}{}", &source[..=end], text, &source[end + 1..])
+        }
+        _ => format!("{}{}", text, source),
+    }
+}
+
+/// Uniform buffer bound to `LIGHTS_BINDING`.
+#[derive(Debug)]
+pub struct LightBuffer {
+    pub ubo: NativeBuffer,
+}
+
+impl LightBuffer {
+    /// Header (ambient and count) plus every light.
+    pub const SIZE: usize = 16 + MAX_LIGHTS * 80;
+
+    pub fn new(gl: &Context) -> Self {
+        unsafe {
+            let ubo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::UNIFORM_BUFFER, Some(ubo));
+            gl.buffer_data_size(glow::UNIFORM_BUFFER, Self::SIZE as i32, glow::DYNAMIC_DRAW);
+            gl.bind_buffer_base(glow::UNIFORM_BUFFER, LIGHTS_BINDING, Some(ubo));
+            gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+            Self { ubo }
+        }
+    }
+
+    /// Nothing is uploaded if there are too many lights.
+    pub fn upload(&self, gl: &Context, lights: &Lights) -> Result<(), LightError> {
+        let data = lights.std140()?;
+        unsafe {
+            gl.bind_buffer(glow::UNIFORM_BUFFER, Some(self.ubo));
+            gl.buffer_sub_data_u8_slice(glow::UNIFORM_BUFFER, 0, &data);
+            gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+        }
+        Ok(())
+    }
+
+    /// Connects the program's `Lights` block and material samplers, needs to run after every link.
+    pub fn bind_program(&self, gl: &Context, program: NativeProgram) {
+        unsafe {
+            if let Some(index) = gl.get_uniform_block_index(program, "Lights") {
+                gl.uniform_block_binding(program, index, LIGHTS_BINDING);
+            }
+            gl.use_program(Some(program));
+            for (name, unit) in [
+                ("material.diffuse", DIFFUSE_UNIT),
+                ("material.specular", SPECULAR_UNIT),
+                ("material.normal", NORMAL_UNIT),
+                ("material.emission", EMISSION_UNIT),
+                ("material.displacement", DISPLACEMENT_UNIT),
+                ("environment_map", ENVIRONMENT_UNIT),
+            ] {
+                let location = gl.get_uniform_location(program, name);
+                gl.uniform_1_i32(location.as_ref(), unit as i32);
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe { gl.delete_buffer(self.ubo) };
+    }
+}
+
+/// 1x1 textures bound in place of missing maps, so the shader doesn't need to branch.
+#[derive(Debug, Clone, Copy)]
+pub struct DefaultMaps {
+    pub white: NativeTexture,
+    /// Points straight out of the surface.
+    pub flat_normal: NativeTexture,
+}
+
+impl DefaultMaps {
+    pub fn new(gl: &Context) -> Self {
+        let pixel = |rgba: [u8; 4]| unsafe {
+            let texture = gl.create_texture().unwrap();
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                1,
+                1,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&rgba),
+            );
+            texture
+        };
+        Self {
+            white: pixel([255; 4]),
+            flat_normal: pixel([128, 128, 255, 255]),
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_texture(self.white);
+            gl.delete_texture(self.flat_normal);
+        }
+    }
+}
+
+/// How a `PhongMaterial` mixes in the cubemap bound to `ENVIRONMENT_UNIT`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum EnvironmentMapping {
+    #[default]
+    None,
+    /// Mirror reflection, `amount` of it replaces the lit color.
+    Reflect { amount: f32 },
+    /// `ratio` is the refractive index being left over the one being entered, 1 / 1.52 for glass.
+    Refract { ratio: f32, amount: f32 },
+}
+
+impl EnvironmentMapping {
+    /// `illum 3` reflects as much as the specular color, `illum 6` and `7` refract by `Ni`.
+    pub fn from_mtl(material: &Material) -> Self {
+        match material.illumination {
+            3 => Self::Reflect {
+                amount: material.specular.into_iter().fold(0.0, f32::max),
+            },
+            6 | 7 if material.optical_density > 0.0 => Self::Refract {
+                ratio: 1.0 / material.optical_density,
+                amount: 1.0,
+            },
+            _ => Self::None,
+        }
+    }
+
+    /// `kind` matches the defines in the shader.
+    fn uniforms(&self) -> (i32, f32, f32) {
+        match *self {
+            Self::None => (0, 0.0, 1.0),
+            Self::Reflect { amount } => (1, amount, 1.0),
+            Self::Refract { ratio, amount } => (2, amount, ratio),
+        }
+    }
+}
+
+/// How `PhongMaterial::displacement_map` offsets the texture coordinates.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ParallaxMode {
+    Off,
+    /// Steps through the map in layers and uses the first one below the surface.
+    Steep,
+    /// Steep parallax that interpolates between the layers above and below the surface.
+    #[default]
+    Occlusion,
+}
+
+impl ParallaxMode {
+    /// Off, steep, occlusion and around again.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Steep,
+            Self::Steep => Self::Occlusion,
+            Self::Occlusion => Self::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parallax {
+    pub mode: ParallaxMode,
+    /// Depth of white in the displacement map, in texture coordinates.
+    pub height_scale: f32,
+    /// Layers used looking straight at the surface, more are used towards `max_layers`
+    /// as the view gets flatter.
+    pub min_layers: u32,
+    pub max_layers: u32,
+}
+
+impl Default for Parallax {
+    fn default() -> Self {
+        Self {
+            mode: ParallaxMode::Occlusion,
+            height_scale: 0.1,
+            min_layers: 8,
+            max_layers: 32,
+        }
+    }
+}
+
+/// Maps are multiplied with their colors. Missing maps use `DefaultMaps`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhongMaterial {
+    pub diffuse: glm::Vec3,
+    pub specular: glm::Vec3,
+    pub emission: glm::Vec3,
+    pub shininess: f32,
+    pub diffuse_map: Option<NativeTexture>,
+    pub specular_map: Option<NativeTexture>,
+    /// Tangent space.
+    pub normal_map: Option<NativeTexture>,
+    pub emission_map: Option<NativeTexture>,
+    /// Depth below the surface, white is deepest. Only used for parallax.
+    pub displacement_map: Option<NativeTexture>,
+    pub parallax: Parallax,
+    pub environment: EnvironmentMapping,
+}
+
+impl Default for PhongMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: glm::vec3(1.0, 1.0, 1.0),
+            specular: glm::vec3(0.5, 0.5, 0.5),
+            emission: glm::Vec3::zeros(),
+            shininess: 32.0,
+            diffuse_map: None,
+            specular_map: None,
+            normal_map: None,
+            emission_map: None,
+            displacement_map: None,
+            parallax: Parallax::default(),
+            environment: EnvironmentMapping::None,
+        }
+    }
+}
+
+impl PhongMaterial {
+    /// Textures are shared through `cache`, maps that fail to load are logged and left out.
+    pub fn from_mtl(
+        gl: &Context,
+        material: &Material,
+        cache: &mut HashMap<PathBuf, NativeTexture>,
+    ) -> Self {
+        Self::from_mtl_with(material, |path| {
+            if let Some(texture) = cache.get(path) {
+                return Some(*texture);
+            }
+            match load_texture(gl, path) {
+                Ok(texture) => Some(*cache.entry(path.to_path_buf()).or_insert(texture)),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    None
+                }
+            }
+        })
+    }
+
+    /// `from_mtl` with the textures coming from `texture`.
+    pub fn from_mtl_with(
+        material: &Material,
+        mut texture: impl FnMut(&Path) -> Option<NativeTexture>,
+    ) -> Self {
+        let mut load = |path: &Option<PathBuf>| texture(path.as_ref()?);
+
+        let diffuse_map = load(&material.diffuse_map);
+        let specular_map = load(&material.specular_map);
+        let normal_map = load(&material.normal_map);
+        let emission_map = load(&material.emissive_map);
+        let displacement_map = load(&material.displacement_map);
+
+        //Maps replace the color when it's left at the default, which exporters usually do.
+        let specular = match specular_map {
+            Some(_) if material.specular == [0.0; 3] => glm::vec3(1.0, 1.0, 1.0),
+            _ => material.specular.into(),
+        };
+        let emission = match emission_map {
+            Some(_) if material.emissive == [0.0; 3] => glm::vec3(1.0, 1.0, 1.0),
+            _ => material.emissive.into(),
+        };
+
+        Self {
+            diffuse: material.diffuse.into(),
+            specular,
+            emission,
+            shininess: material.shininess.max(1.0),
+            diffuse_map,
+            specular_map,
+            normal_map,
+            emission_map,
+            displacement_map,
+            parallax: Parallax::default(),
+            environment: EnvironmentMapping::from_mtl(material),
+        }
+    }
+
+    /// Sets the `material` uniforms of the current program and binds the maps.
+    pub fn bind(&self, gl: &Context, program: NativeProgram, defaults: &DefaultMaps) {
+        unsafe {
+            let uniform = |name: &str| gl.get_uniform_location(program, name);
+            let vec3 = |name: &str, v: &glm::Vec3| {
+                gl.uniform_3_f32(uniform(name).as_ref(), v.x, v.y, v.z);
+            };
+            vec3("material.diffuse_color", &self.diffuse);
+            vec3("material.specular_color", &self.specular);
+            vec3("material.emission_color", &self.emission);
+            gl.uniform_1_f32(uniform("material.shininess").as_ref(), self.shininess);
+            let (kind, amount, ratio) = self.environment.uniforms();
+            gl.uniform_1_i32(uniform("material.environment").as_ref(), kind);
+            gl.uniform_1_f32(uniform("material.environment_amount").as_ref(), amount);
+            gl.uniform_1_f32(uniform("material.refraction_ratio").as_ref(), ratio);
+            let parallax = match (self.displacement_map, self.parallax.mode) {
+                (None, _) | (_, ParallaxMode::Off) => 0,
+                (_, ParallaxMode::Steep) => 1,
+                (_, ParallaxMode::Occlusion) => 2,
+            };
+            gl.uniform_1_i32(uniform("material.parallax").as_ref(), parallax);
+            let p = &self.parallax;
+            gl.uniform_1_f32(uniform("material.height_scale").as_ref(), p.height_scale);
+            gl.uniform_1_f32(uniform("material.min_layers").as_ref(), p.min_layers as f32);
+            gl.uniform_1_f32(uniform("material.max_layers").as_ref(), p.max_layers as f32);
+
+            for (unit, map, default) in [
+                (DIFFUSE_UNIT, self.diffuse_map, defaults.white),
+                (SPECULAR_UNIT, self.specular_map, defaults.white),
+                (NORMAL_UNIT, self.normal_map, defaults.flat_normal),
+                (EMISSION_UNIT, self.emission_map, defaults.white),
+                (DISPLACEMENT_UNIT, self.displacement_map, defaults.white),
+            ] {
+                gl.active_texture(glow::TEXTURE0 + unit);
+                gl.bind_texture(glow::TEXTURE_2D, Some(map.unwrap_or(default)));
+            }
+            gl.active_texture(glow::TEXTURE0);
+        }
+    }
+}
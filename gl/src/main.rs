@@ -1,14 +1,41 @@
 #![windows_subsystem = "windows"]
 #![allow(unused)]
+use std::collections::HashMap;
 use std::io::Read;
 use std::path::Path;
 use std::{f32::consts::PI, fs::File};
 
 use glfw::{Action, Key, Monitor, WindowEvent};
 use glow::*;
-
+use layout::VertexLayout;
+use reload::ShaderWatcher;
+
+pub use camera::*;
+pub use cubemap::*;
+pub use framebuffer::*;
+pub use gltf::*;
+pub use lighting::*;
+pub use model::*;
+pub use pbr::*;
+pub use post::*;
+pub use scene::*;
 pub use shaders::*;
+pub use shadows::*;
+pub mod camera;
+pub mod cubemap;
+pub mod framebuffer;
+pub mod gltf;
+pub mod json;
+pub mod lighting;
+pub mod model;
+pub mod pbr;
+pub mod post;
+pub mod scene;
 pub mod shaders;
+pub mod shadows;
+
+#[cfg(test)]
+mod tests;
 
 extern crate nalgebra_glm as glm;
 
@@ -20,41 +47,64 @@ pub fn open(path: impl AsRef<Path>) -> String {
 }
 
 pub unsafe fn program(gl: &Context, vertex: &str, fragment: &str) -> NativeProgram {
-    let program = gl.create_program().unwrap();
+    match try_program(gl, vertex, fragment) {
+        Ok(program) => program,
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// Same as `program` but returns the compiler or linker log instead of panicking.
+unsafe fn try_program(gl: &Context, vertex: &str, fragment: &str) -> Result<NativeProgram, String> {
+    let program = gl.create_program()?;
+
+    let compile = |shader_type: u32, source: &str| -> Result<NativeShader, String> {
+        let shader = gl.create_shader(shader_type)?;
+        gl.shader_source(shader, source);
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            let error = gl.get_shader_info_log(shader);
+            gl.delete_shader(shader);
+            return Err(error);
+        }
+        gl.attach_shader(program, shader);
+        Ok(shader)
+    };
 
     //Vertex shader
-    let v = gl.create_shader(glow::VERTEX_SHADER).unwrap();
-    let error = gl.get_shader_info_log(v);
-    if !error.is_empty() {
-        panic!("{}", error);
-    }
-    gl.shader_source(v, vertex);
-    gl.compile_shader(v);
-    gl.attach_shader(program, v);
+    let v = match compile(glow::VERTEX_SHADER, vertex) {
+        Ok(v) => v,
+        Err(error) => {
+            gl.delete_program(program);
+            return Err(error);
+        }
+    };
 
     //Fragment shader
-    let f = gl.create_shader(glow::FRAGMENT_SHADER).unwrap();
-    let error = gl.get_shader_info_log(f);
-    if !error.is_empty() {
-        panic!("{}", error);
-    }
-    gl.shader_source(f, fragment);
-    gl.compile_shader(f);
-    gl.attach_shader(program, f);
+    let f = match compile(glow::FRAGMENT_SHADER, fragment) {
+        Ok(f) => f,
+        Err(error) => {
+            gl.delete_shader(v);
+            gl.delete_program(program);
+            return Err(error);
+        }
+    };
 
     //Link program
     gl.link_program(program);
-    if !gl.get_program_link_status(program) {
-        panic!("{}", gl.get_program_info_log(program));
-    }
-
-    gl.use_program(Some(program));
 
     //Cleanup
     gl.delete_shader(v);
     gl.delete_shader(f);
 
-    program
+    if !gl.get_program_link_status(program) {
+        let error = gl.get_program_info_log(program);
+        gl.delete_program(program);
+        return Err(error);
+    }
+
+    gl.use_program(Some(program));
+
+    Ok(program)
 }
 
 // &vertices.align_to::<u8>().1
@@ -68,11 +118,6 @@ fn buffer(vertices: &[f32]) -> &[u8] {
     }
 }
 
-#[inline]
-fn radians(input: f32) -> f32 {
-    input * PI / 180.0
-}
-
 pub fn check_error(gl: &Context) {
     let error = unsafe { gl.get_error() };
     match error {
@@ -91,6 +136,15 @@ pub fn check_error(gl: &Context) {
 const VERTEX: &[u8] = include_bytes!("../shaders/vertex.glsl");
 const FRAGMENT: &[u8] = include_bytes!("../shaders/fragment.glsl");
 
+//The same files on disk, only read by debug builds when they change.
+const VERTEX_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/vertex.glsl");
+const FRAGMENT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/fragment.glsl");
+const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+const SHADER_PATHS: [&str; 3] = [VERTEX_PATH, FRAGMENT_PATH, PBR_FRAGMENT_PATH];
+
+//Generated image based lighting, regenerated when the HDR or the settings change.
+const IBL_CACHE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/ibl");
+
 fn main() {
     unsafe {
         use glfw::Context;
@@ -113,6 +167,8 @@ fn main() {
             .expect("Failed to create GLFW window.");
         window.set_key_polling(true);
         window.set_cursor_pos_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_scroll_polling(true);
         window.set_cursor_mode(glfw::CursorMode::Disabled);
         window.make_current();
 
@@ -122,51 +178,66 @@ fn main() {
 
         let frag = std::str::from_utf8_unchecked(FRAGMENT);
         let vert = std::str::from_utf8_unchecked(VERTEX);
-        let program = program(&gl, vert, frag);
+        let mut program = program(&gl, vert, &with_light_limit(&with_shadows(frag)));
+        let mut pbr_program = self::program(&gl, vert, &pbr_fragment(PBR_FRAGMENT));
+        #[cfg(debug_assertions)]
+        let mut watcher = ShaderWatcher::new([SHADER_DIRECTORY]);
+
+        let textures = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/textures");
+        let hdr = Path::new(textures).join("hdr/newport_loft.hdr");
+        let ibl = Ibl::load(
+            &gl,
+            &hdr,
+            IblSettings::default(),
+            Some(Path::new(IBL_CACHE)),
+        )
+        .map_err(|error| eprintln!("{}", error))
+        .ok();
+        gl.use_program(Some(program));
 
         #[rustfmt::skip]
         let vertices: &[f32] = &[
-            -0.5, -0.5, -0.5,  0.0, 0.0,
-            0.5, -0.5, -0.5,  1.0, 0.0,
-            0.5,  0.5, -0.5,  1.0, 1.0,
-            0.5,  0.5, -0.5,  1.0, 1.0,
-            -0.5,  0.5, -0.5,  0.0, 1.0,
-            -0.5, -0.5, -0.5,  0.0, 0.0,
-
-            -0.5, -0.5,  0.5,  0.0, 0.0,
-            0.5, -0.5,  0.5,  1.0, 0.0,
-            0.5,  0.5,  0.5,  1.0, 1.0,
-            0.5,  0.5,  0.5,  1.0, 1.0,
-            -0.5,  0.5,  0.5,  0.0, 1.0,
-            -0.5, -0.5,  0.5,  0.0, 0.0,
-
-            -0.5,  0.5,  0.5,  1.0, 0.0,
-            -0.5,  0.5, -0.5,  1.0, 1.0,
-            -0.5, -0.5, -0.5,  0.0, 1.0,
-            -0.5, -0.5, -0.5,  0.0, 1.0,
-            -0.5, -0.5,  0.5,  0.0, 0.0,
-            -0.5,  0.5,  0.5,  1.0, 0.0,
-
-            0.5,  0.5,  0.5,  1.0, 0.0,
-            0.5,  0.5, -0.5,  1.0, 1.0,
-            0.5, -0.5, -0.5,  0.0, 1.0,
-            0.5, -0.5, -0.5,  0.0, 1.0,
-            0.5, -0.5,  0.5,  0.0, 0.0,
-            0.5,  0.5,  0.5,  1.0, 0.0,
-
-            -0.5, -0.5, -0.5,  0.0, 1.0,
-            0.5, -0.5, -0.5,  1.0, 1.0,
-            0.5, -0.5,  0.5,  1.0, 0.0,
-            0.5, -0.5,  0.5,  1.0, 0.0,
-            -0.5, -0.5,  0.5,  0.0, 0.0,
-            -0.5, -0.5, -0.5,  0.0, 1.0,
-
-            -0.5,  0.5, -0.5,  0.0, 1.0,
-            0.5,  0.5, -0.5,  1.0, 1.0,
-            0.5,  0.5,  0.5,  1.0, 0.0,
-            0.5,  0.5,  0.5,  1.0, 0.0,
-            -0.5,  0.5,  0.5,  0.0, 0.0,
-            -0.5,  0.5, -0.5,  0.0, 1.0
+            -0.5, -0.5, -0.5,  0.0, 0.0,  0.0,  0.0, -1.0,
+            0.5, -0.5, -0.5,  1.0, 0.0,  0.0,  0.0, -1.0,
+            0.5,  0.5, -0.5,  1.0, 1.0,  0.0,  0.0, -1.0,
+            0.5,  0.5, -0.5,  1.0, 1.0,  0.0,  0.0, -1.0,
+            -0.5,  0.5, -0.5,  0.0, 1.0,  0.0,  0.0, -1.0,
+            -0.5, -0.5, -0.5,  0.0, 0.0,  0.0,  0.0, -1.0,
+
+            -0.5, -0.5,  0.5,  0.0, 0.0,  0.0,  0.0,  1.0,
+            0.5, -0.5,  0.5,  1.0, 0.0,  0.0,  0.0,  1.0,
+            0.5,  0.5,  0.5,  1.0, 1.0,  0.0,  0.0,  1.0,
+            0.5,  0.5,  0.5,  1.0, 1.0,  0.0,  0.0,  1.0,
+            -0.5,  0.5,  0.5,  0.0, 1.0,  0.0,  0.0,  1.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,  0.0,  0.0,  1.0,
+
+            -0.5,  0.5,  0.5,  1.0, 0.0,  -1.0,  0.0,  0.0,
+            -0.5,  0.5, -0.5,  1.0, 1.0,  -1.0,  0.0,  0.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,  -1.0,  0.0,  0.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,  -1.0,  0.0,  0.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,  -1.0,  0.0,  0.0,
+            -0.5,  0.5,  0.5,  1.0, 0.0,  -1.0,  0.0,  0.0,
+
+            0.5,  0.5,  0.5,  1.0, 0.0,  1.0,  0.0,  0.0,
+            0.5,  0.5, -0.5,  1.0, 1.0,  1.0,  0.0,  0.0,
+            0.5, -0.5, -0.5,  0.0, 1.0,  1.0,  0.0,  0.0,
+            0.5, -0.5, -0.5,  0.0, 1.0,  1.0,  0.0,  0.0,
+            0.5, -0.5,  0.5,  0.0, 0.0,  1.0,  0.0,  0.0,
+            0.5,  0.5,  0.5,  1.0, 0.0,  1.0,  0.0,  0.0,
+
+            -0.5, -0.5, -0.5,  0.0, 1.0,  0.0, -1.0,  0.0,
+            0.5, -0.5, -0.5,  1.0, 1.0,  0.0, -1.0,  0.0,
+            0.5, -0.5,  0.5,  1.0, 0.0,  0.0, -1.0,  0.0,
+            0.5, -0.5,  0.5,  1.0, 0.0,  0.0, -1.0,  0.0,
+            -0.5, -0.5,  0.5,  0.0, 0.0,  0.0, -1.0,  0.0,
+            -0.5, -0.5, -0.5,  0.0, 1.0,  0.0, -1.0,  0.0,
+
+            -0.5,  0.5, -0.5,  0.0, 1.0,  0.0,  1.0,  0.0,
+            0.5,  0.5, -0.5,  1.0, 1.0,  0.0,  1.0,  0.0,
+            0.5,  0.5,  0.5,  1.0, 0.0,  0.0,  1.0,  0.0,
+            0.5,  0.5,  0.5,  1.0, 0.0,  0.0,  1.0,  0.0,
+            -0.5,  0.5,  0.5,  0.0, 0.0,  0.0,  1.0,  0.0,
+            -0.5,  0.5, -0.5,  0.0, 1.0,  0.0,  1.0,  0.0
         ];
 
         #[rustfmt::skip]
@@ -183,118 +254,299 @@ fn main() {
             glm::vec3(-1.3,  1.0, -1.5)
         ];
 
-        let vao = gl.create_vertex_array().unwrap();
-        let vbo = gl.create_buffer().unwrap();
-
-        gl.bind_vertex_array(Some(vao));
-
-        gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-        gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, buffer(vertices), glow::STATIC_DRAW);
-
-        // position attribute
-        gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 5 * 4, 0);
-        gl.enable_vertex_attrib_array(0);
-
-        // texture coord attribute
-        gl.vertex_attrib_pointer_f32(1, 2, glow::FLOAT, false, 5 * 4, 3 * 4);
-        gl.enable_vertex_attrib_array(1);
-
-        //First texture
-        let bytes = include_bytes!("../resources/textures/container.jpg");
-        let im = image::load_from_memory(bytes).unwrap();
-        let texture1 = gl.create_texture().unwrap();
-
-        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
-        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::REPEAT as i32);
-        gl.tex_parameter_i32(
-            glow::TEXTURE_2D,
-            glow::TEXTURE_MIN_FILTER,
-            glow::LINEAR as i32,
-        );
-        gl.tex_parameter_i32(
-            glow::TEXTURE_2D,
-            glow::TEXTURE_MAG_FILTER,
-            glow::LINEAR as i32,
-        );
-
-        gl.tex_image_2d(
-            glow::TEXTURE_2D,
-            0,
-            glow::RGB as i32,
-            im.width() as i32,
-            im.height() as i32,
-            0,
-            glow::RGB,
-            glow::UNSIGNED_BYTE,
-            Some(im.as_bytes()),
-        );
-
-        gl.active_texture(glow::TEXTURE1);
-        gl.bind_texture(glow::TEXTURE_2D, Some(texture1));
-
-        //Second texture
-        let bytes = include_bytes!("../resources/textures/awesomeface.png");
-        let im = image::load_from_memory(bytes).unwrap().flipv();
+        //Position, uv and normal, the tangents are generated for normal and parallax mapping.
+        let mut cube_mesh = Mesh {
+            name: "Cube".to_string(),
+            vertices: vertices
+                .chunks_exact(8)
+                .map(|v| ModelVertex {
+                    position: [v[0], v[1], v[2]],
+                    uv: [v[3], v[4]],
+                    normal: [v[5], v[6], v[7]],
+                    ..Default::default()
+                })
+                .collect(),
+            indices: (0..36).collect(),
+            submeshes: vec![Submesh {
+                material: None,
+                indices: 0..36,
+            }],
+            ..Default::default()
+        };
+        cube_mesh.generate_tangents();
+        let cube = GpuMesh::new(&gl, &cube_mesh);
+
+        let lights_buffer = LightBuffer::new(&gl);
+        let defaults = DefaultMaps::new(&gl);
+        let container = PhongMaterial {
+            diffuse_map: load_texture(&gl, &Path::new(textures).join("container2.png")).ok(),
+            specular_map: load_texture(&gl, &Path::new(textures).join("container2_specular.png"))
+                .ok(),
+            specular: glm::vec3(1.0, 1.0, 1.0),
+            ..Default::default()
+        };
+        //Every third cube is a mirror and every third one after that is glass.
+        let cube_materials = [
+            container.clone(),
+            PhongMaterial {
+                environment: EnvironmentMapping::Reflect { amount: 0.8 },
+                ..container.clone()
+            },
+            PhongMaterial {
+                environment: EnvironmentMapping::Refract {
+                    ratio: 1.0 / 1.52,
+                    amount: 0.9,
+                },
+                ..Default::default()
+            },
+        ];
+        //P cycles the parallax mode of these two.
+        let texture = |name: &str| load_texture(&gl, &Path::new(textures).join(name)).ok();
+        let mut parallax_materials = [
+            PhongMaterial {
+                diffuse_map: texture("bricks2.jpg"),
+                normal_map: texture("bricks2_normal.jpg"),
+                displacement_map: texture("bricks2_disp.jpg"),
+                specular: glm::vec3(0.2, 0.2, 0.2),
+                ..Default::default()
+            },
+            PhongMaterial {
+                diffuse_map: texture("wood.png"),
+                normal_map: texture("toy_box_normal.png"),
+                displacement_map: texture("toy_box_disp.png"),
+                specular: glm::vec3(0.2, 0.2, 0.2),
+                ..Default::default()
+            },
+        ];
 
-        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
-        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::REPEAT as i32);
-        gl.tex_parameter_i32(
-            glow::TEXTURE_2D,
-            glow::TEXTURE_MIN_FILTER,
-            glow::LINEAR as i32,
-        );
-        gl.tex_parameter_i32(
-            glow::TEXTURE_2D,
-            glow::TEXTURE_MAG_FILTER,
-            glow::LINEAR as i32,
-        );
+        //Loaded models, mesh `i + 1` in the scene draws `models[i]`.
+        let objects = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/objects");
+        let mut texture_cache = HashMap::new();
+        let mut models: Vec<(Vec<GpuMesh>, Vec<PhongMaterial>, Option<Aabb>)> = Vec::new();
+        for path in [
+            "planet/planet.obj",
+            "cyborg/cyborg.obj",
+            "nanosuit/nanosuit.obj",
+        ] {
+            let model = load_obj(Path::new(objects).join(path)).unwrap();
+            let meshes = model.meshes.iter().map(|m| GpuMesh::new(&gl, m)).collect();
+            let materials = model
+                .materials
+                .iter()
+                .map(|m| PhongMaterial::from_mtl(&gl, m, &mut texture_cache))
+                .collect();
+            let bounds = model
+                .meshes
+                .iter()
+                .filter_map(Aabb::from_mesh)
+                .reduce(|a, b| a.union(&b));
+            models.push((meshes, materials, bounds));
+        }
 
-        gl.tex_image_2d(
-            glow::TEXTURE_2D,
-            0,
-            glow::RGB as i32,
-            im.width() as i32,
-            im.height() as i32,
-            0,
-            glow::RGBA,
-            glow::UNSIGNED_BYTE,
-            Some(im.as_bytes()),
+        //Mesh `SPHERES + i` draws the sphere with `pbr_materials[i]`.
+        let sphere = GpuMesh::new(&gl, &Mesh::sphere(64, 32));
+        let pbr_materials: Vec<GpuPbrMaterial> = [
+            ("gold", None),
+            ("grass", Some(glm::vec3(0.25, 0.45, 0.1))),
+            ("plastic", None),
+            ("rusted_iron", Some(glm::vec3(0.45, 0.25, 0.15))),
+            ("wall", Some(glm::vec3(0.6, 0.6, 0.6))),
+        ]
+        .into_iter()
+        .map(|(name, albedo)| {
+            let dir = Path::new(textures).join("pbr").join(name);
+            let mut material = GpuPbrMaterial::load_dir(&gl, &dir);
+            //Some of the sets don't have an albedo map.
+            if let Some(albedo) = albedo {
+                material.albedo = albedo;
+            }
+            material
+        })
+        .collect();
+        gl.bind_texture(glow::TEXTURE_2D, None);
+
+        //Mesh indices used by the scene.
+        const CUBE: usize = 0;
+        const PLANET: usize = 1;
+        const CYBORG: usize = 2;
+        const NANOSUIT: usize = 3;
+        const SPHERES: usize = 4;
+
+        let mut scene = Scene::new();
+        let cubes = scene.add("Cubes", Transform::default(), None);
+        let cube_bounds = Aabb::new(glm::vec3(-0.5, -0.5, -0.5), glm::vec3(0.5, 0.5, 0.5));
+        let cube_nodes: Vec<usize> = cube_positions
+            .iter()
+            .enumerate()
+            .map(|(i, &position)| {
+                let transform = Transform::from_translation(position);
+                let name = format!("Cube {}", i);
+                scene.add_mesh(&name, transform, Some(cubes), CUBE, Some(cube_bounds))
+            })
+            .collect();
+        let parallax_nodes: Vec<usize> = [
+            ("Bricks", glm::vec3(-3.0, 0.0, -1.0)),
+            ("Toy box", glm::vec3(3.0, 0.0, -1.0)),
+        ]
+        .into_iter()
+        .map(|(name, position)| {
+            let transform = Transform::from_translation(position);
+            scene.add_mesh(name, transform, None, CUBE, Some(cube_bounds))
+        })
+        .collect();
+        for (name, mesh, transform) in [
+            (
+                "Planet",
+                PLANET,
+                Transform::from_translation(glm::vec3(0.0, 0.0, -30.0)),
+            ),
+            (
+                "Cyborg",
+                CYBORG,
+                Transform::from_translation(glm::vec3(4.0, -1.5, -6.0)),
+            ),
+            (
+                "Nanosuit",
+                NANOSUIT,
+                Transform {
+                    translation: glm::vec3(-4.0, -1.5, -6.0),
+                    scale: glm::vec3(0.2, 0.2, 0.2),
+                    ..Default::default()
+                },
+            ),
+        ] {
+            scene.add_mesh(name, transform, None, mesh, models[mesh - 1].2);
+        }
+        let spheres = scene.add(
+            "Spheres",
+            Transform::from_translation(glm::vec3(0.0, 4.0, -8.0)),
+            None,
         );
+        let sphere_bounds = Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0));
+        for i in 0..pbr_materials.len() {
+            let x = (i as f32 - (pbr_materials.len() - 1) as f32 / 2.0) * 2.5;
+            let transform = Transform::from_translation(glm::vec3(x, 0.0, 0.0));
+            let name = format!("Sphere {}", i);
+            scene.add_mesh(
+                &name,
+                transform,
+                Some(spheres),
+                SPHERES + i,
+                Some(sphere_bounds),
+            );
+        }
 
-        gl.active_texture(glow::TEXTURE0);
-
-        //Texture0 is always bound?
-        // let ul1 = gl.get_uniform_location(program, "texture1").unwrap();
-        // gl.uniform_1_i32(Some(&ul1), 0);
-
-        let ul2 = gl.get_uniform_location(program, "texture2").unwrap();
-        gl.uniform_1_i32(Some(&ul2), 1);
-
-        let model_location = gl.get_uniform_location(program, "model").unwrap();
-        let view_location = gl.get_uniform_location(program, "view").unwrap();
-
-        let projection_location = gl.get_uniform_location(program, "projection").unwrap();
-        let projection = glm::perspective(width as f32 / height as f32, 45.0, 0.1, 100.0);
-        gl.uniform_matrix_4_f32_slice(Some(&projection_location), false, projection.as_slice());
-
-        gl.clear_color(0.1, 0.2, 0.3, 1.0);
+        let mut camera = Camera::perspective(45f32.to_radians(), 0.1, 100.0);
+        camera.position = glm::vec3(0.0, 0.0, 3.0);
+        //Follows the framebuffer, uploaded every frame with the view.
+        let mut aspect = width / height;
+        let mut projection = camera.projection(aspect);
+
+        //Needs to run again whenever the program is reloaded.
+        let uniforms = |program: NativeProgram| {
+            lights_buffer.bind_program(&gl, program);
+
+            //`None` if a reloaded shader stopped using them.
+            let model_location = gl.get_uniform_location(program, "model");
+            let view_location = gl.get_uniform_location(program, "view");
+            let camera_location = gl.get_uniform_location(program, "camera_position");
+            let projection_location = gl.get_uniform_location(program, "projection");
+
+            (
+                model_location,
+                view_location,
+                camera_location,
+                projection_location,
+            )
+        };
+        let pbr_uniforms = |program: NativeProgram| {
+            let locations = uniforms(program);
+            bind_pbr_program(&gl, program);
+            if let Some(ibl) = &ibl {
+                ibl.bind(&gl, program);
+            }
+            locations
+        };
+        let (mut model_location, mut view_location, mut camera_location, mut projection_location) =
+            uniforms(program);
+        let (
+            mut pbr_model_location,
+            mut pbr_view_location,
+            mut pbr_camera_location,
+            mut pbr_projection_location,
+        ) = pbr_uniforms(pbr_program);
+
+        //A dim sun, four lamps between the cubes and a flashlight that follows the camera.
+        let mut lights = Lights::new(glm::vec3(0.05, 0.05, 0.05));
+        let white = glm::vec3(1.0, 1.0, 1.0);
+        let sun = lights
+            .push(Light::Directional {
+                direction: glm::vec3(-0.2, -1.0, -0.3),
+                color: white,
+                intensity: 0.4,
+            })
+            .unwrap();
+        for (position, color) in [
+            (glm::vec3(0.7, 0.2, 2.0), white),
+            (glm::vec3(2.3, -3.3, -4.0), glm::vec3(1.0, 0.6, 0.2)),
+            (glm::vec3(-4.0, 2.0, -12.0), glm::vec3(0.2, 0.4, 1.0)),
+            (glm::vec3(0.0, 0.0, -3.0), white),
+        ] {
+            lights
+                .push(Light::Point {
+                    position,
+                    color,
+                    intensity: 1.0,
+                    attenuation: Attenuation::range(50.0),
+                })
+                .unwrap();
+        }
+        let flashlight = lights
+            .push(Light::Spot {
+                position: camera.position,
+                direction: camera.front,
+                color: white,
+                intensity: 1.0,
+                attenuation: Attenuation::range(32.0),
+                inner: 12.5f32.to_radians(),
+                outer: 17.5f32.to_radians(),
+            })
+            .unwrap();
+
+        //The sun and the white lamp next to where the camera starts cast shadows.
+        //V cycles through the shadow maps in the corner, C tints the cascades.
+        let mut shadows = Shadows::new(&gl, ShadowSettings::default()).unwrap();
+        shadows.directional = Some(sun);
+        shadows.point = Some(sun + 1);
+        let shadow_debug = ShadowDebug::new(&gl).unwrap();
+        let mut shadow_view = None;
+
+        //B shows the HDR environment lighting the spheres instead of the sky.
+        let sky = Cubemap::from_faces(&gl, &face_paths(&Path::new(textures).join("skybox"), "jpg"))
+            .unwrap();
+        let mut skybox = Skybox::new(&gl).unwrap();
+        let mut show_environment = false;
+
+        //Everything above renders in linear HDR. 1 to 7 toggle the passes, T switches the
+        //tone mapper and - and = change the exposure.
+        let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+        let mut post = PostChain::new(
+            &gl,
+            framebuffer_width as u32,
+            framebuffer_height as u32,
+            default_passes(),
+        )
+        .unwrap();
+        gl.use_program(Some(program));
 
-        let mut camera_pos = glm::vec3(0.0, 0.0, 3.0);
-        let mut camera_front = glm::vec3(0.0, 0.0, -1.0);
-        let camera_up = glm::vec3(0.0, 1.0, 0.0);
+        //Tab switches between flying and orbiting around the origin.
+        let mut fly = FlyController::new(&camera);
+        let mut orbit = OrbitController::from_camera(&camera, 3.0);
+        let mut orbiting = false;
 
         let mut delta_time: f32;
         let mut last_frame: f32 = 0.0;
 
-        //Mouse
-        let mut first_mouse = true;
-        // yaw is initialized to -90.0 degrees since a yaw of 0.0 results in a direction vector pointing to the right so we initially rotate a bit to the left.
-        let mut yaw: f32 = -90.0;
-        let mut pitch: f32 = 0.0;
-        let mut last_x: f32 = 800.0 / 2.0;
-        let mut last_y: f32 = 600.0 / 2.0;
-        let sensitivity: f32 = 0.1;
-
         // let mut tb = TriangleBuffer::new(&gl);
         // let (r, g, b) = (1.0, 1.0, 1.0);
         // for x in 1..2 {
@@ -327,22 +579,6 @@ fn main() {
             delta_time = current_frame - last_frame;
             last_frame = current_frame;
 
-            let camera_speed = 5.0 * delta_time;
-
-            //Camera Input
-            if window.get_key(Key::W) == Action::Press {
-                camera_pos += camera_speed * camera_front;
-            }
-            if window.get_key(Key::S) == Action::Press {
-                camera_pos -= camera_speed * camera_front;
-            }
-            if window.get_key(Key::A) == Action::Press {
-                camera_pos -= glm::normalize(&glm::cross(&camera_front, &camera_up)) * camera_speed;
-            }
-            if window.get_key(Key::D) == Action::Press {
-                camera_pos += glm::normalize(&glm::cross(&camera_front, &camera_up)) * camera_speed;
-            }
-
             //Events
             for (_, event) in glfw::flush_messages(&events) {
                 match event {
@@ -350,49 +586,145 @@ fn main() {
                         window.set_should_close(true)
                     }
                     WindowEvent::Close => window.set_should_close(true),
-                    WindowEvent::CursorPos(x, y) => {
-                        let (x, y) = (x as f32, y as f32);
-                        if first_mouse {
-                            last_x = x;
-                            last_y = y;
-                            first_mouse = false;
+                    WindowEvent::Key(Key::B, _, Action::Press, _) => {
+                        show_environment = !show_environment
+                    }
+                    WindowEvent::Key(Key::V, _, Action::Press, _) => {
+                        shadow_view = ShadowView::cycle(shadow_view, shadows.cascades.len())
+                    }
+                    WindowEvent::Key(Key::C, _, Action::Press, _) => {
+                        shadows.tint_cascades = !shadows.tint_cascades
+                    }
+                    WindowEvent::Key(Key::P, _, Action::Press, _) => {
+                        for material in &mut parallax_materials {
+                            material.parallax.mode = material.parallax.mode.next();
                         }
-                        let mut xoffset = x - last_x;
-                        let mut yoffset = last_y - y;
-                        // reversed since y-coordinates go from bottom to top
-                        last_x = x;
-                        last_y = y;
-
-                        xoffset *= sensitivity;
-                        yoffset *= sensitivity;
-
-                        yaw += xoffset;
-                        pitch += yoffset;
-
-                        if pitch > 89.9 {
-                            pitch = 89.9;
+                        println!("Parallax: {:?}", parallax_materials[0].parallax.mode);
+                    }
+                    WindowEvent::Key(
+                        key @ (Key::Num1
+                        | Key::Num2
+                        | Key::Num3
+                        | Key::Num4
+                        | Key::Num5
+                        | Key::Num6
+                        | Key::Num7),
+                        _,
+                        Action::Press,
+                        _,
+                    ) => {
+                        let index = key as usize - Key::Num1 as usize;
+                        if let Some(pass) = post.passes.get_mut(index) {
+                            pass.enabled = !pass.enabled;
+                            println!("{}: {}", pass.effect.name(), pass.enabled);
                         }
-                        if pitch < -89.9 {
-                            pitch = -89.9;
+                    }
+                    WindowEvent::Key(Key::T, _, Action::Press, _) => {
+                        for pass in &mut post.passes {
+                            if let Effect::ToneMap(tone_mapper) = &mut pass.effect {
+                                *tone_mapper = tone_mapper.next();
+                                println!("Tone mapper: {:?}", tone_mapper);
+                            }
                         }
+                    }
+                    WindowEvent::Key(
+                        key @ (Key::Minus | Key::Equal),
+                        _,
+                        Action::Press | Action::Repeat,
+                        _,
+                    ) => {
+                        let factor = if key == Key::Equal { 1.25 } else { 0.8 };
+                        for pass in &mut post.passes {
+                            if let Effect::Exposure(exposure) = &mut pass.effect {
+                                *exposure *= factor;
+                                println!("Exposure: {:.2}", exposure);
+                            }
+                        }
+                    }
+                    WindowEvent::Key(Key::Tab, _, Action::Press, _) => {
+                        orbiting = !orbiting;
+                        window.set_cursor_mode(if orbiting {
+                            glfw::CursorMode::Normal
+                        } else {
+                            glfw::CursorMode::Disabled
+                        });
+                        //Pick up where the other controller left off.
+                        if orbiting {
+                            orbit = OrbitController::from_camera(&camera, 3.0);
+                            orbit.smoothing = 0.05;
+                        } else {
+                            fly = FlyController::new(&camera);
+                        }
+                    }
+                    _ if orbiting => orbit.event(&event),
+                    _ => fly.event(&event),
+                }
+            }
+            if orbiting {
+                orbit.update(&mut camera, delta_time);
+            } else {
+                fly.update(&mut camera, delta_time);
+            }
 
-                        let mut front = glm::vec3(0.0, 0.0, 0.0);
-                        // front.x = f32::cos(yaw) * f32::cos(pitch);
-                        // front.y = f32::sin(pitch);
-                        // front.z = f32::sin(yaw) * f32::cos(pitch);
-
-                        front.x = f32::cos(radians(yaw)) * f32::cos(radians(pitch));
-                        front.y = f32::sin(radians(pitch));
-                        front.z = f32::sin(radians(yaw)) * f32::cos(radians(pitch));
-
-                        camera_front = glm::normalize(&front);
+            //Shader hot reload
+            #[cfg(debug_assertions)]
+            if watcher
+                .poll()
+                .iter()
+                .any(|changed| SHADER_PATHS.iter().any(|path| changed == Path::new(path)))
+            {
+                let read = |path: &str| std::fs::read_to_string(path).map_err(|e| e.to_string());
+                let reloaded = read(VERTEX_PATH).and_then(|v| {
+                    let phong = read(FRAGMENT_PATH)?;
+                    let phong = try_program(&gl, &v, &with_light_limit(&with_shadows(&phong)))?;
+                    match read(PBR_FRAGMENT_PATH)
+                        .and_then(|f| try_program(&gl, &v, &pbr_fragment(&f)))
+                    {
+                        Ok(pbr) => Ok((phong, pbr)),
+                        Err(error) => {
+                            gl.delete_program(phong);
+                            Err(error)
+                        }
+                    }
+                });
+                match reloaded {
+                    Ok((new, new_pbr)) => {
+                        gl.delete_program(program);
+                        gl.delete_program(pbr_program);
+                        (program, pbr_program) = (new, new_pbr);
+                        (
+                            model_location,
+                            view_location,
+                            camera_location,
+                            projection_location,
+                        ) = uniforms(program);
+                        (
+                            pbr_model_location,
+                            pbr_view_location,
+                            pbr_camera_location,
+                            pbr_projection_location,
+                        ) = pbr_uniforms(pbr_program);
+                        println!("Reloaded shaders");
+                    }
+                    //Keep drawing with the old program.
+                    Err(error) => {
+                        gl.use_program(Some(program));
+                        eprintln!("{}", error);
                     }
-                    _ => {}
                 }
             }
 
             //Rendering
-            gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+            let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+            let size = (framebuffer_width as u32, framebuffer_height as u32);
+            //Minimized windows have no framebuffer.
+            if size != (post.scene.width, post.scene.height) && size.0 > 0 && size.1 > 0 {
+                gl.viewport(0, 0, framebuffer_width, framebuffer_height);
+                post.resize(&gl, size.0, size.1).unwrap();
+                aspect = size.0 as f32 / size.1 as f32;
+                projection = camera.projection(aspect);
+            }
+            post.begin(&gl);
 
             // test(&gl);
             // tb.draw(&gl);
@@ -405,28 +737,148 @@ fn main() {
 
             // draw_line(&gl, -0.3, 0.0, 0.3, 0.3, color(0.1, 0.1, 0.1));
 
-            'cubes: {
-                // break 'cubes;
-
-                //Camera/View transformation
-                let view = glm::look_at(&camera_pos, &(camera_pos + camera_front), &camera_up);
-                gl.uniform_matrix_4_f32_slice(Some(&view_location), false, view.as_slice());
-
-                for (i, cube) in cube_positions.iter().enumerate() {
-                    // calculate the model matrix for each object and pass it to shader before drawing
-                    let mut model = glm::translate(&glm::identity(), &cube);
-                    model = glm::rotate(&model, 20.0 * i as f32, &glm::vec3(1.0, 0.3, 0.5));
-                    model = glm::rotate(
-                        &model,
-                        (i as f32 + 1.0) * glfw.get_time() as f32 / 4.0,
-                        &glm::vec3(0.5, 1.0, 0.0),
-                    );
-                    gl.uniform_matrix_4_f32_slice(Some(&model_location), false, model.as_slice());
+            //Camera/View transformation
+            let view = camera.view();
+            let position = camera.position;
+            for (shader, view_location, camera_location, projection_location) in [
+                (
+                    program,
+                    &view_location,
+                    &camera_location,
+                    &projection_location,
+                ),
+                (
+                    pbr_program,
+                    &pbr_view_location,
+                    &pbr_camera_location,
+                    &pbr_projection_location,
+                ),
+            ] {
+                gl.use_program(Some(shader));
+                gl.uniform_matrix_4_f32_slice(view_location.as_ref(), false, view.as_slice());
+                gl.uniform_matrix_4_f32_slice(
+                    projection_location.as_ref(),
+                    false,
+                    projection.as_slice(),
+                );
+                gl.uniform_3_f32(camera_location.as_ref(), position.x, position.y, position.z);
+            }
+
+            if let Light::Spot {
+                position,
+                direction,
+                ..
+            } = &mut lights.lights[flashlight]
+            {
+                *position = camera.position;
+                *direction = camera.front;
+            }
+            if let Err(error) = lights_buffer.upload(&gl, &lights) {
+                eprintln!("{}", error);
+            }
 
-                    gl.draw_arrays(glow::TRIANGLES, 0, 36);
+            for (i, &node) in cube_nodes.iter().enumerate() {
+                let i = i as f32;
+                scene.transform_mut(node).rotation =
+                    glm::quat_angle_axis(20.0 * i, &glm::vec3(1.0, 0.3, 0.5))
+                        * glm::quat_angle_axis(
+                            (i + 1.0) * glfw.get_time() as f32 / 4.0,
+                            &glm::vec3(0.5, 1.0, 0.0),
+                        );
+            }
+            scene.update();
+
+            //Only geometry, every map uses the same depth only program.
+            shadows.render(&gl, &lights, &camera, aspect, |model, light| {
+                for id in scene.visible(light) {
+                    let node = &scene.nodes[id];
+                    gl.uniform_matrix_4_f32_slice(Some(model), false, node.world().as_slice());
+                    match node.mesh {
+                        Some(CUBE) => cube.draw(&gl, |_| {}),
+                        Some(mesh) if mesh < SPHERES => {
+                            for mesh in &models[mesh - 1].0 {
+                                mesh.draw(&gl, |_| {});
+                            }
+                        }
+                        Some(_) => sphere.draw(&gl, |_| {}),
+                        None => {}
+                    }
+                }
+            });
+            shadows.bind(&gl, program);
+            shadows.bind(&gl, pbr_program);
+
+            //Blinn-Phong first, then the PBR spheres.
+            let visible = scene.visible(&(projection * view));
+            gl.use_program(Some(program));
+            sky.bind(&gl, ENVIRONMENT_UNIT);
+            gl.active_texture(glow::TEXTURE0);
+            for &id in &visible {
+                let node = &scene.nodes[id];
+                gl.uniform_matrix_4_f32_slice(
+                    model_location.as_ref(),
+                    false,
+                    node.world().as_slice(),
+                );
+
+                match node.mesh {
+                    Some(CUBE) => {
+                        let material = match parallax_nodes.iter().position(|&n| n == id) {
+                            Some(i) => &parallax_materials[i],
+                            None => {
+                                let i = cube_nodes.iter().position(|&n| n == id).unwrap_or(0);
+                                &cube_materials[i % 3]
+                            }
+                        };
+                        cube.draw(&gl, |_| material.bind(&gl, program, &defaults));
+                    }
+                    Some(mesh) if mesh < SPHERES => {
+                        let (meshes, materials, _) = &models[mesh - 1];
+                        for mesh in meshes {
+                            mesh.draw(&gl, |submesh| {
+                                let material = submesh.material.map(|i| &materials[i]);
+                                material
+                                    .unwrap_or(&PhongMaterial::default())
+                                    .bind(&gl, program, &defaults);
+                            });
+                        }
+                    }
+                    _ => {}
                 }
             }
 
+            gl.use_program(Some(pbr_program));
+            for &id in &visible {
+                let node = &scene.nodes[id];
+                if let Some(mesh) = node.mesh.filter(|&mesh| mesh >= SPHERES) {
+                    gl.uniform_matrix_4_f32_slice(
+                        pbr_model_location.as_ref(),
+                        false,
+                        node.world().as_slice(),
+                    );
+                    pbr_materials[mesh - SPHERES].bind(&gl, pbr_program, &defaults);
+                    sphere.draw(&gl, |_| {});
+                }
+            }
+
+            //Last, so only the pixels nothing covered are shaded.
+            let sky_map = match &ibl {
+                Some(ibl) if show_environment => &ibl.environment,
+                _ => &sky,
+            };
+            skybox.draw(&gl, sky_map, &view, &projection);
+            post.end(&gl);
+
+            if let Some(shadow_view) = shadow_view {
+                let (_, height) = window.get_framebuffer_size();
+                let size = height / 3;
+                let width = match shadow_view {
+                    ShadowView::Cascade(_) => size,
+                    ShadowView::Point => size * 2,
+                };
+                shadow_debug.draw(&gl, &shadows, shadow_view, [10, 10, width, size]);
+            }
+
             window.swap_buffers();
             glfw.poll_events();
         }
@@ -0,0 +1,717 @@
+//! Wavefront OBJ and MTL loading.
+//!
+//! http://paulbourke.net/dataformats/obj/
+//! http://paulbourke.net/dataformats/mtl/
+use crate::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// `position`, `uv`, `normal` and `tangent` are at locations 0, 1, 2 and 3.
+/// The cube in `main` uses the same layout so models can share its shaders.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, VertexLayout)]
+pub struct ModelVertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+    pub normal: [f32; 3],
+    /// Along +u, `w` is the handedness: `bitangent = w * cross(normal, tangent)`.
+    /// See `generate_tangents`.
+    pub tangent: [f32; 4],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// `Ka`
+    pub ambient: [f32; 3],
+    /// `Kd`
+    pub diffuse: [f32; 3],
+    /// `Ks`
+    pub specular: [f32; 3],
+    /// `Ke`
+    pub emissive: [f32; 3],
+    /// `Ns`, the specular exponent.
+    pub shininess: f32,
+    /// `d` or `1 - Tr`.
+    pub opacity: f32,
+    /// `Ni`
+    pub optical_density: f32,
+    /// `illum`
+    pub illumination: u32,
+    /// Texture paths are relative to the `.mtl` file they came from.
+    pub ambient_map: Option<PathBuf>,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+    pub shininess_map: Option<PathBuf>,
+    pub alpha_map: Option<PathBuf>,
+    /// `map_Bump`, `bump` or `norm`. Usually a tangent space normal map.
+    pub normal_map: Option<PathBuf>,
+    /// `disp`
+    pub displacement_map: Option<PathBuf>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 1.0,
+            opacity: 1.0,
+            optical_density: 1.0,
+            illumination: 2,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            shininess_map: None,
+            alpha_map: None,
+            normal_map: None,
+            displacement_map: None,
+        }
+    }
+}
+
+/// Range of `Mesh::indices` drawn with one material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submesh {
+    /// Index into `Model::materials` or `Gltf::materials`.
+    /// `None` before the first `usemtl` or for glTF primitives without a material.
+    pub material: Option<usize>,
+    pub indices: Range<u32>,
+}
+
+/// One OBJ object (`o`), every vertex is unique.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mesh {
+    pub name: String,
+    /// Group names (`g`) used inside of the object.
+    pub groups: Vec<String>,
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    /// One per material, in the order they were first used.
+    pub submeshes: Vec<Submesh>,
+}
+
+impl Mesh {
+    /// UV sphere of radius 1 with one submesh and no material.
+    /// The seam and poles repeat vertices so every vertex has its own UV.
+    pub fn sphere(segments: u32, rings: u32) -> Self {
+        let mut vertices = Vec::new();
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let theta = v * PI;
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let phi = u * 2.0 * PI;
+                //From the south pole up, so v matches OBJ's bottom left origin.
+                let normal = [
+                    phi.cos() * theta.sin(),
+                    -theta.cos(),
+                    -phi.sin() * theta.sin(),
+                ];
+                vertices.push(ModelVertex {
+                    position: normal,
+                    uv: [u, v],
+                    normal,
+                    ..Default::default()
+                });
+            }
+        }
+
+        let mut indices = Vec::new();
+        let row = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * row + segment;
+                let b = a + row;
+                indices.extend([a, a + 1, b + 1, a, b + 1, b]);
+            }
+        }
+
+        let mut sphere = Self {
+            name: "Sphere".to_string(),
+            groups: Vec::new(),
+            vertices,
+            submeshes: vec![Submesh {
+                material: None,
+                indices: 0..indices.len() as u32,
+            }],
+            indices,
+        };
+        sphere.generate_tangents();
+        sphere
+    }
+
+    /// Replaces every tangent, see `generate_tangents`. Needs the normals to be set.
+    pub fn generate_tangents(&mut self) {
+        generate_tangents(&mut self.vertices, &mut self.indices);
+    }
+}
+
+/// Per vertex tangents from the UVs of the triangles in `indices`, in the style of MikkTSpace.
+/// Not checked against its output, so normal maps baked by other tools can be slightly off.
+///
+/// Each triangle's tangent and bitangent are projected onto the plane of the vertex normal and
+/// weighted by the angle of the triangle at that corner. Vertices shared by triangles that
+/// disagree on handedness, like on the seam of mirrored UVs, are split in two and `indices`
+/// is updated. Vertices without usable UVs get any tangent perpendicular to the normal.
+pub fn generate_tangents(vertices: &mut Vec<ModelVertex>, indices: &mut [u32]) {
+    //Sums of the tangent and bitangent for each vertex and handedness.
+    let mut sums: HashMap<(u32, bool), (glm::Vec3, glm::Vec3)> = HashMap::new();
+    let mut flipped = vec![false; indices.len()];
+
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let vertex = |i: usize| &vertices[triangle[i] as usize];
+        let p = |i: usize| glm::Vec3::from(vertex(i).position);
+        let uv = |i: usize| glm::Vec2::from(vertex(i).uv);
+        let (e1, e2) = (p(1) - p(0), p(2) - p(0));
+        let (d1, d2) = (uv(1) - uv(0), uv(2) - uv(0));
+        let area = d1.x * d2.y - d2.x * d1.y;
+        if area.abs() < f32::EPSILON {
+            continue;
+        }
+        let tangent = (e1 * d2.y - e2 * d1.y) / area;
+        let bitangent = (e2 * d1.x - e1 * d2.x) / area;
+        //Mirrored in UV space.
+        let mirrored = area < 0.0;
+
+        for corner in 0..3 {
+            let index = triangle[corner];
+            let normal = glm::Vec3::from(vertex(corner).normal);
+            let project = |v: glm::Vec3| (v - normal * normal.dot(&v)).try_normalize(0.0);
+            let (Some(tangent), Some(bitangent)) = (project(tangent), project(bitangent)) else {
+                continue;
+            };
+            let a = p((corner + 1) % 3) - p(corner);
+            let b = p((corner + 2) % 3) - p(corner);
+            let (Some(a), Some(b)) = (a.try_normalize(0.0), b.try_normalize(0.0)) else {
+                continue;
+            };
+            let angle = a.dot(&b).clamp(-1.0, 1.0).acos();
+
+            let sum = sums
+                .entry((index, mirrored))
+                .or_insert((glm::Vec3::zeros(), glm::Vec3::zeros()));
+            sum.0 += tangent * angle;
+            sum.1 += bitangent * angle;
+            flipped[t * 3 + corner] = mirrored;
+        }
+    }
+
+    //Mirrored corners of vertices that are also used unmirrored move to a copy.
+    let mut copies: HashMap<u32, u32> = HashMap::new();
+    for (index, mirrored) in indices.iter_mut().zip(flipped) {
+        let original = *index;
+        if mirrored && sums.contains_key(&(original, false)) {
+            *index = *copies.entry(original).or_insert_with(|| {
+                vertices.push(vertices[original as usize]);
+                (vertices.len() - 1) as u32
+            });
+        }
+    }
+
+    for vertex in vertices.iter_mut() {
+        let t = perpendicular(&glm::Vec3::from(vertex.normal));
+        vertex.tangent = [t.x, t.y, t.z, 1.0];
+    }
+    for (&(index, mirrored), &(tangent, bitangent)) in &sums {
+        let index = match copies.get(&index) {
+            Some(&copy) if mirrored => copy,
+            _ => index,
+        };
+        let vertex = &mut vertices[index as usize];
+        let normal = glm::Vec3::from(vertex.normal);
+        let t = (tangent - normal * normal.dot(&tangent))
+            .try_normalize(0.0)
+            .unwrap_or_else(|| perpendicular(&normal));
+        let w = if normal.cross(&t).dot(&bitangent) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = [t.x, t.y, t.z, w];
+    }
+}
+
+/// Any unit vector perpendicular to `normal`, +X if it's zero.
+fn perpendicular(normal: &glm::Vec3) -> glm::Vec3 {
+    let axis = if normal.x.abs() < 0.9 {
+        glm::vec3(1.0, 0.0, 0.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    };
+    (axis - normal * normal.dot(&axis))
+        .try_normalize(0.0)
+        .unwrap_or(glm::vec3(1.0, 0.0, 0.0))
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    /// Every material used by a `usemtl`, filled in from the `mtllib` files by `load_obj`.
+    pub materials: Vec<Material>,
+    /// `mtllib` paths as written in the file.
+    pub material_libraries: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelError {
+    Io(PathBuf, String),
+    Parse {
+        file: String,
+        line: usize,
+        message: String,
+    },
+    /// Errors in formats without lines, like glTF.
+    Format {
+        file: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            ModelError::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+            ModelError::Format { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+fn read(path: &Path) -> Result<String, ModelError> {
+    std::fs::read_to_string(path).map_err(|e| ModelError::Io(path.to_path_buf(), e.to_string()))
+}
+
+/// Parses the OBJ and every material library it references.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Model, ModelError> {
+    let path = path.as_ref();
+    let mut model = parse_obj(&read(path)?, &path.display().to_string())?;
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    for library in model.material_libraries.clone() {
+        let path = directory.join(&library);
+        let materials = parse_mtl(
+            &read(&path)?,
+            path.parent().unwrap_or(Path::new("")),
+            &path.display().to_string(),
+        )?;
+        for material in materials {
+            if let Some(used) = model.materials.iter_mut().find(|m| m.name == material.name) {
+                *used = material;
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+/// `name` is only used for errors.
+/// Materials are left as `Material::new` placeholders, see `load_obj`.
+pub fn parse_obj(source: &str, name: &str) -> Result<Model, ModelError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut model = Model::default();
+    let mut builder = MeshBuilder::default();
+    let mut material = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| ModelError::Parse {
+            file: name.to_string(),
+            line: i + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let rest = line[keyword.len()..].trim();
+
+        match keyword {
+            "v" => positions.push(floats(words, error)?),
+            "vt" => {
+                //The third component is optional and unused.
+                let uv: Vec<&str> = words.take(2).collect();
+                let u = float(uv.first().copied(), error)?;
+                let v = if uv.len() > 1 {
+                    float(uv.get(1).copied(), error)?
+                } else {
+                    0.0
+                };
+                uvs.push([u, v]);
+            }
+            "vn" => normals.push(floats(words, error)?),
+            "f" => {
+                let mut corners = Vec::new();
+                for word in words {
+                    corners.push(corner(word, &positions, &uvs, &normals).map_err(error)?);
+                }
+                if corners.len() < 3 {
+                    return Err(error(format!(
+                        "face needs 3 vertices, got {}",
+                        corners.len()
+                    )));
+                }
+                builder.face(&corners, material, &positions, &uvs, &normals);
+            }
+            "o" => {
+                if let Some(mesh) = builder.finish() {
+                    model.meshes.push(mesh);
+                }
+                builder.mesh.name = rest.to_string();
+            }
+            "g" => {
+                for group in rest.split_whitespace() {
+                    if !builder.mesh.groups.iter().any(|g| g == group) {
+                        builder.mesh.groups.push(group.to_string());
+                    }
+                }
+            }
+            "usemtl" => {
+                material = Some(match model.materials.iter().position(|m| m.name == rest) {
+                    Some(index) => index,
+                    None => {
+                        model.materials.push(Material::new(rest));
+                        model.materials.len() - 1
+                    }
+                });
+            }
+            //Libraries are separated by spaces, so file names can't contain any.
+            "mtllib" => model
+                .material_libraries
+                .extend(rest.split_whitespace().map(str::to_string)),
+            //Smoothing groups and lines aren't used.
+            "s" | "l" | "p" => {}
+            _ => {}
+        }
+    }
+
+    if let Some(mesh) = builder.finish() {
+        model.meshes.push(mesh);
+    }
+    Ok(model)
+}
+
+/// Index into the position, uv and normal arrays. Missing attributes are `None`.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`. Indices start at 1 and negative indices count from the end.
+fn corner(
+    word: &str,
+    positions: &[[f32; 3]],
+    uvs: &[[f32; 2]],
+    normals: &[[f32; 3]],
+) -> Result<Corner, String> {
+    let index = |text: &str, len: usize| -> Result<usize, String> {
+        let i: i64 = text
+            .parse()
+            .map_err(|_| format!("invalid index '{}'", text))?;
+        let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+        if resolved < 0 || resolved >= len as i64 {
+            return Err(format!("index {} is out of range", i));
+        }
+        Ok(resolved as usize)
+    };
+
+    let mut parts = word.split('/');
+    let position = index(parts.next().unwrap(), positions.len())?;
+    let uv = match parts.next() {
+        Some(text) if !text.is_empty() => Some(index(text, uvs.len())?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(text) if !text.is_empty() => Some(index(text, normals.len())?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    mesh: Mesh,
+    /// Deduplicates vertices that share all of their attributes.
+    unique: HashMap<Corner, u32>,
+    /// Triangles for each material, merged into one index buffer by `finish`.
+    triangles: Vec<(Option<usize>, Vec<u32>)>,
+    /// Sum of face normals for vertices that didn't have one.
+    generated: HashMap<u32, glm::Vec3>,
+}
+
+impl MeshBuilder {
+    fn face(
+        &mut self,
+        corners: &[Corner],
+        material: Option<usize>,
+        positions: &[[f32; 3]],
+        uvs: &[[f32; 2]],
+        normals: &[[f32; 3]],
+    ) {
+        let indices: Vec<u32> = corners
+            .iter()
+            .map(|&corner| {
+                *self.unique.entry(corner).or_insert_with(|| {
+                    let (p, uv, n) = corner;
+                    self.mesh.vertices.push(ModelVertex {
+                        position: positions[p],
+                        uv: uv.map(|i| uvs[i]).unwrap_or_default(),
+                        normal: n.map(|i| normals[i]).unwrap_or_default(),
+                        ..Default::default()
+                    });
+                    (self.mesh.vertices.len() - 1) as u32
+                })
+            })
+            .collect();
+
+        if corners.iter().any(|c| c.2.is_none()) {
+            let p = |i: usize| glm::Vec3::from(positions[corners[i].0]);
+            let normal = glm::cross(&(p(1) - p(0)), &(p(2) - p(0)));
+            for (corner, &index) in corners.iter().zip(&indices) {
+                if corner.2.is_none() {
+                    *self.generated.entry(index).or_insert(glm::Vec3::zeros()) += normal;
+                }
+            }
+        }
+
+        let triangles = match self.triangles.iter_mut().find(|t| t.0 == material) {
+            Some((_, triangles)) => triangles,
+            None => {
+                self.triangles.push((material, Vec::new()));
+                &mut self.triangles.last_mut().unwrap().1
+            }
+        };
+        //Triangle fan, assumes n-gons are convex.
+        for i in 1..indices.len() - 1 {
+            triangles.extend([indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+
+    /// Returns the mesh and starts a new one, empty meshes are skipped.
+    fn finish(&mut self) -> Option<Mesh> {
+        let mut builder = std::mem::take(self);
+        if builder.triangles.is_empty() {
+            //Keep the groups of objects that haven't had faces yet.
+            self.mesh.name = builder.mesh.name;
+            self.mesh.groups = builder.mesh.groups;
+            return None;
+        }
+
+        for (index, normal) in builder.generated {
+            let normal = glm::normalize(&normal);
+            builder.mesh.vertices[index as usize].normal = [normal.x, normal.y, normal.z];
+        }
+
+        let mesh = &mut builder.mesh;
+        for (material, triangles) in builder.triangles {
+            let start = mesh.indices.len() as u32;
+            mesh.indices.extend(triangles);
+            mesh.submeshes.push(Submesh {
+                material,
+                indices: start..mesh.indices.len() as u32,
+            });
+        }
+        mesh.generate_tangents();
+        Some(builder.mesh)
+    }
+}
+
+/// `directory` is the folder of the `.mtl` file, texture paths are joined onto it.
+pub fn parse_mtl(source: &str, directory: &Path, name: &str) -> Result<Vec<Material>, ModelError> {
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let error = |message: String| ModelError::Parse {
+            file: name.to_string(),
+            line: i + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap().trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let rest = line[keyword.len()..].trim();
+
+        if keyword == "newmtl" {
+            materials.push(Material::new(rest));
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            return Err(error(format!("'{}' before newmtl", keyword)));
+        };
+
+        //Texture options like `-bm 1.0` come before the file name.
+        //Exporters on Windows sometimes write backslashes.
+        let map = || Some(directory.join(rest.split_whitespace().last()?.replace('\\', "/")));
+
+        match keyword {
+            "Ka" => material.ambient = floats(words, error)?,
+            "Kd" => material.diffuse = floats(words, error)?,
+            "Ks" => material.specular = floats(words, error)?,
+            "Ke" => material.emissive = floats(words, error)?,
+            "Ns" => material.shininess = float(words.next(), error)?,
+            "Ni" => material.optical_density = float(words.next(), error)?,
+            "d" => material.opacity = float(words.next(), error)?,
+            "Tr" => material.opacity = 1.0 - float(words.next(), error)?,
+            "illum" => {
+                material.illumination = rest
+                    .parse()
+                    .map_err(|_| error(format!("invalid illumination model '{}'", rest)))?
+            }
+            "map_Ka" => material.ambient_map = map(),
+            "map_Kd" => material.diffuse_map = map(),
+            "map_Ks" => material.specular_map = map(),
+            "map_Ke" => material.emissive_map = map(),
+            "map_Ns" => material.shininess_map = map(),
+            "map_d" => material.alpha_map = map(),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_map = map(),
+            "disp" => material.displacement_map = map(),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn float<E>(word: Option<&str>, error: impl Fn(String) -> E) -> Result<f32, E> {
+    match word {
+        Some(word) => word
+            .parse()
+            .map_err(|_| error(format!("invalid number '{}'", word))),
+        None => Err(error("missing number".to_string())),
+    }
+}
+
+/// Parses the first `N` numbers, anything after them is ignored.
+fn floats<'a, const N: usize, E>(
+    mut words: impl Iterator<Item = &'a str>,
+    error: impl Fn(String) -> E,
+) -> Result<[f32; N], E> {
+    let mut out = [0.0; N];
+    for value in &mut out {
+        *value = float(words.next(), &error)?;
+    }
+    Ok(out)
+}
+
+/// Vertex and index buffers for a `Mesh`.
+#[derive(Debug)]
+pub struct GpuMesh {
+    pub vao: NativeVertexArray,
+    pub vbo: NativeBuffer,
+    pub ebo: NativeBuffer,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl GpuMesh {
+    pub fn new(gl: &Context, mesh: &Mesh) -> Self {
+        unsafe {
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+
+            let vbo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                mesh.vertices.align_to::<u8>().1,
+                glow::STATIC_DRAW,
+            );
+
+            let ebo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
+            gl.buffer_data_u8_slice(
+                glow::ELEMENT_ARRAY_BUFFER,
+                mesh.indices.align_to::<u8>().1,
+                glow::STATIC_DRAW,
+            );
+
+            layout::gl::bind::<ModelVertex>(gl);
+            gl.bind_vertex_array(None);
+
+            Self {
+                vao,
+                vbo,
+                ebo,
+                submeshes: mesh.submeshes.clone(),
+            }
+        }
+    }
+
+    /// `material` is called before each submesh is drawn so it can bind textures and uniforms.
+    pub fn draw(&self, gl: &Context, mut material: impl FnMut(&Submesh)) {
+        unsafe {
+            gl.bind_vertex_array(Some(self.vao));
+            for submesh in &self.submeshes {
+                material(submesh);
+                gl.draw_elements(
+                    glow::TRIANGLES,
+                    submesh.indices.len() as i32,
+                    glow::UNSIGNED_INT,
+                    (submesh.indices.start as usize * std::mem::size_of::<u32>()) as i32,
+                );
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_vertex_array(self.vao);
+            gl.delete_buffer(self.vbo);
+            gl.delete_buffer(self.ebo);
+        }
+    }
+}
+
+/// Loads an image from disk with mipmaps. The image is flipped since OBJ's origin is the bottom left.
+pub fn load_texture(gl: &Context, path: &Path) -> Result<NativeTexture, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?
+        .flipv()
+        .into_rgba8();
+
+    unsafe {
+        let texture = gl.create_texture()?;
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, glow::REPEAT as i32);
+        gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, glow::REPEAT as i32);
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::LINEAR_MIPMAP_LINEAR as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::LINEAR as i32,
+        );
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA8 as i32,
+            image.width() as i32,
+            image.height() as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            Some(&image),
+        );
+        gl.generate_mipmap(glow::TEXTURE_2D);
+        Ok(texture)
+    }
+}
@@ -0,0 +1,468 @@
+//! Metallic-roughness materials lit by lights and an HDR environment.
+//!
+//! The environment is preprocessed into the three textures of the split sum approximation:
+//! https://learnopengl.com/PBR/IBL/Specular-IBL
+use crate::*;
+use std::io::{BufReader, BufWriter};
+use std::time::UNIX_EPOCH;
+
+/// Texture units used by `GpuPbrMaterial::bind` and `Ibl::bind`, see `bind_pbr_program`.
+pub const ALBEDO_UNIT: u32 = 0;
+pub const PBR_NORMAL_UNIT: u32 = 1;
+pub const METALLIC_UNIT: u32 = 2;
+pub const ROUGHNESS_UNIT: u32 = 3;
+pub const AO_UNIT: u32 = 4;
+pub const IRRADIANCE_UNIT: u32 = 5;
+pub const PREFILTERED_UNIT: u32 = 6;
+pub const BRDF_UNIT: u32 = 7;
+
+pub const PBR_FRAGMENT: &str = include_str!("../shaders/pbr_fragment.glsl");
+pub const PBR_FRAGMENT_PATH: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/pbr_fragment.glsl");
+const BRDF_COMMON: &str = include_str!("../shaders/brdf_common.glsl");
+const IRRADIANCE_FRAGMENT: &str = include_str!("../shaders/irradiance_fragment.glsl");
+const PREFILTER_FRAGMENT: &str = include_str!("../shaders/prefilter_fragment.glsl");
+const BRDF_FRAGMENT: &str = include_str!("../shaders/brdf_fragment.glsl");
+
+/// Adds the light limit, the shared BRDF functions and shadows to `pbr_fragment.glsl`.
+/// It shares `vertex.glsl` with the Blinn-Phong shader.
+pub fn pbr_fragment(source: &str) -> String {
+    with_light_limit(&with_shadows(&after_version(source, BRDF_COMMON)))
+}
+
+/// Connects the material and IBL samplers to their units, needs to run after every link.
+pub fn bind_pbr_program(gl: &Context, program: NativeProgram) {
+    unsafe {
+        gl.use_program(Some(program));
+        for (name, unit) in [
+            ("material.albedo", ALBEDO_UNIT),
+            ("material.normal", PBR_NORMAL_UNIT),
+            ("material.metallic", METALLIC_UNIT),
+            ("material.roughness", ROUGHNESS_UNIT),
+            ("material.ao", AO_UNIT),
+            ("irradiance_map", IRRADIANCE_UNIT),
+            ("prefiltered_map", PREFILTERED_UNIT),
+            ("brdf_lut", BRDF_UNIT),
+        ] {
+            let location = gl.get_uniform_location(program, name);
+            gl.uniform_1_i32(location.as_ref(), unit as i32);
+        }
+    }
+}
+
+/// Textures for `pbr_fragment.glsl`, maps are multiplied with their factors. Missing maps use `DefaultMaps`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuPbrMaterial {
+    /// Linear, the map is sRGB.
+    pub albedo: glm::Vec3,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+    pub albedo_map: Option<NativeTexture>,
+    /// Tangent space.
+    pub normal_map: Option<NativeTexture>,
+    /// Metallic, roughness and AO are read from the red channel.
+    pub metallic_map: Option<NativeTexture>,
+    pub roughness_map: Option<NativeTexture>,
+    pub ao_map: Option<NativeTexture>,
+}
+
+impl Default for GpuPbrMaterial {
+    fn default() -> Self {
+        Self {
+            albedo: glm::vec3(1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 0.5,
+            ao: 1.0,
+            albedo_map: None,
+            normal_map: None,
+            metallic_map: None,
+            roughness_map: None,
+            ao_map: None,
+        }
+    }
+}
+
+impl GpuPbrMaterial {
+    /// Loads `albedo.png`, `normal.png`, `metallic.png`, `roughness.png` and `ao.png` from `dir`.
+    /// Missing files are left out, files that fail to load are logged.
+    pub fn load_dir(gl: &Context, dir: &Path) -> Self {
+        let load = |name: &str| {
+            let path = dir.join(name);
+            if !path.exists() {
+                return None;
+            }
+            load_texture(gl, &path)
+                .map_err(|error| eprintln!("{}", error))
+                .ok()
+        };
+
+        let mut material = Self {
+            albedo_map: load("albedo.png"),
+            normal_map: load("normal.png"),
+            metallic_map: load("metallic.png"),
+            roughness_map: load("roughness.png"),
+            ao_map: load("ao.png"),
+            ..Default::default()
+        };
+        //The maps hold the real values.
+        if material.metallic_map.is_some() {
+            material.metallic = 1.0;
+        }
+        if material.roughness_map.is_some() {
+            material.roughness = 1.0;
+        }
+        material
+    }
+
+    /// Sets the `material` uniforms of the current program and binds the maps.
+    pub fn bind(&self, gl: &Context, program: NativeProgram, defaults: &DefaultMaps) {
+        unsafe {
+            let uniform = |name: &str| gl.get_uniform_location(program, name);
+            let albedo = &self.albedo;
+            gl.uniform_3_f32(
+                uniform("material.albedo_color").as_ref(),
+                albedo.x,
+                albedo.y,
+                albedo.z,
+            );
+            gl.uniform_1_f32(uniform("material.metallic_factor").as_ref(), self.metallic);
+            gl.uniform_1_f32(
+                uniform("material.roughness_factor").as_ref(),
+                self.roughness,
+            );
+            gl.uniform_1_f32(uniform("material.ao_factor").as_ref(), self.ao);
+
+            for (unit, map, default) in [
+                (ALBEDO_UNIT, self.albedo_map, defaults.white),
+                (PBR_NORMAL_UNIT, self.normal_map, defaults.flat_normal),
+                (METALLIC_UNIT, self.metallic_map, defaults.white),
+                (ROUGHNESS_UNIT, self.roughness_map, defaults.white),
+                (AO_UNIT, self.ao_map, defaults.white),
+            ] {
+                gl.active_texture(glow::TEXTURE0 + unit);
+                gl.bind_texture(glow::TEXTURE_2D, Some(map.unwrap_or(default)));
+            }
+            gl.active_texture(glow::TEXTURE0);
+        }
+    }
+}
+
+/// Face sizes in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IblSettings {
+    pub environment_size: u32,
+    pub irradiance_size: u32,
+    pub prefiltered_size: u32,
+    /// Mip levels of the prefiltered map, from roughness 0 to 1.
+    pub prefiltered_mips: u32,
+    pub brdf_size: u32,
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+        Self {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_mips: 5,
+            brdf_size: 512,
+        }
+    }
+}
+
+/// Image based lighting from an equirectangular HDR image.
+#[derive(Debug)]
+pub struct Ibl {
+    /// Mipmapped, it's also what the skybox shows.
+    pub environment: Cubemap,
+    pub irradiance: Cubemap,
+    /// One mip level per roughness step.
+    pub prefiltered: Cubemap,
+    /// RG16F, scale and bias to F0 indexed by NdotV and roughness.
+    pub brdf_lut: NativeTexture,
+    pub settings: IblSettings,
+}
+
+/// Written last, so an interrupted write never matches.
+const CACHE_KEY_FILE: &str = "key.txt";
+/// Part of the key, bumped whenever the files in the cache change.
+const CACHE_VERSION: u32 = 2;
+
+impl Ibl {
+    /// Reads the textures from `cache` if they were generated from the same file with the same
+    /// settings, otherwise generates them and writes them there. Cache errors are only logged.
+    ///
+    /// Changes the bound program.
+    pub fn load(
+        gl: &Context,
+        hdr: &Path,
+        settings: IblSettings,
+        cache: Option<&Path>,
+    ) -> Result<Self, String> {
+        let key = cache_key(hdr, &settings)?;
+        if let Some(cache) = cache {
+            match Self::read_cache(gl, cache, &key, settings) {
+                Ok(Some(ibl)) => return Ok(ibl),
+                Ok(None) => {}
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+
+        let ibl = Self::generate(gl, hdr, settings)?;
+        if let Some(cache) = cache {
+            if let Err(error) = ibl.write_cache(gl, cache, &key) {
+                eprintln!("{}", error);
+            }
+        }
+        Ok(ibl)
+    }
+
+    /// Renders every texture on the GPU.
+    ///
+    /// Changes the bound program.
+    pub fn generate(gl: &Context, hdr: &Path, settings: IblSettings) -> Result<Self, String> {
+        let s = settings;
+        let environment = Cubemap::from_equirectangular(gl, hdr, s.environment_size)?;
+
+        unsafe {
+            let irradiance_program = try_program(gl, CUBE_VERTEX, IRRADIANCE_FRAGMENT)?;
+            let prefilter_program = try_program(
+                gl,
+                CUBE_VERTEX,
+                &after_version(PREFILTER_FRAGMENT, BRDF_COMMON),
+            )?;
+            let brdf_program = try_program(
+                gl,
+                FULLSCREEN_VERTEX,
+                &after_version(BRDF_FRAGMENT, BRDF_COMMON),
+            )?;
+
+            let capture = Capture::new(gl);
+            environment.bind(gl, 0);
+
+            //Irradiance
+            let irradiance = Cubemap::new(gl, s.irradiance_size, 1);
+            capture.render_faces(gl, irradiance_program, &irradiance, 0);
+
+            //Prefiltered, one roughness per mip. The mips of the environment keep it from sparkling.
+            let prefiltered = Cubemap::new(gl, s.prefiltered_size, s.prefiltered_mips);
+            environment.bind(gl, 0);
+            gl.use_program(Some(prefilter_program));
+            let uniform = |name: &str| gl.get_uniform_location(prefilter_program, name);
+            gl.uniform_1_f32(uniform("resolution").as_ref(), s.environment_size as f32);
+            for mip in 0..s.prefiltered_mips {
+                let roughness = mip as f32 / (s.prefiltered_mips - 1).max(1) as f32;
+                gl.uniform_1_f32(uniform("roughness").as_ref(), roughness);
+                capture.render_faces(gl, prefilter_program, &prefiltered, mip);
+            }
+
+            //BRDF lookup table
+            let brdf_lut = create_texture_2d(gl, glow::RG16F, s.brdf_size, s.brdf_size, None);
+            capture.render_fullscreen(gl, brdf_program, brdf_lut, s.brdf_size, s.brdf_size);
+
+            capture.delete(gl);
+            for program in [irradiance_program, prefilter_program, brdf_program] {
+                gl.delete_program(program);
+            }
+
+            Ok(Self {
+                environment,
+                irradiance,
+                prefiltered,
+                brdf_lut,
+                settings,
+            })
+        }
+    }
+
+    /// `Ok(None)` if there's no cache or it's out of date.
+    fn read_cache(
+        gl: &Context,
+        dir: &Path,
+        key: &str,
+        settings: IblSettings,
+    ) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(dir.join(CACHE_KEY_FILE)) {
+            Ok(cached) if cached == key => {}
+            _ => return Ok(None),
+        }
+
+        //Read everything before creating textures so nothing leaks on errors.
+        let s = settings;
+        let faces = |name: &str, size: u32| read_hdr(&dir.join(name), size, size * 6);
+        let environment = faces("environment.hdr", s.environment_size)?;
+        let irradiance = faces("irradiance.hdr", s.irradiance_size)?;
+        let prefiltered = (0..s.prefiltered_mips)
+            .map(|mip| {
+                let size = (s.prefiltered_size >> mip).max(1);
+                faces(&format!("prefiltered_{}.hdr", mip), size)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let brdf = read_floats(
+            &dir.join("brdf_lut.f32"),
+            (s.brdf_size * s.brdf_size * 3) as usize,
+        )?;
+
+        let cubemap = |size: u32, levels: &[Vec<f32>]| {
+            let cubemap = Cubemap::new(gl, size, levels.len() as u32);
+            for (level, rgb) in levels.iter().enumerate() {
+                cubemap.upload(gl, level as u32, rgb);
+            }
+            cubemap
+        };
+        let environment_map = Cubemap::new(gl, s.environment_size, mip_levels(s.environment_size));
+        environment_map.upload(gl, 0, &environment);
+        //Only level 0 is cached.
+        environment_map.generate_mipmaps(gl);
+        Ok(Some(Self {
+            environment: environment_map,
+            irradiance: cubemap(s.irradiance_size, &[irradiance]),
+            prefiltered: cubemap(s.prefiltered_size, &prefiltered),
+            //Blue is dropped.
+            brdf_lut: create_texture_2d(gl, glow::RG16F, s.brdf_size, s.brdf_size, Some(&brdf)),
+            settings,
+        }))
+    }
+
+    fn write_cache(&self, gl: &Context, dir: &Path, key: &str) -> Result<(), String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let s = self.settings;
+        let faces = |name: &str, cubemap: &Cubemap, level: u32| {
+            let size = cubemap.level_size(level);
+            write_hdr(
+                &dir.join(name),
+                size,
+                size * 6,
+                &cubemap.download(gl, level),
+            )
+        };
+        faces("environment.hdr", &self.environment, 0)?;
+        faces("irradiance.hdr", &self.irradiance, 0)?;
+        for mip in 0..s.prefiltered_mips {
+            faces(&format!("prefiltered_{}.hdr", mip), &self.prefiltered, mip)?;
+        }
+
+        let mut brdf = vec![0.0f32; (s.brdf_size * s.brdf_size * 3) as usize];
+        unsafe {
+            //Blue reads back as 0.
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.brdf_lut));
+            gl.get_tex_image(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGB,
+                glow::FLOAT,
+                PixelPackData::Slice(brdf.align_to_mut::<u8>().1),
+            );
+        }
+        //The bias is much smaller than the scale, RGBE would round it away.
+        write_floats(&dir.join("brdf_lut.f32"), &brdf)?;
+
+        let path = dir.join(CACHE_KEY_FILE);
+        std::fs::write(&path, key).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Binds the maps to their units and sets `prefiltered_mips` on the current program.
+    pub fn bind(&self, gl: &Context, program: NativeProgram) {
+        unsafe {
+            let location = gl.get_uniform_location(program, "prefiltered_mips");
+            gl.uniform_1_f32(location.as_ref(), self.settings.prefiltered_mips as f32);
+            self.irradiance.bind(gl, IRRADIANCE_UNIT);
+            self.prefiltered.bind(gl, PREFILTERED_UNIT);
+            gl.active_texture(glow::TEXTURE0 + BRDF_UNIT);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.brdf_lut));
+            gl.active_texture(glow::TEXTURE0);
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        self.environment.delete(gl);
+        self.irradiance.delete(gl);
+        self.prefiltered.delete(gl);
+        unsafe { gl.delete_texture(self.brdf_lut) };
+    }
+}
+
+/// Changes when the source file or the settings do.
+pub fn cache_key(source: &Path, settings: &IblSettings) -> Result<String, String> {
+    let metadata = std::fs::metadata(source).map_err(|e| format!("{}: {}", source.display(), e))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+    Ok(format!(
+        "{}\n{}\n{}\n{}\n{:?}\n",
+        CACHE_VERSION,
+        source.display(),
+        metadata.len(),
+        modified,
+        settings
+    ))
+}
+
+/// Writes tightly packed RGB floats, cubemaps are stored as six faces from top to bottom.
+pub fn write_hdr(path: &Path, width: u32, height: u32, rgb: &[f32]) -> Result<(), String> {
+    assert_eq!(rgb.len(), (width * height * 3) as usize);
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let pixels: Vec<image::Rgb<f32>> = rgb
+        .chunks(3)
+        .map(|c| image::Rgb([c[0], c[1], c[2]]))
+        .collect();
+    image::codecs::hdr::HdrEncoder::new(BufWriter::new(file))
+        .encode(&pixels, width as usize, height as usize)
+        .map_err(|e| error(&e))
+}
+
+/// Inverse of `write_hdr`, fails if the image isn't `width` by `height`.
+pub fn read_hdr(path: &Path, width: u32, height: u32) -> Result<Vec<f32>, String> {
+    let (w, h, rgb) = decode_hdr(path)?;
+    if (w, h) != (width, height) {
+        return Err(format!(
+            "{}: expected {}x{}, found {}x{}",
+            path.display(),
+            width,
+            height,
+            w,
+            h
+        ));
+    }
+    Ok(rgb)
+}
+
+/// Writes little endian floats as they are. `write_hdr` shares one exponent per pixel.
+pub fn write_floats(path: &Path, values: &[f32]) -> Result<(), String> {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Inverse of `write_floats`, fails unless the file holds exactly `len` floats.
+pub fn read_floats(path: &Path, len: usize) -> Result<Vec<f32>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if bytes.len() != len * 4 {
+        return Err(format!(
+            "{}: expected {} floats, found {} bytes",
+            path.display(),
+            len,
+            bytes.len()
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect())
+}
+
+/// Width, height and RGB floats from the top left.
+/// `image::open` would tone map `.hdr` files to 8 bits.
+pub fn decode_hdr(path: &Path) -> Result<(u32, u32, Vec<f32>), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::open(path).map_err(|e| error(&e))?;
+    let decoder =
+        image::codecs::hdr::HdrDecoder::new(BufReader::new(file)).map_err(|e| error(&e))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr().map_err(|e| error(&e))?;
+    let rgb = pixels.iter().flat_map(|p| p.0).collect();
+    Ok((metadata.width, metadata.height, rgb))
+}
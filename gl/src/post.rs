@@ -0,0 +1,457 @@
+//! HDR rendering and a chain of post processing passes.
+//!
+//! The scene renders into a floating point target between `PostChain::begin` and `end`,
+//! then every enabled pass runs in order and the result is copied to the framebuffer that
+//! was bound at `begin`.
+use crate::*;
+
+const POST_FRAGMENT: &str = include_str!("../shaders/post_fragment.glsl");
+const BLOOM_FRAGMENT: &str = include_str!("../shaders/bloom_fragment.glsl");
+const FXAA_FRAGMENT: &str = include_str!("../shaders/fxaa_fragment.glsl");
+
+/// Format of the scene and every intermediate target. RGBA since GL 3.3 doesn't require RGB16F to be renderable.
+pub const HDR_FORMAT: u32 = glow::RGBA16F;
+
+/// Most levels a `Bloom` pass can blur through.
+pub const MAX_BLOOM_LEVELS: usize = 8;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMapper {
+    /// `c / (c + 1)`, never quite reaches white.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve.
+    #[default]
+    Aces,
+    /// Hable's Uncharted 2 curve.
+    Filmic,
+}
+
+impl ToneMapper {
+    /// Reinhard, ACES, filmic and around again.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Reinhard => Self::Aces,
+            Self::Aces => Self::Filmic,
+            Self::Filmic => Self::Reinhard,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    /// Keeps what's brighter than `threshold` for the next `Bloom`, without changing the image.
+    /// Colors up to `knee` below it fade in.
+    BrightPass {
+        threshold: f32,
+        knee: f32,
+    },
+    /// Blurs the last bright pass, or the image without one, through `levels` halvings with
+    /// a dual filter and adds it back. `radius` spreads the samples further apart.
+    Bloom {
+        levels: usize,
+        radius: f32,
+        intensity: f32,
+    },
+    Exposure(f32),
+    ToneMap(ToneMapper),
+    Gamma(f32),
+    /// Needs tone mapped and gamma corrected colors. See `fxaa_fragment.glsl`.
+    Fxaa {
+        span_max: f32,
+        reduce_mul: f32,
+        reduce_min: f32,
+    },
+    /// Darkens the corners by `intensity`, starting `radius` from the center in texture
+    /// coordinates and reaching full strength `softness` further out.
+    Vignette {
+        intensity: f32,
+        radius: f32,
+        softness: f32,
+    },
+}
+
+impl Effect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Effect::BrightPass { .. } => "Bright pass",
+            Effect::Bloom { .. } => "Bloom",
+            Effect::Exposure(_) => "Exposure",
+            Effect::ToneMap(_) => "Tone mapping",
+            Effect::Gamma(_) => "Gamma",
+            Effect::Fxaa { .. } => "FXAA",
+            Effect::Vignette { .. } => "Vignette",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostPass {
+    pub enabled: bool,
+    pub effect: Effect,
+}
+
+impl PostPass {
+    pub fn new(effect: Effect) -> Self {
+        Self {
+            enabled: true,
+            effect,
+        }
+    }
+}
+
+/// Bloom, ACES tone mapping, gamma 2.2, FXAA and a light vignette.
+pub fn default_passes() -> Vec<PostPass> {
+    [
+        Effect::BrightPass {
+            threshold: 0.8,
+            knee: 0.4,
+        },
+        Effect::Bloom {
+            levels: 5,
+            radius: 1.0,
+            intensity: 0.6,
+        },
+        Effect::Exposure(1.0),
+        Effect::ToneMap(ToneMapper::Aces),
+        Effect::Gamma(2.2),
+        Effect::Fxaa {
+            span_max: 8.0,
+            reduce_mul: 1.0 / 8.0,
+            reduce_min: 1.0 / 128.0,
+        },
+        Effect::Vignette {
+            intensity: 0.4,
+            radius: 0.5,
+            softness: 0.45,
+        },
+    ]
+    .into_iter()
+    .map(PostPass::new)
+    .collect()
+}
+
+/// Sizes of the bloom targets, halving from `width` and `height` until either side would
+/// drop below 2 pixels or there are `max` of them.
+pub fn bloom_sizes(width: u32, height: u32, max: usize) -> Vec<(u32, u32)> {
+    let mut sizes = Vec::new();
+    let (mut width, mut height) = (width / 2, height / 2);
+    while sizes.len() < max && width >= 2 && height >= 2 {
+        sizes.push((width, height));
+        width /= 2;
+        height /= 2;
+    }
+    sizes
+}
+
+/// `post_fragment.glsl` and `bloom_fragment.glsl` compiled once per define.
+#[derive(Debug)]
+struct PostPrograms {
+    bright_pass: NativeProgram,
+    composite: NativeProgram,
+    exposure: NativeProgram,
+    tone_map: NativeProgram,
+    gamma: NativeProgram,
+    vignette: NativeProgram,
+    fxaa: NativeProgram,
+    downsample: NativeProgram,
+    upsample: NativeProgram,
+}
+
+impl PostPrograms {
+    fn new(gl: &Context) -> Result<Self, String> {
+        let mut programs = Vec::new();
+        let mut compile = |source: &str, define: &str| {
+            let source = after_version(source, &format!("#define {}\n", define));
+            let program = unsafe { try_program(gl, FULLSCREEN_VERTEX, &source) };
+            if let Ok(program) = program {
+                programs.push(program);
+            }
+            program
+        };
+        let result = (|| {
+            Ok(Self {
+                bright_pass: compile(POST_FRAGMENT, "BRIGHT_PASS")?,
+                composite: compile(POST_FRAGMENT, "COMPOSITE")?,
+                exposure: compile(POST_FRAGMENT, "EXPOSURE")?,
+                tone_map: compile(POST_FRAGMENT, "TONE_MAP")?,
+                gamma: compile(POST_FRAGMENT, "GAMMA")?,
+                vignette: compile(POST_FRAGMENT, "VIGNETTE")?,
+                fxaa: compile(FXAA_FRAGMENT, "FXAA")?,
+                downsample: compile(BLOOM_FRAGMENT, "DOWNSAMPLE")?,
+                upsample: compile(BLOOM_FRAGMENT, "UPSAMPLE")?,
+            })
+        })();
+        //Don't leak the ones that compiled before the error.
+        if result.is_err() {
+            for program in programs {
+                unsafe { gl.delete_program(program) };
+            }
+        }
+        result
+    }
+
+    fn all(&self) -> [NativeProgram; 9] {
+        [
+            self.bright_pass,
+            self.composite,
+            self.exposure,
+            self.tone_map,
+            self.gamma,
+            self.vignette,
+            self.fxaa,
+            self.downsample,
+            self.upsample,
+        ]
+    }
+}
+
+/// Runs `passes` over the scene. Assumes blending is off, the depth test is turned off
+/// while the passes run.
+#[derive(Debug)]
+pub struct PostChain {
+    /// Runs in order, the same effect can appear more than once.
+    pub passes: Vec<PostPass>,
+    /// The scene renders into this between `begin` and `end`, it has a depth buffer.
+    pub scene: RenderTarget,
+    ping_pong: [RenderTarget; 2],
+    bright: RenderTarget,
+    bloom: Vec<RenderTarget>,
+    programs: PostPrograms,
+    vao: NativeVertexArray,
+    /// Saved by `begin` for `end`.
+    state: Option<RenderState>,
+}
+
+impl PostChain {
+    pub fn new(
+        gl: &Context,
+        width: u32,
+        height: u32,
+        passes: Vec<PostPass>,
+    ) -> Result<Self, String> {
+        let programs = PostPrograms::new(gl)?;
+        let target = |depth: bool| RenderTarget::new(gl, width, height, HDR_FORMAT, depth);
+        let bloom = bloom_sizes(width, height, MAX_BLOOM_LEVELS)
+            .into_iter()
+            .map(|(w, h)| RenderTarget::new(gl, w, h, HDR_FORMAT, false))
+            .collect::<Result<_, _>>()?;
+        unsafe {
+            //Core profiles need some vertex array bound, even without attributes.
+            let vao = gl.create_vertex_array()?;
+            Ok(Self {
+                passes,
+                scene: target(true)?,
+                ping_pong: [target(false)?, target(false)?],
+                bright: target(false)?,
+                bloom,
+                programs,
+                vao,
+                state: None,
+            })
+        }
+    }
+
+    /// Resizes every target, call it when the window's framebuffer changes size.
+    pub fn resize(&mut self, gl: &Context, width: u32, height: u32) -> Result<(), String> {
+        self.scene.resize(gl, width, height)?;
+        for target in self.ping_pong.iter_mut() {
+            target.resize(gl, width, height)?;
+        }
+        self.bright.resize(gl, width, height)?;
+        for target in self.bloom.drain(..) {
+            target.delete(gl);
+        }
+        for (w, h) in bloom_sizes(width, height, MAX_BLOOM_LEVELS) {
+            self.bloom
+                .push(RenderTarget::new(gl, w, h, HDR_FORMAT, false)?);
+        }
+        Ok(())
+    }
+
+    /// Binds and clears the scene target. Remembers the framebuffer and viewport for `end`.
+    pub fn begin(&mut self, gl: &Context) {
+        self.state = Some(RenderState::save(gl));
+        self.scene.begin(gl);
+        unsafe { gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT) };
+    }
+
+    /// Runs the passes and copies the result into the viewport saved by `begin`.
+    /// Changes the bound program, vertex array and texture unit 0.
+    pub fn end(&mut self, gl: &Context) {
+        let state = self.state.take().unwrap_or_else(|| RenderState::save(gl));
+        unsafe {
+            let depth_test = gl.is_enabled(glow::DEPTH_TEST);
+            gl.disable(glow::DEPTH_TEST);
+            gl.bind_vertex_array(Some(self.vao));
+
+            //The scene, then whichever half of the ping pong was written last.
+            let mut current = &self.scene;
+            let mut next = 0;
+            let mut bright = None;
+            for pass in self.passes.iter().filter(|pass| pass.enabled) {
+                let program = self.program(&pass.effect);
+                gl.use_program(Some(program));
+                let uniform = |name: &str| gl.get_uniform_location(program, name);
+                let float = |name: &str, value: f32| {
+                    gl.uniform_1_f32(uniform(name).as_ref(), value);
+                };
+
+                match pass.effect {
+                    Effect::BrightPass { threshold, knee } => {
+                        float("threshold", threshold);
+                        float("knee", knee);
+                        self.draw(gl, current.texture, &self.bright);
+                        bright = Some(self.bright.texture);
+                        continue;
+                    }
+                    Effect::Bloom {
+                        levels,
+                        radius,
+                        intensity,
+                    } => {
+                        let source = bright.unwrap_or(current.texture);
+                        let Some(bloom) = self.blur(gl, source, levels, radius) else {
+                            continue;
+                        };
+                        gl.use_program(Some(program));
+                        float("intensity", intensity);
+                        gl.uniform_1_i32(uniform("bloom").as_ref(), 1);
+                        gl.active_texture(glow::TEXTURE1);
+                        gl.bind_texture(glow::TEXTURE_2D, Some(bloom));
+                        gl.active_texture(glow::TEXTURE0);
+                    }
+                    Effect::Exposure(exposure) => float("exposure", exposure),
+                    Effect::ToneMap(tone_mapper) => {
+                        gl.uniform_1_i32(uniform("tone_mapper").as_ref(), tone_mapper as i32);
+                    }
+                    Effect::Gamma(gamma) => float("gamma", gamma),
+                    Effect::Fxaa {
+                        span_max,
+                        reduce_mul,
+                        reduce_min,
+                    } => {
+                        let (w, h) = (current.width as f32, current.height as f32);
+                        gl.uniform_2_f32(uniform("texel").as_ref(), 1.0 / w, 1.0 / h);
+                        float("span_max", span_max);
+                        float("reduce_mul", reduce_mul);
+                        float("reduce_min", reduce_min);
+                    }
+                    Effect::Vignette {
+                        intensity,
+                        radius,
+                        softness,
+                    } => {
+                        float("intensity", intensity);
+                        float("radius", radius);
+                        float("softness", softness);
+                    }
+                }
+                self.draw(gl, current.texture, &self.ping_pong[next]);
+                current = &self.ping_pong[next];
+                next = 1 - next;
+            }
+
+            //Scaled if the viewport isn't the size of the targets.
+            let [x, y, width, height] = state.viewport;
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(current.framebuffer));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, state.framebuffer);
+            gl.blit_framebuffer(
+                0,
+                0,
+                current.width as i32,
+                current.height as i32,
+                x,
+                y,
+                x + width,
+                y + height,
+                glow::COLOR_BUFFER_BIT,
+                glow::LINEAR,
+            );
+            state.restore(gl);
+            if depth_test {
+                gl.enable(glow::DEPTH_TEST);
+            }
+        }
+    }
+
+    fn program(&self, effect: &Effect) -> NativeProgram {
+        let programs = &self.programs;
+        match effect {
+            Effect::BrightPass { .. } => programs.bright_pass,
+            Effect::Bloom { .. } => programs.composite,
+            Effect::Exposure(_) => programs.exposure,
+            Effect::ToneMap(_) => programs.tone_map,
+            Effect::Gamma(_) => programs.gamma,
+            Effect::Fxaa { .. } => programs.fxaa,
+            Effect::Vignette { .. } => programs.vignette,
+        }
+    }
+
+    /// Draws the bound program over all of `target` with `source` on unit 0.
+    fn draw(&self, gl: &Context, source: NativeTexture, target: &RenderTarget) {
+        target.begin(gl);
+        unsafe {
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(source));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        }
+    }
+
+    /// Downsamples `source` through the first `levels` bloom targets and back up to the
+    /// first, which is returned. `None` if the targets are too small for any levels.
+    fn blur(
+        &self,
+        gl: &Context,
+        source: NativeTexture,
+        levels: usize,
+        radius: f32,
+    ) -> Option<NativeTexture> {
+        let levels = &self.bloom[..levels.min(self.bloom.len())];
+        let first = levels.first()?;
+        unsafe {
+            let texel = |program: NativeProgram, width: u32, height: u32| {
+                let location = gl.get_uniform_location(program, "texel");
+                gl.uniform_2_f32(location.as_ref(), 1.0 / width as f32, 1.0 / height as f32);
+            };
+            for program in [self.programs.downsample, self.programs.upsample] {
+                gl.use_program(Some(program));
+                let location = gl.get_uniform_location(program, "radius");
+                gl.uniform_1_f32(location.as_ref(), radius);
+            }
+
+            gl.use_program(Some(self.programs.downsample));
+            let (mut texture, mut width, mut height) =
+                (source, self.scene.width, self.scene.height);
+            for level in levels {
+                texel(self.programs.downsample, width, height);
+                self.draw(gl, texture, level);
+                (texture, width, height) = (level.texture, level.width, level.height);
+            }
+
+            gl.use_program(Some(self.programs.upsample));
+            for level in levels.iter().rev().skip(1) {
+                texel(self.programs.upsample, width, height);
+                self.draw(gl, texture, level);
+                (texture, width, height) = (level.texture, level.width, level.height);
+            }
+        }
+        Some(first.texture)
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            for program in self.programs.all() {
+                gl.delete_program(program);
+            }
+            gl.delete_vertex_array(self.vao);
+        }
+        self.scene.delete(gl);
+        for target in self.ping_pong {
+            target.delete(gl);
+        }
+        self.bright.delete(gl);
+        for target in self.bloom {
+            target.delete(gl);
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Node hierarchy with cached world matrices and frustum culling.
+use crate::*;
+
+/// Translation * rotation * scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: glm::Vec3,
+    pub rotation: glm::Quat,
+    pub scale: glm::Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: glm::Vec3::zeros(),
+            rotation: glm::Quat::identity(),
+            scale: glm::vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: glm::Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn matrix(&self) -> glm::Mat4 {
+        glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale)
+    }
+}
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = glm::Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self {
+            min: glm::min2(&aabb.min, &p),
+            max: glm::max2(&aabb.max, &p),
+        }))
+    }
+
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        Self::from_points(mesh.vertices.iter().map(|v| glm::Vec3::from(v.position)))
+    }
+
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Half of the size.
+    pub fn extents(&self) -> glm::Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    /// Box around the transformed box, which is larger than it when rotated.
+    pub fn transform(&self, matrix: &glm::Mat4) -> Aabb {
+        let center = (matrix * self.center().push(1.0)).xyz();
+        let linear = glm::mat4_to_mat3(matrix).abs();
+        let extents = linear * self.extents();
+        Self {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+/// Clip planes of a view-projection matrix, normals point inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far as (normal, distance).
+    pub planes: [glm::Vec4; 6],
+}
+
+impl Frustum {
+    /// Gribb and Hartmann, expects OpenGL's -w..w clip space depth.
+    pub fn new(view_projection: &glm::Mat4) -> Self {
+        let row = |i: usize| view_projection.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|p| p / p.xyz().norm());
+        Self { planes }
+    }
+
+    pub fn contains(&self, point: &glm::Vec3) -> bool {
+        self.planes.iter().all(|p| p.xyz().dot(point) + p.w >= 0.0)
+    }
+
+    /// Conservative, boxes near the corners can pass without being inside.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            //The corner furthest along the normal.
+            let corner = glm::vec3(
+                if p.x > 0.0 { aabb.max.x } else { aabb.min.x },
+                if p.y > 0.0 { aabb.max.y } else { aabb.min.y },
+                if p.z > 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            p.xyz().dot(&corner) + p.w >= 0.0
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode {
+    pub name: String,
+    /// Indices into whatever the application keeps its meshes, lights and cameras in.
+    pub mesh: Option<usize>,
+    pub light: Option<usize>,
+    pub camera: Option<usize>,
+    /// Hidden nodes and their children are skipped by `Scene::visible`.
+    pub hidden: bool,
+    transform: Transform,
+    /// Local space, nodes without bounds are never culled.
+    bounds: Option<Aabb>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    world: glm::Mat4,
+    world_bounds: Option<Aabb>,
+    dirty: bool,
+}
+
+impl SceneNode {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn bounds(&self) -> Option<&Aabb> {
+        self.bounds.as_ref()
+    }
+
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    /// As of the last `Scene::update`.
+    pub fn world(&self) -> &glm::Mat4 {
+        &self.world
+    }
+
+    /// As of the last `Scene::update`.
+    pub fn world_bounds(&self) -> Option<&Aabb> {
+        self.world_bounds.as_ref()
+    }
+}
+
+/// Nodes are never removed so their indices stay valid.
+///
+/// Changing a transform marks the node dirty, `update` then recomputes it and
+/// everything below it. Call it once per frame before reading world matrices.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, transform: Transform, parent: Option<usize>) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(SceneNode {
+            name: name.to_string(),
+            mesh: None,
+            light: None,
+            camera: None,
+            hidden: false,
+            transform,
+            bounds: None,
+            parent,
+            children: Vec::new(),
+            world: glm::Mat4::identity(),
+            world_bounds: None,
+            dirty: true,
+        });
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        id
+    }
+
+    /// Same as `add` but with a mesh and its bounds.
+    pub fn add_mesh(
+        &mut self,
+        name: &str,
+        transform: Transform,
+        parent: Option<usize>,
+        mesh: usize,
+        bounds: Option<Aabb>,
+    ) -> usize {
+        let id = self.add(name, transform, parent);
+        self.nodes[id].mesh = Some(mesh);
+        self.nodes[id].bounds = bounds;
+        id
+    }
+
+    /// Nodes mirror the glTF nodes and their indices, mesh bounds are computed from the vertices.
+    /// Nodes outside of the default scene are hidden.
+    pub fn from_gltf(gltf: &Gltf) -> Self {
+        let mut scene = Self::new();
+        for node in &gltf.nodes {
+            let transform = Transform {
+                translation: node.translation,
+                rotation: node.rotation,
+                scale: node.scale,
+            };
+            let id = scene.add(&node.name, transform, None);
+            scene.nodes[id].mesh = node.mesh;
+            scene.nodes[id].camera = node.camera;
+            scene.nodes[id].bounds = node.mesh.and_then(|m| Aabb::from_mesh(&gltf.meshes[m]));
+        }
+        for (i, node) in gltf.nodes.iter().enumerate() {
+            for &child in &node.children {
+                scene.set_parent(child, Some(i));
+            }
+        }
+        for &root in &scene.roots {
+            scene.nodes[root].hidden = !gltf.roots.contains(&root);
+        }
+        scene
+    }
+
+    pub fn transform_mut(&mut self, node: usize) -> &mut Transform {
+        self.nodes[node].dirty = true;
+        &mut self.nodes[node].transform
+    }
+
+    pub fn set_transform(&mut self, node: usize, transform: Transform) {
+        *self.transform_mut(node) = transform;
+    }
+
+    pub fn set_bounds(&mut self, node: usize, bounds: Option<Aabb>) {
+        self.nodes[node].bounds = bounds;
+        self.nodes[node].dirty = true;
+    }
+
+    /// Moves the node and its children, keeping the local transform.
+    ///
+    /// Panics if `parent` is the node or one of its descendants.
+    pub fn set_parent(&mut self, node: usize, parent: Option<usize>) {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert!(a != node, "node {} can't be its own ancestor", node);
+            ancestor = self.nodes[a].parent;
+        }
+
+        match self.nodes[node].parent {
+            Some(old) => self.nodes[old].children.retain(|&c| c != node),
+            None => self.roots.retain(|&r| r != node),
+        }
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(node),
+            None => self.roots.push(node),
+        }
+        self.nodes[node].parent = parent;
+        self.nodes[node].dirty = true;
+    }
+
+    /// Recomputes the world matrices and bounds of dirty nodes and their descendants.
+    /// Returns how many nodes were recomputed.
+    pub fn update(&mut self) -> usize {
+        let mut updated = 0;
+        let mut stack: Vec<(usize, bool)> = self.roots.iter().map(|&r| (r, false)).collect();
+        while let Some((id, parent_changed)) = stack.pop() {
+            let changed = parent_changed || self.nodes[id].dirty;
+            if changed {
+                let parent = match self.nodes[id].parent {
+                    Some(parent) => self.nodes[parent].world,
+                    None => glm::Mat4::identity(),
+                };
+                let node = &mut self.nodes[id];
+                node.world = parent * node.transform.matrix();
+                node.world_bounds = node.bounds.map(|b| b.transform(&node.world));
+                node.dirty = false;
+                updated += 1;
+            }
+            stack.extend(self.nodes[id].children.iter().map(|&c| (c, changed)));
+        }
+        updated
+    }
+
+    /// Nodes with a mesh that aren't hidden and intersect the frustum, in depth first order.
+    pub fn visible(&self, view_projection: &glm::Mat4) -> Vec<usize> {
+        let frustum = Frustum::new(view_projection);
+        let mut visible = Vec::new();
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if node.hidden {
+                continue;
+            }
+            if node.mesh.is_some() && node.world_bounds.is_none_or(|b| frustum.intersects(&b)) {
+                visible.push(id);
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        visible
+    }
+}
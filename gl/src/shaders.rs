@@ -1,6 +1,7 @@
 use std::mem::size_of;
 
 use crate::*;
+use layout::VertexLayout;
 
 /// Limitations: https://registry.khronos.org/OpenGL-Refpages/gl4/html/glLinkProgram.xhtml
 /// Cannot exceed the limit for attributes, uniforms or have any compile issues.
@@ -92,6 +93,14 @@ pub const RECTANGLE_F: &str = r#"
         }
     "#;
 
+/// Layout of `TriangleBuffer::data`.
+#[repr(C)]
+#[derive(VertexLayout)]
+pub struct ColorVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
 pub struct TriangleBuffer {
     pub buffer: NativeBuffer,
     pub shader: NativeProgram,
@@ -134,11 +143,7 @@ impl TriangleBuffer {
     }
     pub fn draw(&self, gl: &Context) {
         unsafe {
-            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 6 * 4, 0);
-            gl.enable_vertex_attrib_array(0);
-
-            gl.vertex_attrib_pointer_f32(1, 3, glow::FLOAT, false, 6 * 4, 3 * 4);
-            gl.enable_vertex_attrib_array(1);
+            layout::gl::bind::<ColorVertex>(gl);
 
             gl.use_program(Some(self.shader));
             gl.draw_arrays(glow::TRIANGLES, 0, 3);
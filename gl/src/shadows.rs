@@ -0,0 +1,418 @@
+//! Shadow maps for one directional light, split into cascades, and one point light.
+//!
+//! `with_shadows` adds `shaders/shadows_common.glsl` to a lighting shader, which then multiplies
+//! each light by `shadow(...)`. Cascades follow
+//! https://developer.nvidia.com/gpugems/gpugems3/part-ii-light-and-shadows/chapter-10-parallel-split-shadow-maps-programmable-gpus
+use crate::*;
+use std::num::NonZeroU32;
+
+/// Size of the cascade arrays in the shader.
+pub const MAX_CASCADES: usize = 4;
+
+/// Texture units used by `Shadows::bind`, above every material and IBL unit.
+pub const CASCADE_UNIT: u32 = 8;
+pub const POINT_SHADOW_UNIT: u32 = 9;
+
+const SHADOW_VERTEX: &str = include_str!("../shaders/shadow_vertex.glsl");
+const SHADOW_FRAGMENT: &str = include_str!("../shaders/shadow_fragment.glsl");
+const SHADOWS_COMMON: &str = include_str!("../shaders/shadows_common.glsl");
+const SHADOW_DEBUG_FRAGMENT: &str = include_str!("../shaders/shadow_debug_fragment.glsl");
+
+/// Inserts `#define MAX_CASCADES` and the shadow functions after the `#version` line.
+pub fn with_shadows(source: &str) -> String {
+    let common = format!("#define MAX_CASCADES {}\n{}", MAX_CASCADES, SHADOWS_COMMON);
+    after_version(source, &common)
+}
+
+/// Biases are in world units, the slope bias is scaled by the tangent of the light's angle
+/// to the surface and then clamped to `max_bias`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowSettings {
+    /// Resolution of each cascade.
+    pub size: u32,
+    /// Resolution of each face of the point light cube.
+    pub point_size: u32,
+    /// PCF samples `(2 * radius + 1)^2` texels for the cascades and 20 directions for
+    /// point lights, 0 is hard shadows.
+    pub pcf_radius: i32,
+    pub constant_bias: f32,
+    pub slope_bias: f32,
+    pub max_bias: f32,
+    /// At most `MAX_CASCADES`.
+    pub cascade_count: usize,
+    /// Blend between uniform (0) and logarithmic (1) splits.
+    pub split_lambda: f32,
+    /// How far from the camera directional shadows reach.
+    pub shadow_distance: f32,
+    /// How far behind each cascade casters are still rendered.
+    pub caster_distance: f32,
+    /// Far plane of the point light, fragments past it are lit.
+    pub point_far: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            size: 2048,
+            point_size: 1024,
+            pcf_radius: 1,
+            constant_bias: 0.01,
+            slope_bias: 0.01,
+            max_bias: 0.05,
+            cascade_count: MAX_CASCADES,
+            split_lambda: 0.75,
+            shadow_distance: 50.0,
+            caster_distance: 50.0,
+            point_far: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cascade {
+    /// World to light clip space.
+    pub matrix: glm::Mat4,
+    /// View space distance where the cascade ends.
+    pub far: f32,
+    /// Distance between the near and far plane of `matrix`.
+    pub depth: f32,
+}
+
+/// Far distance of each split, mixing uniform and logarithmic splits by `lambda`.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// World space corners of the volume `inverse_view_projection` maps to clip space.
+pub fn frustum_corners(inverse_view_projection: &glm::Mat4) -> [glm::Vec3; 8] {
+    let mut corners = [glm::Vec3::zeros(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = glm::vec4(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+            1.0,
+        );
+        let world = inverse_view_projection * ndc;
+        *corner = world.xyz() / world.w;
+    }
+    corners
+}
+
+/// Orthographic light matrix covering `corners` with `caster_distance` of room towards the light.
+///
+/// Fits a sphere instead of a box so the size doesn't change as the camera turns, and snaps
+/// to whole texels of a `size` map so edges don't shimmer as it moves.
+pub fn fit_light(
+    direction: &glm::Vec3,
+    corners: &[glm::Vec3],
+    size: u32,
+    caster_distance: f32,
+) -> (glm::Mat4, f32) {
+    let center = corners.iter().sum::<glm::Vec3>() / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| glm::distance(corner, &center))
+        .fold(0.0, f32::max);
+    //Rounded up, float noise would otherwise change the texel size every frame.
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let direction = direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        glm::vec3(0.0, 0.0, 1.0)
+    } else {
+        glm::vec3(0.0, 1.0, 0.0)
+    };
+    let eye = center - direction * (radius + caster_distance);
+    let view = glm::look_at(&eye, &center, &up);
+    let depth = radius * 2.0 + caster_distance;
+    let mut projection = glm::ortho(-radius, radius, -radius, radius, 0.0, depth);
+
+    let origin = projection * view * glm::vec4(0.0, 0.0, 0.0, 1.0);
+    let texels = origin.xy() * size as f32 / 2.0;
+    let offset = (texels.map(f32::round) - texels) * 2.0 / size as f32;
+    projection[(0, 3)] += offset.x;
+    projection[(1, 3)] += offset.y;
+    (projection * view, depth)
+}
+
+/// Splits the camera's view up to `settings.shadow_distance` and fits a light matrix to each.
+pub fn cascades(
+    camera: &Camera,
+    aspect: f32,
+    direction: &glm::Vec3,
+    settings: &ShadowSettings,
+) -> Vec<Cascade> {
+    let (near, far) = match camera.projection {
+        Projection::Perspective { znear, zfar, .. } => (znear, zfar.unwrap_or(f32::INFINITY)),
+        Projection::Orthographic { znear, zfar, .. } => (znear, zfar),
+    };
+    let far = far.min(settings.shadow_distance);
+    let count = settings.cascade_count.clamp(1, MAX_CASCADES);
+    let view = camera.view();
+
+    let mut start = near;
+    let mut cascades = Vec::with_capacity(count);
+    for end in cascade_splits(near, far, count, settings.split_lambda) {
+        let projection = match camera.projection {
+            Projection::Perspective {
+                yfov,
+                aspect: fixed,
+                ..
+            } => glm::perspective(fixed.unwrap_or(aspect), yfov, start, end),
+            Projection::Orthographic { xmag, ymag, .. } => {
+                glm::ortho(-xmag, xmag, -ymag, ymag, start, end)
+            }
+        };
+        let inverse = (projection * view)
+            .try_inverse()
+            .unwrap_or_else(glm::Mat4::identity);
+        let corners = frustum_corners(&inverse);
+        let (matrix, depth) =
+            fit_light(direction, &corners, settings.size, settings.caster_distance);
+        cascades.push(Cascade {
+            matrix,
+            far: end,
+            depth,
+        });
+        start = end;
+    }
+    cascades
+}
+
+/// Renders and binds the shadow maps. Pick the lights with `directional` and `point`,
+/// lights of another kind are ignored.
+#[derive(Debug)]
+pub struct Shadows {
+    pub settings: ShadowSettings,
+    pub program: NativeProgram,
+    pub cascade_map: DepthMap,
+    pub point_map: DepthMap,
+    /// Index into `Lights::lights`.
+    pub directional: Option<usize>,
+    pub point: Option<usize>,
+    /// Tints the scene by cascade.
+    pub tint_cascades: bool,
+    /// From the last `render`, empty without a directional light.
+    pub cascades: Vec<Cascade>,
+    point_rendered: bool,
+}
+
+impl Shadows {
+    /// The map sizes are fixed, create new `Shadows` to change them.
+    pub fn new(gl: &Context, settings: ShadowSettings) -> Result<Self, String> {
+        let program = unsafe { try_program(gl, SHADOW_VERTEX, SHADOW_FRAGMENT)? };
+        let cascade_map =
+            DepthMap::new(gl, settings.size, DepthLayout::Array(MAX_CASCADES as u32))?;
+        let point_map = DepthMap::new(gl, settings.point_size, DepthLayout::Cube)?;
+        Ok(Self {
+            settings,
+            program,
+            cascade_map,
+            point_map,
+            directional: None,
+            point: None,
+            tint_cascades: false,
+            cascades: Vec::new(),
+            point_rendered: false,
+        })
+    }
+
+    /// Renders every map. `draw` is called once per cascade and cube face with the `model`
+    /// location to set and the light's view projection for culling, it should only draw
+    /// geometry. Restores the framebuffer, viewport and program.
+    pub fn render(
+        &mut self,
+        gl: &Context,
+        lights: &Lights,
+        camera: &Camera,
+        aspect: f32,
+        mut draw: impl FnMut(&NativeUniformLocation, &glm::Mat4),
+    ) {
+        unsafe {
+            let state = RenderState::save(gl);
+            let current = gl.get_parameter_i32(glow::CURRENT_PROGRAM);
+            gl.use_program(Some(self.program));
+            let uniform = |name: &str| gl.get_uniform_location(self.program, name);
+            let model = uniform("model").unwrap();
+            let light_space = uniform("light_space");
+            let linear_depth = uniform("linear_depth");
+
+            self.cascades.clear();
+            let light = self.directional.and_then(|i| lights.lights.get(i));
+            if let Some(Light::Directional { direction, .. }) = light {
+                self.cascades = cascades(camera, aspect, direction, &self.settings);
+                gl.uniform_1_i32(linear_depth.as_ref(), 0);
+                for (layer, cascade) in self.cascades.iter().enumerate() {
+                    self.cascade_map.begin(gl, layer as u32);
+                    let matrix = cascade.matrix.as_slice();
+                    gl.uniform_matrix_4_f32_slice(light_space.as_ref(), false, matrix);
+                    draw(&model, &cascade.matrix);
+                }
+            }
+
+            self.point_rendered = false;
+            let light = self.point.and_then(|i| lights.lights.get(i));
+            if let Some(Light::Point { position, .. }) = light {
+                let far = self.settings.point_far;
+                let projection = glm::perspective(1.0, PI / 2.0, 0.05, far);
+                let translation = glm::translation(&-position);
+                gl.uniform_1_i32(linear_depth.as_ref(), 1);
+                let p = position;
+                gl.uniform_3_f32(uniform("light_position").as_ref(), p.x, p.y, p.z);
+                gl.uniform_1_f32(uniform("far_plane").as_ref(), far);
+                for (face, view) in capture_views().iter().enumerate() {
+                    self.point_map.begin(gl, face as u32);
+                    let matrix = projection * view * translation;
+                    gl.uniform_matrix_4_f32_slice(light_space.as_ref(), false, matrix.as_slice());
+                    draw(&model, &matrix);
+                }
+                self.point_rendered = true;
+            }
+
+            state.restore(gl);
+            gl.use_program(NonZeroU32::new(current as u32).map(NativeProgram));
+        }
+    }
+
+    /// Sets the uniforms from `shadows_common.glsl` and binds the maps. Leaves `program` in use.
+    pub fn bind(&self, gl: &Context, program: NativeProgram) {
+        unsafe {
+            gl.use_program(Some(program));
+            let uniform = |name: &str| gl.get_uniform_location(program, name);
+            let int = |name: &str, value: i32| gl.uniform_1_i32(uniform(name).as_ref(), value);
+            let float = |name: &str, value: f32| gl.uniform_1_f32(uniform(name).as_ref(), value);
+
+            int("shadow_cascades", CASCADE_UNIT as i32);
+            int("point_shadow_map", POINT_SHADOW_UNIT as i32);
+            let index = |light: Option<usize>, rendered: bool| match light {
+                Some(i) if rendered => i as i32 + 1,
+                _ => 0,
+            };
+            let cascade_count = self.cascades.len();
+            int(
+                "directional_shadow",
+                index(self.directional, cascade_count > 0),
+            );
+            int("point_shadow", index(self.point, self.point_rendered));
+            int("cascade_count", cascade_count as i32);
+            int("shadow_pcf_radius", self.settings.pcf_radius.max(0));
+            int("show_cascades", self.tint_cascades as i32);
+            float("point_shadow_far", self.settings.point_far);
+            float("shadow_constant_bias", self.settings.constant_bias);
+            float("shadow_slope_bias", self.settings.slope_bias);
+            float("shadow_max_bias", self.settings.max_bias);
+
+            if cascade_count > 0 {
+                let matrices: Vec<f32> = self
+                    .cascades
+                    .iter()
+                    .flat_map(|cascade| cascade.matrix.as_slice().to_vec())
+                    .collect();
+                let far: Vec<f32> = self.cascades.iter().map(|cascade| cascade.far).collect();
+                let depth: Vec<f32> = self.cascades.iter().map(|cascade| cascade.depth).collect();
+                let location = uniform("cascade_matrices");
+                gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &matrices);
+                gl.uniform_1_f32_slice(uniform("cascade_far").as_ref(), &far);
+                gl.uniform_1_f32_slice(uniform("cascade_depth").as_ref(), &depth);
+            }
+
+            self.cascade_map.bind(gl, CASCADE_UNIT);
+            self.point_map.bind(gl, POINT_SHADOW_UNIT);
+            gl.active_texture(glow::TEXTURE0);
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe { gl.delete_program(self.program) };
+        self.cascade_map.delete(gl);
+        self.point_map.delete(gl);
+    }
+}
+
+/// Which depth map `ShadowDebug` shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowView {
+    Cascade(usize),
+    /// Unwrapped like an equirectangular map.
+    Point,
+}
+
+impl ShadowView {
+    /// Off, every cascade, the point light, then off again.
+    pub fn cycle(view: Option<Self>, cascades: usize) -> Option<Self> {
+        match view {
+            None if cascades > 0 => Some(Self::Cascade(0)),
+            None => Some(Self::Point),
+            Some(Self::Cascade(i)) if i + 1 < cascades => Some(Self::Cascade(i + 1)),
+            Some(Self::Cascade(_)) => Some(Self::Point),
+            Some(Self::Point) => None,
+        }
+    }
+}
+
+/// Draws a shadow map into a rectangle of the current framebuffer, white is far.
+#[derive(Debug)]
+pub struct ShadowDebug {
+    pub program: NativeProgram,
+    vao: NativeVertexArray,
+}
+
+impl ShadowDebug {
+    pub fn new(gl: &Context) -> Result<Self, String> {
+        unsafe {
+            let program = try_program(gl, FULLSCREEN_VERTEX, SHADOW_DEBUG_FRAGMENT)?;
+            for (name, unit) in [("cascades", CASCADE_UNIT), ("point_map", POINT_SHADOW_UNIT)] {
+                let location = gl.get_uniform_location(program, name);
+                gl.uniform_1_i32(location.as_ref(), unit as i32);
+            }
+            //Core profiles need some vertex array bound, even without attributes.
+            let vao = gl.create_vertex_array()?;
+            Ok(Self { program, vao })
+        }
+    }
+
+    /// `viewport` is x, y, width and height in pixels. Binds the maps to their units.
+    pub fn draw(&self, gl: &Context, shadows: &Shadows, view: ShadowView, viewport: [i32; 4]) {
+        unsafe {
+            let state = RenderState::save(gl);
+            let depth_test = gl.is_enabled(glow::DEPTH_TEST);
+            gl.disable(glow::DEPTH_TEST);
+            let [x, y, width, height] = viewport;
+            gl.viewport(x, y, width, height);
+
+            gl.use_program(Some(self.program));
+            let (mode, layer) = match view {
+                ShadowView::Cascade(layer) => (0, layer as i32),
+                ShadowView::Point => (1, 0),
+            };
+            let uniform = |name: &str| gl.get_uniform_location(self.program, name);
+            gl.uniform_1_i32(uniform("mode").as_ref(), mode);
+            gl.uniform_1_i32(uniform("layer").as_ref(), layer);
+            shadows.cascade_map.bind(gl, CASCADE_UNIT);
+            shadows.point_map.bind(gl, POINT_SHADOW_UNIT);
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_vertex_array(Some(self.vao));
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+
+            state.restore(gl);
+            if depth_test {
+                gl.enable(glow::DEPTH_TEST);
+            }
+        }
+    }
+
+    pub fn delete(self, gl: &Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vao);
+        }
+    }
+}
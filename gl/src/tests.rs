@@ -0,0 +1,916 @@
+use crate::*;
+
+const OBJECTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/objects");
+
+/// Every index points at a vertex and every vertex has a normal.
+fn check_mesh(mesh: &Mesh) {
+    assert_eq!(mesh.indices.len() % 3, 0);
+    assert!(mesh
+        .indices
+        .iter()
+        .all(|&i| (i as usize) < mesh.vertices.len()));
+    assert!(mesh.vertices.len() <= mesh.indices.len());
+
+    let covered: usize = mesh.submeshes.iter().map(|s| s.indices.len()).sum();
+    assert_eq!(covered, mesh.indices.len());
+    for vertex in &mesh.vertices {
+        let normal = glm::Vec3::from(vertex.normal);
+        assert!((normal.norm() - 1.0).abs() < 0.01, "{:?}", vertex);
+        let tangent = glm::Vec4::from(vertex.tangent).xyz();
+        assert!((tangent.norm() - 1.0).abs() < 0.01, "{:?}", vertex);
+        assert!(tangent.dot(&normal).abs() < 0.01, "{:?}", vertex);
+        assert!(vertex.tangent[3].abs() == 1.0, "{:?}", vertex);
+    }
+}
+
+#[test]
+fn obj_rock() {
+    let model = load_obj(format!("{OBJECTS}/rock/rock.obj")).unwrap();
+    assert_eq!(model.material_libraries, ["rock.mtl"]);
+    assert_eq!(model.meshes.len(), 1);
+
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.name, "Cube");
+    assert_eq!(mesh.indices.len(), 192 * 3);
+    assert_eq!(
+        mesh.submeshes,
+        [Submesh {
+            material: Some(0),
+            indices: 0..192 * 3
+        }]
+    );
+    check_mesh(mesh);
+    //Corners shared between faces were merged.
+    assert!(mesh.vertices.len() < mesh.indices.len() / 2);
+
+    //f 30/1/1 3/2/2 44/3/3
+    assert_eq!(
+        mesh.vertices[mesh.indices[1] as usize].position,
+        [-1.052088, -0.064600, 0.954513]
+    );
+
+    let material = &model.materials[0];
+    assert_eq!(material.name, "Material");
+    assert_eq!(material.shininess, 13.72549);
+    assert_eq!(material.specular, [0.007937; 3]);
+    assert_eq!(material.diffuse_map, None);
+    let normal_map = material.normal_map.as_ref().unwrap();
+    assert_eq!(normal_map, &Path::new(OBJECTS).join("rock/rock.png"));
+    assert!(normal_map.exists());
+}
+
+#[test]
+fn obj_nanosuit() {
+    let model = load_obj(format!("{OBJECTS}/nanosuit/nanosuit.obj")).unwrap();
+    let names: Vec<&str> = model.meshes.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(
+        names,
+        ["Visor", "Legs", "hands", "Lights", "Arms", "Helmet", "Body"]
+    );
+
+    let materials: Vec<&str> = model.materials.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(materials, ["Glass", "Leg", "Hand", "Arm", "Helmet", "Body"]);
+
+    let faces: usize = model.meshes.iter().map(|m| m.indices.len() / 3).sum();
+    assert_eq!(faces, 19058);
+    model.meshes.iter().for_each(check_mesh);
+
+    for material in &model.materials {
+        assert_eq!(material.diffuse, [0.64; 3]);
+        assert!(material.diffuse_map.as_ref().unwrap().exists());
+        assert!(material.normal_map.as_ref().unwrap().exists());
+    }
+}
+
+#[test]
+fn obj_cyborg() {
+    let model = load_obj(format!("{OBJECTS}/cyborg/cyborg.obj")).unwrap();
+    assert_eq!(model.meshes.len(), 1);
+    assert_eq!(model.meshes[0].indices.len(), 5549 * 3);
+    check_mesh(&model.meshes[0]);
+
+    let material = &model.materials[0];
+    assert_eq!(material.diffuse, [0.512; 3]);
+    assert!(material
+        .diffuse_map
+        .as_ref()
+        .unwrap()
+        .ends_with("cyborg/cyborg_diffuse.png"));
+    assert!(material.specular_map.as_ref().unwrap().exists());
+}
+
+#[test]
+fn obj_polygons() {
+    let source = "
+mtllib a.mtl b.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+g top side
+usemtl red
+f 1/1 2/2 3/3 4/4
+usemtl blue
+f -4 -2 -1
+usemtl red
+f 1/1 3/3 4/4
+";
+    let model = parse_obj(source, "quad.obj").unwrap();
+    assert_eq!(model.material_libraries, ["a.mtl", "b.mtl"]);
+    assert_eq!(model.materials[1], Material::new("blue"));
+
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.groups, ["top", "side"]);
+    //The quad is split into a fan and the last face reuses its vertices.
+    assert_eq!(mesh.vertices.len(), 7);
+    assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 0, 2, 3, 4, 5, 6]);
+    assert_eq!(mesh.submeshes[0].indices, 0..9);
+    assert_eq!(mesh.submeshes[1].indices, 9..12);
+    //Faces without normals get one generated.
+    assert_eq!(mesh.vertices[0].normal, [0.0, 0.0, 1.0]);
+
+    let error = parse_obj("v 0 0 0\nf 1 2 3", "bad.obj").unwrap_err();
+    assert_eq!(error.to_string(), "bad.obj:2: index 2 is out of range");
+}
+
+#[test]
+fn mtl() {
+    let source = "
+Kd 1 1 1
+";
+    let error = parse_mtl(source, Path::new("."), "bad.mtl").unwrap_err();
+    assert_eq!(error.to_string(), "bad.mtl:2: 'Kd' before newmtl");
+
+    let source = "
+newmtl Glass
+Kd 0.1 0.2 0.3
+Tr 0.25
+map_Kd -s 1 1 1 textures\\glass.png
+norm glass_normal.png
+";
+    let materials = parse_mtl(source, Path::new("models"), "glass.mtl").unwrap();
+    let glass = &materials[0];
+    assert_eq!(glass.diffuse, [0.1, 0.2, 0.3]);
+    assert_eq!(glass.opacity, 0.75);
+    assert_eq!(
+        glass.diffuse_map.as_deref(),
+        Some(Path::new("models/textures/glass.png"))
+    );
+    assert_eq!(
+        glass.normal_map.as_deref(),
+        Some(Path::new("models/glass_normal.png"))
+    );
+
+    //Every map reaches the Phong material, parallax needs `disp`.
+    let source = "
+newmtl Bricks
+map_Kd bricks.jpg
+map_Ks bricks_specular.jpg
+norm bricks_normal.jpg
+map_Ke bricks_emission.jpg
+disp bricks_disp.jpg
+";
+    let bricks = &parse_mtl(source, Path::new(""), "bricks.mtl").unwrap()[0];
+    let mut loaded = Vec::new();
+    let phong = PhongMaterial::from_mtl_with(bricks, |path| {
+        loaded.push(path.to_path_buf());
+        Some(NativeTexture(
+            std::num::NonZeroU32::new(loaded.len() as u32).unwrap(),
+        ))
+    });
+    let texture = |id: u32| Some(NativeTexture(std::num::NonZeroU32::new(id).unwrap()));
+    assert_eq!(phong.diffuse_map, texture(1));
+    assert_eq!(phong.normal_map, texture(3));
+    assert_eq!(phong.displacement_map, texture(5));
+    assert_eq!(loaded[4], Path::new("bricks_disp.jpg"));
+}
+
+#[test]
+fn json() {
+    let value = json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "\"é😀\n"}} "#).unwrap();
+    assert_eq!(value.get("a").as_array().len(), 4);
+    assert_eq!(value.get("a").as_array()[1].as_f32(), Some(-25.0));
+    assert_eq!(value.get("b").get("c").as_str(), Some("\"é😀\n"));
+    assert!(value.get("missing").get("nested").is_null());
+
+    assert_eq!(
+        json::parse("[1, 2").unwrap_err(),
+        "expected ',' or ']' at byte 5"
+    );
+    assert!(json::parse("{\"a\": 1} x").is_err());
+
+    //Deep nesting is an error, not a stack overflow.
+    let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+    assert!(json::parse(&nested(json::MAX_DEPTH)).is_ok());
+    assert_eq!(
+        json::parse(&nested(100_000)).unwrap_err(),
+        format!("nested too deeply at byte {}", json::MAX_DEPTH)
+    );
+}
+
+fn check_box(gltf: &Gltf) {
+    let names: Vec<&str> = gltf.nodes.iter().map(|n| n.name.as_str()).collect();
+    assert_eq!(names, ["Root", "Box", "Camera", "Unused"]);
+    assert_eq!(gltf.roots, [0]);
+    assert_eq!(gltf.nodes[0].children, [1, 2]);
+    assert_eq!(gltf.nodes[1].parent, Some(0));
+    assert_eq!(gltf.nodes[3].parent, None);
+    assert_eq!(gltf.nodes[1].mesh, Some(0));
+    assert_eq!(gltf.nodes[2].camera, Some(0));
+
+    //The camera is 5 units in front of the root, which is rotated 90 degrees and scaled by 2.
+    let world = gltf.world_matrices();
+    let camera = world[2] * glm::vec4(0.0, 0.0, 0.0, 1.0);
+    assert!(glm::distance(&camera.xyz(), &glm::vec3(10.0, 1.0, 0.0)) < 1e-5);
+    assert_eq!(gltf.nodes[2].translation, glm::vec3(0.0, 0.0, 5.0));
+    assert_eq!(gltf.nodes[2].scale, glm::vec3(1.0, 1.0, 1.0));
+
+    //Only the box is drawn, a mesh outside of the scene isn't.
+    assert_eq!(gltf.mesh_nodes(), [(1, world[1])]);
+    let mut outside = gltf.clone();
+    outside.nodes.push(GltfNode {
+        name: "Outside".to_string(),
+        mesh: Some(0),
+        ..outside.nodes[3].clone()
+    });
+    assert_eq!(outside.mesh_nodes(), [(1, world[1])]);
+
+    let mesh = &gltf.meshes[0];
+    assert_eq!(mesh.vertices.len(), 8);
+    //The strip of 4 vertices becomes 2 triangles.
+    assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 4, 5, 6, 6, 5, 7]);
+    assert_eq!(
+        mesh.submeshes,
+        [
+            Submesh {
+                material: Some(0),
+                indices: 0..6
+            },
+            Submesh {
+                material: Some(1),
+                indices: 6..12
+            }
+        ]
+    );
+    assert_eq!(mesh.vertices[0].uv, [0.0, 1.0]);
+    assert_eq!(mesh.vertices[2].uv, [1.0, 0.0]);
+    //Missing normals are generated.
+    assert_eq!(mesh.vertices[4].normal, [0.0, 0.0, 1.0]);
+
+    let checker = &gltf.materials[0];
+    assert_eq!(checker.name, "Checker");
+    assert_eq!(checker.base_color_texture.unwrap().texture, 0);
+    assert_eq!((checker.metallic, checker.roughness), (0.0, 0.5));
+    assert!(checker.double_sided);
+
+    let red = &gltf.materials[1];
+    assert_eq!(red.base_color, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(red.alpha_mode, AlphaMode::Mask(0.25));
+    assert_eq!(red.normal_scale, 0.5);
+    assert_eq!(red.emissive, [0.1, 0.0, 0.0]);
+
+    assert_eq!(gltf.textures[0].sampler.mag_filter, glow::NEAREST);
+    assert_eq!(gltf.textures[1].sampler, TextureSampler::default());
+
+    let checker = gltf.images[0].decode().unwrap();
+    assert_eq!(checker.dimensions(), (2, 2));
+    assert_eq!(checker.get_pixel(1, 0).0, [0, 0, 0, 255]);
+    let normal = gltf.images[1].decode().unwrap();
+    assert_eq!(normal.get_pixel(0, 0).0, [128, 128, 255, 255]);
+
+    assert_eq!(
+        gltf.cameras[0].projection,
+        Projection::Perspective {
+            yfov: 0.8,
+            aspect: Some(1.5),
+            znear: 0.1,
+            zfar: Some(100.0)
+        }
+    );
+    assert!(matches!(
+        gltf.cameras[1].projection,
+        Projection::Orthographic { xmag, .. } if xmag == 2.0
+    ));
+}
+
+#[test]
+fn gltf_separate() {
+    let gltf = load_gltf(format!("{OBJECTS}/box/box.gltf")).unwrap();
+    check_box(&gltf);
+    assert_eq!(
+        gltf.images[0].source,
+        ImageSource::Path(Path::new(OBJECTS).join("box/checker.png"))
+    );
+}
+
+#[test]
+fn gltf_binary() {
+    let glb = load_gltf(format!("{OBJECTS}/box/box.glb")).unwrap();
+    check_box(&glb);
+    assert!(matches!(
+        &glb.images[0].source,
+        ImageSource::Embedded { mime_type, .. } if mime_type == "image/png"
+    ));
+
+    let gltf = load_gltf(format!("{OBJECTS}/box/box.gltf")).unwrap();
+    assert_eq!(glb.meshes, gltf.meshes);
+    assert_eq!(glb.nodes, gltf.nodes);
+}
+
+#[test]
+fn gltf_errors() {
+    let error = |json: &str| parse_gltf(json, None, Path::new("")).unwrap_err();
+    assert_eq!(
+        error(r#"{"asset": {"version": "1.0"}}"#),
+        "unsupported glTF version '1.0'"
+    );
+    assert_eq!(
+        error(r#"{"asset": {"version": "2.0"}, "nodes": [{"children": [1]}, {"children": [0]}]}"#),
+        "node 0 is its own ancestor"
+    );
+    assert_eq!(
+        error(
+            r#"{"asset": {"version": "2.0"}, "nodes": [{"children": [2]}, {"children": [2]}, {}]}"#
+        ),
+        "node 2 has more than one parent"
+    );
+    assert_eq!(
+        error(r#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 4}]}"#),
+        "buffer 0 has no uri and there is no binary chunk"
+    );
+    assert_eq!(
+        error(r#"{"asset": {"version": "2.0"}, "nodes": [{"mesh": 0}]}"#),
+        "node 0 references a missing mesh or camera"
+    );
+    assert!(load_gltf(format!("{OBJECTS}/box/missing.gltf")).is_err());
+
+    //Three positions and 32 bit indices, the last one is 2^24 + 1 which f32 can't hold.
+    let mut bin = vec![0; 36];
+    for index in [0u32, 1, (1 << 24) + 1] {
+        bin.extend(index.to_le_bytes());
+    }
+    let mesh = |indices: &str| {
+        format!(
+            r#"{{"asset": {{"version": "2.0"}}, "buffers": [{{"byteLength": 48}}],
+            "bufferViews": [{{"buffer": 0, "byteLength": 36}}, {{"buffer": 0, "byteOffset": 36, "byteLength": 12}}],
+            "accessors": [{{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}}, {indices}],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}]}}"#
+        )
+    };
+    let error = |indices: &str| parse_gltf(&mesh(indices), Some(&bin), Path::new("")).unwrap_err();
+    assert_eq!(
+        error(r#"{"bufferView": 1, "componentType": 5125, "count": 3, "type": "SCALAR"}"#),
+        "mesh 0 has index 16777217 out of range"
+    );
+    assert_eq!(
+        error(r#"{"bufferView": 1, "componentType": 5126, "count": 3, "type": "SCALAR"}"#),
+        "accessor 1 isn't unsigned integers"
+    );
+    assert_eq!(
+        error(r#"{"bufferView": 1, "componentType": 5125, "count": 1e30, "type": "SCALAR"}"#),
+        "accessor 1 is out of bounds"
+    );
+    assert_eq!(
+        error(
+            r#"{"bufferView": 1, "byteOffset": 1e30, "componentType": 5125, "count": 3, "type": "SCALAR"}"#
+        ),
+        "accessor 1 is out of bounds"
+    );
+}
+
+#[test]
+fn scene_hierarchy() {
+    let mut scene = Scene::new();
+    let root = scene.add(
+        "Root",
+        Transform::from_translation(glm::vec3(1.0, 0.0, 0.0)),
+        None,
+    );
+    let child = scene.add(
+        "Child",
+        Transform::from_translation(glm::vec3(0.0, 2.0, 0.0)),
+        Some(root),
+    );
+    let leaf = scene.add("Leaf", Transform::default(), Some(child));
+    let other = scene.add("Other", Transform::default(), None);
+    assert_eq!(scene.update(), 4);
+    assert_eq!(scene.update(), 0);
+
+    let origin = |scene: &Scene, node: usize| {
+        (scene.nodes[node].world() * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz()
+    };
+    assert_eq!(origin(&scene, leaf), glm::vec3(1.0, 2.0, 0.0));
+
+    //Changing a parent updates everything below it and nothing else.
+    scene.transform_mut(root).scale = glm::vec3(2.0, 2.0, 2.0);
+    assert_eq!(scene.update(), 3);
+    assert_eq!(origin(&scene, leaf), glm::vec3(1.0, 4.0, 0.0));
+
+    scene.set_parent(child, Some(other));
+    assert_eq!(scene.roots, [root, other]);
+    assert_eq!(scene.nodes[other].children(), [child]);
+    assert_eq!(scene.update(), 2);
+    assert_eq!(origin(&scene, leaf), glm::vec3(0.0, 2.0, 0.0));
+
+    let cycle = std::panic::catch_unwind(move || scene.set_parent(other, Some(leaf)));
+    assert!(cycle.is_err());
+
+    let gltf = load_gltf(format!("{OBJECTS}/box/box.gltf")).unwrap();
+    let mut scene = Scene::from_gltf(&gltf);
+    scene.update();
+    assert_eq!(scene.roots, [0, 3]);
+    assert!(scene.nodes[3].hidden);
+    assert_eq!(scene.nodes[0].children(), [1, 2]);
+    for (i, world) in gltf.world_matrices().iter().enumerate().take(3) {
+        let expected = (world * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz();
+        assert!(glm::distance(&origin(&scene, i), &expected) < 1e-5);
+    }
+}
+
+#[test]
+fn frustum_culling() {
+    let rotated = Aabb::new(glm::vec3(-1.0, -1.0, -1.0), glm::vec3(1.0, 1.0, 1.0))
+        .transform(&glm::rotation(PI / 4.0, &glm::vec3(0.0, 0.0, 1.0)));
+    assert!((rotated.max.x - 2.0f32.sqrt()).abs() < 1e-5);
+    assert!((rotated.max.z - 1.0).abs() < 1e-5);
+
+    //Looking down -Z from the origin.
+    let projection = glm::perspective(1.0, PI / 2.0, 0.1, 100.0);
+    let frustum = Frustum::new(&projection);
+    assert!(frustum.contains(&glm::vec3(0.0, 0.0, -10.0)));
+    assert!(!frustum.contains(&glm::vec3(0.0, 0.0, 10.0)));
+    assert!(!frustum.contains(&glm::vec3(0.0, 0.0, -200.0)));
+    assert!(!frustum.contains(&glm::vec3(20.0, 0.0, -10.0)));
+
+    let unit = Aabb::new(glm::vec3(-0.5, -0.5, -0.5), glm::vec3(0.5, 0.5, 0.5));
+    let mut scene = Scene::new();
+    let parent = scene.add("Parent", Transform::default(), None);
+    let ahead = Transform::from_translation(glm::vec3(0.0, 0.0, -10.0));
+    let front = scene.add_mesh("Front", ahead, Some(parent), 0, Some(unit));
+    let behind = Transform::from_translation(glm::vec3(0.0, 0.0, 10.0));
+    let back = scene.add_mesh("Back", behind, Some(parent), 0, Some(unit));
+    //Partially inside of the left plane.
+    let edge = Transform::from_translation(glm::vec3(-10.3, 0.0, -10.0));
+    let edge = scene.add_mesh("Edge", edge, Some(parent), 0, Some(unit));
+    let unbounded = scene.add_mesh("Unbounded", behind, None, 0, None);
+    scene.update();
+    assert_eq!(scene.visible(&projection), [front, edge, unbounded]);
+
+    //Turning the parent around swaps what's visible.
+    scene.transform_mut(parent).rotation = glm::quat_angle_axis(PI, &glm::vec3(0.0, 1.0, 0.0));
+    scene.update();
+    assert_eq!(scene.visible(&projection), [back, unbounded]);
+
+    scene.nodes[parent].hidden = true;
+    assert_eq!(scene.visible(&projection), [unbounded]);
+}
+
+#[test]
+fn camera_controllers() {
+    use glfw::{Action, Key, Modifiers, MouseButton, WindowEvent};
+
+    //45 degrees, not 45 radians.
+    let mut camera = Camera::perspective(45f32.to_radians(), 0.1, 100.0);
+    let projection = camera.projection(1.0);
+    assert!((projection[(1, 1)] - 1.0 / 22.5f32.to_radians().tan()).abs() < 1e-5);
+
+    let (yaw, pitch) = yaw_pitch(&glm::vec3(0.0, 0.0, -1.0));
+    assert!((yaw + PI / 2.0).abs() < 1e-5 && pitch == 0.0);
+    assert!(glm::distance(&direction(yaw, pitch), &camera.front) < 1e-5);
+
+    let key =
+        |key, action| WindowEvent::Key(key, glfw::Scancode::default(), action, Modifiers::empty());
+    let mut fly = FlyController::new(&camera);
+    fly.event(&key(Key::W, Action::Press));
+    fly.event(&key(Key::D, Action::Press));
+    fly.event(&key(Key::D, Action::Release));
+    fly.update(&mut camera, 0.5);
+    assert!(glm::distance(&camera.position, &glm::vec3(0.0, 0.0, -2.5)) < 1e-5);
+
+    //The first cursor event only sets the starting point, looking straight up is clamped.
+    fly.event(&WindowEvent::CursorPos(0.0, 0.0));
+    fly.event(&WindowEvent::CursorPos(0.0, -10000.0));
+    fly.update(&mut camera, 0.0);
+    assert_eq!(fly.pitch, fly.pitch_limit);
+    assert!(camera.front.y > 0.99);
+
+    //Without smoothing the velocity is immediate, with it it builds up.
+    fly.smoothing = 0.1;
+    fly.event(&WindowEvent::Focus(false));
+    fly.update(&mut camera, 1.0);
+    let position = camera.position;
+    fly.event(&key(Key::S, Action::Press));
+    fly.update(&mut camera, 0.01);
+    let moved = glm::distance(&position, &camera.position);
+    assert!(moved > 0.0 && moved < fly.speed * 0.01);
+
+    let mut orbit = OrbitController::new(glm::vec3(1.0, 0.0, 0.0), 5.0);
+    orbit.update(&mut camera, 0.0);
+    assert!(glm::distance(&camera.position, &glm::vec3(1.0, 0.0, 5.0)) < 1e-5);
+    assert!(glm::distance(&camera.front, &glm::vec3(0.0, 0.0, -1.0)) < 1e-5);
+
+    orbit.event(&WindowEvent::Scroll(0.0, 100.0));
+    assert_eq!(orbit.distance, orbit.min_distance);
+    orbit.distance = 5.0;
+
+    //Dragging down raises the camera, it keeps its distance and looks at the target.
+    orbit.event(&WindowEvent::CursorPos(0.0, 0.0));
+    orbit.event(&WindowEvent::MouseButton(
+        MouseButton::Button1,
+        Action::Press,
+        Modifiers::empty(),
+    ));
+    orbit.event(&WindowEvent::CursorPos(0.0, 100.0));
+    orbit.update(&mut camera, 0.0);
+    assert!(camera.position.y > 0.0);
+    assert!((glm::distance(&camera.position, &orbit.target) - 5.0).abs() < 1e-4);
+    assert!(glm::distance(&(camera.position + camera.front * 5.0), &orbit.target) < 1e-4);
+
+    let from_camera = OrbitController::from_camera(&camera, 5.0);
+    let before = camera;
+    from_camera.clone().update(&mut camera, 0.0);
+    assert!(glm::distance(&before.position, &camera.position) < 1e-4);
+}
+
+#[test]
+fn lights() {
+    let float = |data: &[u8], offset: usize| {
+        f32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+    };
+
+    let mut lights = Lights::new(glm::vec3(0.1, 0.2, 0.3));
+    lights
+        .push(Light::Directional {
+            direction: glm::vec3(0.0, -2.0, 0.0),
+            color: glm::vec3(1.0, 0.5, 0.0),
+            intensity: 2.0,
+        })
+        .unwrap();
+    let spot = Light::Spot {
+        position: glm::vec3(1.0, 2.0, 3.0),
+        direction: glm::vec3(0.0, 0.0, -1.0),
+        color: glm::vec3(1.0, 1.0, 1.0),
+        intensity: 1.0,
+        attenuation: Attenuation::range(10.0),
+        inner: 0.0,
+        outer: PI / 2.0,
+    };
+    assert_eq!(lights.push(spot), Ok(1));
+
+    let data = lights.std140().unwrap();
+    assert_eq!(data.len(), LightBuffer::SIZE);
+    assert_eq!(float(&data, 4), 0.2);
+    assert_eq!(i32::from_ne_bytes(data[12..16].try_into().unwrap()), 2);
+    //Directions are normalized and colors are multiplied by the intensity.
+    assert_eq!(float(&data, 16 + 16 + 4), -1.0);
+    assert_eq!(float(&data, 16 + 32), 2.0);
+    assert_eq!(float(&data, 16 + 32 + 4), 1.0);
+    //The second light starts 80 bytes later, its type is in position.w.
+    assert_eq!(float(&data, 96), 1.0);
+    assert_eq!(float(&data, 96 + 12), 2.0);
+    assert_eq!(float(&data, 96 + 64), 1.0);
+    assert!(float(&data, 96 + 68).abs() < 1e-6);
+
+    let attenuation = Attenuation::range(10.0);
+    assert_eq!(attenuation.at(0.0), 1.0);
+    assert!(attenuation.at(10.0) < 0.015);
+
+    //Lights past the limit are refused, never dropped.
+    while lights.lights.len() < MAX_LIGHTS {
+        lights.push(spot).unwrap();
+    }
+    let error = LightError::TooMany {
+        count: MAX_LIGHTS + 1,
+        max: MAX_LIGHTS,
+    };
+    assert_eq!(lights.push(spot), Err(error.clone()));
+    assert_eq!(lights.lights.len(), MAX_LIGHTS);
+    lights.lights.push(spot);
+    assert_eq!(lights.std140(), Err(error));
+    assert_eq!(
+        lights.std140().unwrap_err().to_string(),
+        format!(
+            "{} lights exceeds the limit of {}",
+            MAX_LIGHTS + 1,
+            MAX_LIGHTS
+        )
+    );
+
+    let source = with_light_limit("#version 330 core\nvoid main() {}");
+    assert_eq!(
+        source,
+        format!("#version 330 core\n#define MAX_LIGHTS {MAX_LIGHTS}\nvoid main() {{}}")
+    );
+    //The shader's struct has to match.
+    let fragment = std::fs::read_to_string(FRAGMENT_PATH).unwrap();
+    assert!(fragment.contains("Light lights[MAX_LIGHTS];"));
+}
+
+#[test]
+fn ibl_cache() {
+    let dir = std::env::temp_dir().join(format!("gl_ibl_cache_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    //Six 2x2 faces, RGBE keeps about 8 bits of mantissa.
+    let size = 2;
+    let rgb: Vec<f32> = (0..size * size * 6 * 3).map(|i| i as f32 * 0.5).collect();
+    let path = dir.join("faces.hdr");
+    write_hdr(&path, size, size * 6, &rgb).unwrap();
+    let read = read_hdr(&path, size, size * 6).unwrap();
+    assert_eq!(read.len(), rgb.len());
+    for (a, b) in read.iter().zip(&rgb) {
+        assert!((a - b).abs() <= b * 0.01, "{} != {}", a, b);
+    }
+    let error = read_hdr(&path, size, size).unwrap_err();
+    assert!(error.ends_with("expected 2x2, found 2x12"), "{}", error);
+
+    let settings = IblSettings::default();
+    let key = cache_key(&path, &settings).unwrap();
+    assert_eq!(cache_key(&path, &settings).unwrap(), key);
+    let smaller = IblSettings {
+        irradiance_size: 16,
+        ..settings
+    };
+    assert_ne!(cache_key(&path, &smaller).unwrap(), key);
+    write_hdr(&path, size, size, &rgb[..(size * size * 3) as usize]).unwrap();
+    assert_ne!(cache_key(&path, &settings).unwrap(), key);
+    assert!(cache_key(&dir.join("missing.hdr"), &settings).is_err());
+
+    //A small BRDF LUT like the GPU's, blue is 0 when it's read back.
+    let size = 16;
+    let lut: Vec<f32> = (0..size * size)
+        .flat_map(|i| {
+            let n_dot_v = ((i % size) as f32 + 0.5) / size as f32;
+            let roughness = ((i / size) as f32 + 0.5) / size as f32;
+            let [scale, bias] = integrate_brdf(n_dot_v, roughness, 256);
+            [scale, bias, 0.0]
+        })
+        .collect();
+    let path = dir.join("brdf_lut.f32");
+    write_floats(&path, &lut).unwrap();
+    assert_eq!(read_floats(&path, lut.len()).unwrap(), lut);
+    assert!(read_floats(&path, lut.len() - 1).is_err());
+    //What RGBE used to do to the bias.
+    let path = dir.join("brdf_lut.hdr");
+    write_hdr(&path, size, size, &lut).unwrap();
+    assert_ne!(read_hdr(&path, size, size).unwrap(), lut);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// `integrate_brdf` from `brdf_fragment.glsl`.
+fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: u32) -> [f32; 2] {
+    let v = glm::vec3((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+    let k = roughness * roughness / 2.0;
+    let schlick = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    let a = roughness * roughness;
+    let (mut scale, mut bias) = (0.0, 0.0);
+    for i in 0..samples {
+        //Hammersley point and a GGX distributed half vector around +z.
+        let xi = (
+            i as f32 / samples as f32,
+            i.reverse_bits() as f32 * 2.328_306_4e-10,
+        );
+        let phi = 2.0 * PI * xi.0;
+        let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let h = glm::vec3(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+        let l = (2.0 * v.dot(&h) * h - v).normalize();
+
+        let (n_dot_l, n_dot_h, v_dot_h) = (l.z.max(0.0), h.z.max(0.0), v.dot(&h).max(0.0));
+        if n_dot_l > 0.0 {
+            let g = schlick(n_dot_v) * schlick(n_dot_l);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fc = (1.0 - v_dot_h).powi(5);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    [scale / samples as f32, bias / samples as f32]
+}
+
+#[test]
+fn sphere() {
+    let mesh = Mesh::sphere(16, 8);
+    check_mesh(&mesh);
+    assert_eq!(mesh.vertices.len(), 17 * 9);
+    assert_eq!(mesh.indices.len(), 16 * 8 * 6);
+    for v in &mesh.vertices {
+        let position = glm::Vec3::from(v.position);
+        assert!((position.norm() - 1.0).abs() < 1e-5);
+        assert_eq!(v.position, v.normal);
+    }
+    //Counter-clockwise from outside, so the face normals point away from the center.
+    for triangle in mesh.indices.chunks(3) {
+        let [a, b, c] =
+            [0, 1, 2].map(|i| glm::Vec3::from(mesh.vertices[triangle[i] as usize].position));
+        let normal = (b - a).cross(&(c - a));
+        if normal.norm() > 1e-6 {
+            assert!(normal.dot(&(a + b + c)) > 0.0);
+        }
+    }
+}
+
+#[test]
+fn cubemap() {
+    let skybox = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/textures/skybox");
+    let paths = face_paths(&skybox, "jpg");
+    assert_eq!(paths[0], skybox.join("right.jpg"));
+    assert_eq!(paths[5], skybox.join("back.jpg"));
+    assert!(paths.iter().all(|path| path.exists()));
+
+    assert_eq!(mip_levels(1), 1);
+    assert_eq!(mip_levels(512), 10);
+    assert_eq!(mip_levels(100), 7);
+
+    //Each view looks down its face's axis, in +X, -X, +Y, -Y, +Z, -Z order.
+    let axes = [
+        glm::vec3(1.0, 0.0, 0.0),
+        glm::vec3(-1.0, 0.0, 0.0),
+        glm::vec3(0.0, 1.0, 0.0),
+        glm::vec3(0.0, -1.0, 0.0),
+        glm::vec3(0.0, 0.0, 1.0),
+        glm::vec3(0.0, 0.0, -1.0),
+    ];
+    for (view, axis) in capture_views().iter().zip(axes) {
+        let forward = (view * axis.push(0.0)).xyz();
+        assert!((forward - glm::vec3(0.0, 0.0, -1.0)).norm() < 1e-6);
+    }
+    //The +Z face has s along +X and t along -Y.
+    let right = (capture_views()[4] * glm::vec4(1.0, 0.0, 0.0, 0.0)).xyz();
+    assert!((right - glm::vec3(1.0, 0.0, 0.0)).norm() < 1e-6);
+    let up = (capture_views()[4] * glm::vec4(0.0, -1.0, 0.0, 0.0)).xyz();
+    assert!((up - glm::vec3(0.0, 1.0, 0.0)).norm() < 1e-6);
+
+    let mut material = Material::new("glass");
+    assert_eq!(
+        EnvironmentMapping::from_mtl(&material),
+        EnvironmentMapping::None
+    );
+    material.illumination = 3;
+    material.specular = [0.2, 0.6, 0.4];
+    assert_eq!(
+        EnvironmentMapping::from_mtl(&material),
+        EnvironmentMapping::Reflect { amount: 0.6 }
+    );
+    material.illumination = 7;
+    material.optical_density = 1.25;
+    assert_eq!(
+        EnvironmentMapping::from_mtl(&material),
+        EnvironmentMapping::Refract {
+            ratio: 0.8,
+            amount: 1.0
+        }
+    );
+}
+
+#[test]
+fn shadow_cascades() {
+    let splits = cascade_splits(0.1, 50.0, 4, 0.75);
+    assert_eq!(splits.len(), 4);
+    assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((splits[3] - 50.0).abs() < 1e-4);
+    //Uniform and logarithmic at the ends of lambda.
+    assert!((cascade_splits(1.0, 100.0, 2, 0.0)[0] - 50.5).abs() < 1e-4);
+    assert!((cascade_splits(1.0, 100.0, 2, 1.0)[0] - 10.0).abs() < 1e-4);
+
+    let mut camera = Camera::perspective(45f32.to_radians(), 0.1, 100.0);
+    camera.position = glm::vec3(1.0, 2.0, 3.0);
+    let settings = ShadowSettings::default();
+    let direction = glm::vec3(-0.2, -1.0, -0.3);
+    let cascades = cascades(&camera, 16.0 / 9.0, &direction, &settings);
+    assert_eq!(cascades.len(), settings.cascade_count);
+    assert!((cascades.last().unwrap().far - settings.shadow_distance).abs() < 1e-3);
+
+    //Every slice of the view fits inside its cascade.
+    let mut near = 0.1;
+    for cascade in &cascades {
+        let projection = glm::perspective(16.0 / 9.0, 45f32.to_radians(), near, cascade.far);
+        let inverse = (projection * camera.view()).try_inverse().unwrap();
+        for corner in frustum_corners(&inverse) {
+            let ndc = cascade.matrix * corner.push(1.0);
+            let ndc = ndc.xyz() / ndc.w;
+            assert!(ndc.iter().all(|v| v.abs() <= 1.0 + 1e-4), "{:?}", ndc);
+        }
+        near = cascade.far;
+    }
+
+    //Moving the camera a few and a fraction of a texel moves everything in the map by whole
+    //texels, so shadow edges don't shimmer. Texels are about 0.18 units here.
+    let corners = frustum_corners(&(camera.view_projection(1.0).try_inverse().unwrap()));
+    let moved: Vec<glm::Vec3> = corners
+        .iter()
+        .map(|corner| corner + glm::vec3(0.53, 0.0, -0.31))
+        .collect();
+    let (matrix, _) = fit_light(&direction, &corners, 1024, 10.0);
+    let (moved_matrix, _) = fit_light(&direction, &moved, 1024, 10.0);
+    let rotation_scale = |m: &glm::Mat4| m.fixed_view::<3, 3>(0, 0).into_owned();
+    assert!((rotation_scale(&matrix) - rotation_scale(&moved_matrix)).norm() < 1e-6);
+    let mut shifted = false;
+    for point in [
+        glm::vec3(0.0, 0.0, 0.0),
+        glm::vec3(3.0, 1.0, -2.0),
+        glm::vec3(-5.0, 2.5, 7.0),
+    ] {
+        let texels = |m: &glm::Mat4| (m * point.push(1.0)).xy() * 512.0;
+        let delta = texels(&moved_matrix) - texels(&matrix);
+        assert!((delta - delta.map(f32::round)).norm() < 1e-2, "{:?}", delta);
+        shifted |= delta.norm() > 0.5;
+    }
+    assert!(shifted);
+
+    assert_eq!(ShadowView::cycle(None, 2), Some(ShadowView::Cascade(0)));
+    assert_eq!(
+        ShadowView::cycle(Some(ShadowView::Cascade(1)), 2),
+        Some(ShadowView::Point)
+    );
+    assert_eq!(ShadowView::cycle(Some(ShadowView::Point), 2), None);
+    assert_eq!(ShadowView::cycle(None, 0), Some(ShadowView::Point));
+
+    let source = with_light_limit(&with_shadows("#version 330 core\nvoid main() {}\n"));
+    assert!(source.starts_with("#version 330 core\n#define MAX_LIGHTS"));
+    assert!(source.contains(&format!("#define MAX_CASCADES {}\n", MAX_CASCADES)));
+    assert!(source.contains("float shadow("));
+}
+
+#[test]
+fn tangents() {
+    //Two quads facing +Z, the UVs of the right one are mirrored across the shared edge.
+    let vertex = |x: f32, y: f32, u: f32, v: f32| ModelVertex {
+        position: [x, y, 0.0],
+        uv: [u, v],
+        normal: [0.0, 0.0, 1.0],
+        ..Default::default()
+    };
+    let mut vertices = vec![
+        vertex(0.0, 0.0, 0.0, 0.0),
+        vertex(1.0, 0.0, 1.0, 0.0),
+        vertex(1.0, 1.0, 1.0, 1.0),
+        vertex(0.0, 1.0, 0.0, 1.0),
+        vertex(2.0, 0.0, 0.0, 0.0),
+        vertex(2.0, 1.0, 0.0, 1.0),
+    ];
+    let mut indices = vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+    generate_tangents(&mut vertices, &mut indices);
+
+    //The shared edge is split so each side keeps its own handedness.
+    assert_eq!(vertices.len(), 8);
+    assert_eq!(&indices[..6], &[0, 1, 2, 0, 2, 3]);
+    for &i in &indices[..6] {
+        assert_eq!(vertices[i as usize].tangent, [1.0, 0.0, 0.0, 1.0]);
+    }
+    for &i in &indices[6..] {
+        assert_eq!(vertices[i as usize].tangent, [-1.0, 0.0, 0.0, -1.0]);
+        //The bitangent still follows +v.
+        let t = vertices[i as usize].tangent;
+        let bitangent = glm::vec3(0.0, 0.0, 1.0).cross(&glm::vec3(t[0], t[1], t[2])) * t[3];
+        assert!((bitangent - glm::vec3(0.0, 1.0, 0.0)).norm() < 1e-6);
+    }
+    assert_eq!(vertices[6].position, vertices[1].position);
+
+    //Without UVs any perpendicular tangent will do.
+    let mut flat = vec![vertex(0.0, 0.0, 0.0, 0.0); 3];
+    flat[1].position = [1.0, 0.0, 0.0];
+    flat[2].position = [0.0, 1.0, 0.0];
+    generate_tangents(&mut flat, &mut [0, 1, 2]);
+    assert!(flat.iter().all(|v| v.tangent == [1.0, 0.0, 0.0, 1.0]));
+
+    assert_eq!(ParallaxMode::Off.next(), ParallaxMode::Steep);
+    assert_eq!(ParallaxMode::Occlusion.next(), ParallaxMode::Off);
+}
+
+#[test]
+fn post_chain() {
+    assert_eq!(
+        bloom_sizes(1280, 720, 5),
+        vec![(640, 360), (320, 180), (160, 90), (80, 45), (40, 22)]
+    );
+    //Stops before either side drops below 2 pixels.
+    assert_eq!(bloom_sizes(64, 9, 8), vec![(32, 4), (16, 2)]);
+    assert!(bloom_sizes(3, 3, 8).is_empty());
+
+    //Bloom needs the bright pass before it, tone mapping before gamma and FXAA after both.
+    let names: Vec<_> = default_passes().iter().map(|p| p.effect.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "Bright pass",
+            "Bloom",
+            "Exposure",
+            "Tone mapping",
+            "Gamma",
+            "FXAA",
+            "Vignette"
+        ]
+    );
+    assert!(default_passes().iter().all(|p| p.enabled));
+
+    assert_eq!(ToneMapper::default(), ToneMapper::Aces);
+    assert_eq!(ToneMapper::Filmic.next(), ToneMapper::Reinhard);
+    //The shader's REINHARD, ACES and FILMIC.
+    assert_eq!(ToneMapper::Filmic as i32, 2);
+}
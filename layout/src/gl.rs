@@ -0,0 +1,55 @@
+use crate::*;
+use glow::HasContext;
+
+impl ComponentType {
+    pub const fn gl(self) -> u32 {
+        match self {
+            ComponentType::F32 => glow::FLOAT,
+            ComponentType::I8 => glow::BYTE,
+            ComponentType::U8 => glow::UNSIGNED_BYTE,
+            ComponentType::I16 => glow::SHORT,
+            ComponentType::U16 => glow::UNSIGNED_SHORT,
+            ComponentType::I32 => glow::INT,
+            ComponentType::U32 => glow::UNSIGNED_INT,
+        }
+    }
+}
+
+/// Points the attributes of `V` at the buffer bound to `ARRAY_BUFFER`.
+pub fn bind<V: VertexLayout>(gl: &glow::Context) {
+    bind_at::<V>(gl, 0);
+}
+
+/// Same as `bind` but the data starts `offset` bytes into the buffer.
+/// Useful for instance data, since GL 3.3 has no base instance.
+pub fn bind_at<V: VertexLayout>(gl: &glow::Context, offset: u32) {
+    for attribute in V::ATTRIBUTES {
+        pointer(gl, attribute, offset);
+    }
+}
+
+pub fn pointer(gl: &glow::Context, attribute: &VertexAttribute, offset: u32) {
+    let (location, size, ty) = (
+        attribute.location,
+        attribute.components as i32,
+        attribute.ty.gl(),
+    );
+    let (stride, offset) = (attribute.stride as i32, (offset + attribute.offset) as i32);
+
+    unsafe {
+        gl.enable_vertex_attrib_array(location);
+        //Integers that aren't normalized need the `I` variant, otherwise they're converted to floats.
+        if attribute.ty.is_integer() && !attribute.normalized {
+            gl.vertex_attrib_pointer_i32(location, size, ty, stride, offset);
+        } else {
+            gl.vertex_attrib_pointer_f32(location, size, ty, attribute.normalized, stride, offset);
+        }
+    }
+}
+
+/// Sets the divisor of every attribute in `V`, 1 for per instance data.
+pub fn divisor<V: VertexLayout>(gl: &glow::Context, divisor: u32) {
+    for attribute in V::ATTRIBUTES {
+        unsafe { gl.vertex_attrib_divisor(attribute.location, divisor) };
+    }
+}
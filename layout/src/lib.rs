@@ -0,0 +1,106 @@
+//! Vertex attribute descriptors generated by `#[derive(VertexLayout)]`.
+//!
+//! ```rs
+//! #[repr(C)]
+//! #[derive(VertexLayout)]
+//! pub struct Vertex {
+//!     pub position: [f32; 3],
+//!     #[layout(normalized)]
+//!     pub color: [u8; 4],
+//! }
+//!
+//! layout::gl::bind::<Vertex>(gl);
+//! ```
+pub use layout_derive::VertexLayout;
+
+//Lets the derive refer to `::layout` inside of this crate.
+extern crate self as layout;
+
+#[cfg(feature = "glow")]
+pub mod gl;
+#[cfg(feature = "ash")]
+pub mod vk;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ComponentType {
+    F32,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+}
+
+impl ComponentType {
+    pub const fn size(self) -> u32 {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::F32 | ComponentType::I32 | ComponentType::U32 => 4,
+        }
+    }
+
+    pub const fn is_integer(self) -> bool {
+        !matches!(self, ComponentType::F32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub location: u32,
+    /// 1 to 4.
+    pub components: u32,
+    pub ty: ComponentType,
+    /// Integers are mapped to 0..1 or -1..1 instead of being passed as is.
+    pub normalized: bool,
+    /// Bytes from the start of the vertex.
+    pub offset: u32,
+    /// Size of the whole vertex.
+    pub stride: u32,
+}
+
+/// Implemented by `#[derive(VertexLayout)]`.
+///
+/// Fields are assigned locations in declaration order starting at 0.
+/// `#[layout(location = 3)]` moves a field and every field after it.
+/// `#[layout(normalized)]` marks integer fields as normalized.
+/// `#[layout(skip)]` leaves padding or CPU-only fields out of the layout.
+pub trait VertexLayout {
+    const ATTRIBUTES: &'static [VertexAttribute];
+    const STRIDE: u32;
+}
+
+/// Types that can be used as vertex fields.
+pub trait Component {
+    const TYPE: ComponentType;
+    const COUNT: u32;
+}
+
+macro_rules! component {
+    ($($t:ty => $ty:ident),*) => {
+        $(
+            impl Component for $t {
+                const TYPE: ComponentType = ComponentType::$ty;
+                const COUNT: u32 = 1;
+            }
+            impl Component for [$t; 2] {
+                const TYPE: ComponentType = ComponentType::$ty;
+                const COUNT: u32 = 2;
+            }
+            impl Component for [$t; 3] {
+                const TYPE: ComponentType = ComponentType::$ty;
+                const COUNT: u32 = 3;
+            }
+            impl Component for [$t; 4] {
+                const TYPE: ComponentType = ComponentType::$ty;
+                const COUNT: u32 = 4;
+            }
+        )*
+    };
+}
+
+component!(f32 => F32, i8 => I8, u8 => U8, i16 => I16, u16 => U16, i32 => I32, u32 => U32);
@@ -0,0 +1,59 @@
+use crate::*;
+
+#[repr(C)]
+#[derive(VertexLayout)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    #[layout(skip)]
+    pub _padding: f32,
+    #[layout(location = 3, normalized)]
+    pub color: [u8; 4],
+    pub id: u32,
+}
+
+#[test]
+pub fn derive() {
+    assert_eq!(Vertex::STRIDE, 24);
+    assert_eq!(
+        Vertex::ATTRIBUTES,
+        [
+            VertexAttribute {
+                location: 0,
+                components: 3,
+                ty: ComponentType::F32,
+                normalized: false,
+                offset: 0,
+                stride: 24,
+            },
+            VertexAttribute {
+                location: 3,
+                components: 4,
+                ty: ComponentType::U8,
+                normalized: true,
+                offset: 16,
+                stride: 24,
+            },
+            VertexAttribute {
+                location: 4,
+                components: 1,
+                ty: ComponentType::U32,
+                normalized: false,
+                offset: 20,
+                stride: 24,
+            },
+        ]
+    );
+}
+
+#[cfg(feature = "ash")]
+#[test]
+pub fn vulkan() {
+    let attributes = crate::vk::attributes::<Vertex>(1);
+    assert_eq!(attributes[1].format, ash::vk::Format::R8G8B8A8_UNORM);
+    assert_eq!(attributes[2].format, ash::vk::Format::R32_UINT);
+    assert_eq!(attributes[2].binding, 1);
+    assert_eq!(
+        crate::vk::binding::<Vertex>(1, ash::vk::VertexInputRate::VERTEX).stride,
+        24
+    );
+}
@@ -0,0 +1,89 @@
+use crate::*;
+use ash::vk;
+
+impl VertexAttribute {
+    pub const fn format(&self) -> vk::Format {
+        use ComponentType::*;
+        match (self.ty, self.components, self.normalized) {
+            (F32, 1, _) => vk::Format::R32_SFLOAT,
+            (F32, 2, _) => vk::Format::R32G32_SFLOAT,
+            (F32, 3, _) => vk::Format::R32G32B32_SFLOAT,
+            (F32, 4, _) => vk::Format::R32G32B32A32_SFLOAT,
+
+            (I8, 1, false) => vk::Format::R8_SINT,
+            (I8, 2, false) => vk::Format::R8G8_SINT,
+            (I8, 3, false) => vk::Format::R8G8B8_SINT,
+            (I8, 4, false) => vk::Format::R8G8B8A8_SINT,
+            (I8, 1, true) => vk::Format::R8_SNORM,
+            (I8, 2, true) => vk::Format::R8G8_SNORM,
+            (I8, 3, true) => vk::Format::R8G8B8_SNORM,
+            (I8, 4, true) => vk::Format::R8G8B8A8_SNORM,
+
+            (U8, 1, false) => vk::Format::R8_UINT,
+            (U8, 2, false) => vk::Format::R8G8_UINT,
+            (U8, 3, false) => vk::Format::R8G8B8_UINT,
+            (U8, 4, false) => vk::Format::R8G8B8A8_UINT,
+            (U8, 1, true) => vk::Format::R8_UNORM,
+            (U8, 2, true) => vk::Format::R8G8_UNORM,
+            (U8, 3, true) => vk::Format::R8G8B8_UNORM,
+            (U8, 4, true) => vk::Format::R8G8B8A8_UNORM,
+
+            (I16, 1, false) => vk::Format::R16_SINT,
+            (I16, 2, false) => vk::Format::R16G16_SINT,
+            (I16, 3, false) => vk::Format::R16G16B16_SINT,
+            (I16, 4, false) => vk::Format::R16G16B16A16_SINT,
+            (I16, 1, true) => vk::Format::R16_SNORM,
+            (I16, 2, true) => vk::Format::R16G16_SNORM,
+            (I16, 3, true) => vk::Format::R16G16B16_SNORM,
+            (I16, 4, true) => vk::Format::R16G16B16A16_SNORM,
+
+            (U16, 1, false) => vk::Format::R16_UINT,
+            (U16, 2, false) => vk::Format::R16G16_UINT,
+            (U16, 3, false) => vk::Format::R16G16B16_UINT,
+            (U16, 4, false) => vk::Format::R16G16B16A16_UINT,
+            (U16, 1, true) => vk::Format::R16_UNORM,
+            (U16, 2, true) => vk::Format::R16G16_UNORM,
+            (U16, 3, true) => vk::Format::R16G16B16_UNORM,
+            (U16, 4, true) => vk::Format::R16G16B16A16_UNORM,
+
+            //Vulkan has no normalized 32 bit formats.
+            (I32, 1, _) => vk::Format::R32_SINT,
+            (I32, 2, _) => vk::Format::R32G32_SINT,
+            (I32, 3, _) => vk::Format::R32G32B32_SINT,
+            (I32, 4, _) => vk::Format::R32G32B32A32_SINT,
+            (U32, 1, _) => vk::Format::R32_UINT,
+            (U32, 2, _) => vk::Format::R32G32_UINT,
+            (U32, 3, _) => vk::Format::R32G32B32_UINT,
+            (U32, 4, _) => vk::Format::R32G32B32A32_UINT,
+
+            _ => vk::Format::UNDEFINED,
+        }
+    }
+
+    pub fn description(&self, binding: u32) -> vk::VertexInputAttributeDescription {
+        vk::VertexInputAttributeDescription {
+            location: self.location,
+            binding,
+            format: self.format(),
+            offset: self.offset,
+        }
+    }
+}
+
+pub fn attributes<V: VertexLayout>(binding: u32) -> Vec<vk::VertexInputAttributeDescription> {
+    V::ATTRIBUTES
+        .iter()
+        .map(|attribute| attribute.description(binding))
+        .collect()
+}
+
+pub fn binding<V: VertexLayout>(
+    binding: u32,
+    input_rate: vk::VertexInputRate,
+) -> vk::VertexInputBindingDescription {
+    vk::VertexInputBindingDescription {
+        binding,
+        stride: V::STRIDE,
+        input_rate,
+    }
+}
@@ -0,0 +1,104 @@
+//! `#[derive(VertexLayout)]`, see the `layout` crate.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt};
+
+#[proc_macro_derive(VertexLayout, attributes(layout))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "VertexLayout doesn't support generics",
+        ));
+    }
+
+    //Without `repr(C)` the compiler is free to reorder fields.
+    let mut repr_c = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            }
+            Ok(())
+        })?;
+    }
+    if !repr_c {
+        return Err(Error::new_spanned(
+            name,
+            "VertexLayout needs #[repr(C)] so field offsets are stable",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "VertexLayout needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "VertexLayout only works on structs",
+            ))
+        }
+    };
+
+    let mut location = 0u32;
+    let mut attributes = Vec::new();
+    for field in fields {
+        let (mut normalized, mut skip) = (false, false);
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("layout")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    location = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("normalized") {
+                    normalized = true;
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else {
+                    return Err(meta.error("expected `location = N`, `normalized` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        if skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        attributes.push(quote! {
+            ::layout::VertexAttribute {
+                location: #location,
+                components: <#ty as ::layout::Component>::COUNT,
+                ty: <#ty as ::layout::Component>::TYPE,
+                normalized: #normalized,
+                offset: ::core::mem::offset_of!(#name, #ident) as u32,
+                stride: ::core::mem::size_of::<#name>() as u32,
+            }
+        });
+        location += 1;
+    }
+
+    Ok(quote! {
+        impl ::layout::VertexLayout for #name {
+            const ATTRIBUTES: &'static [::layout::VertexAttribute] = &[#(#attributes),*];
+            const STRIDE: u32 = ::core::mem::size_of::<#name>() as u32;
+        }
+    })
+}
@@ -0,0 +1,69 @@
+//! Polls shader directories so `font` and `gl` can hot reload without depending on each other.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(test)]
+mod tests;
+
+/// Polls directories for modified files.
+/// Polling is used instead of inotify so it works the same on every platform.
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    pub directories: Vec<PathBuf>,
+    /// Minimum time between scans.
+    pub interval: Duration,
+    pub last_poll: Instant,
+    pub modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl ShaderWatcher {
+    pub fn new<P: Into<PathBuf>>(directories: impl IntoIterator<Item = P>) -> Self {
+        let directories: Vec<PathBuf> = directories.into_iter().map(Into::into).collect();
+        Self {
+            modified: scan(&directories),
+            directories,
+            interval: Duration::from_millis(250),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn watch(&mut self, directory: impl Into<PathBuf>) {
+        let directory = directory.into();
+        if !self.directories.contains(&directory) {
+            self.modified.extend(scan(std::slice::from_ref(&directory)));
+            self.directories.push(directory);
+        }
+    }
+
+    /// Files that were created or modified since the last scan.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let modified = scan(&self.directories);
+        let mut changed: Vec<PathBuf> = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.sort();
+        self.modified = modified;
+        changed
+    }
+}
+
+fn scan(directories: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    directories
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path(), modified))
+        })
+        .collect()
+}
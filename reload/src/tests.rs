@@ -0,0 +1,23 @@
+use crate::*;
+
+#[test]
+pub fn shader_watcher() {
+    let directory = std::env::temp_dir().join("reload_shader_watcher");
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    let file = directory.join("test.frag");
+    std::fs::write(&file, "void main() {}").unwrap();
+
+    let mut watcher = ShaderWatcher::new([&directory]);
+    watcher.interval = std::time::Duration::ZERO;
+    assert!(watcher.poll().is_empty());
+
+    //Don't rely on the file system's timestamp resolution.
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+    let f = std::fs::File::options().write(true).open(&file).unwrap();
+    f.set_modified(later).unwrap();
+    assert_eq!(watcher.poll(), std::slice::from_ref(&file));
+    assert!(watcher.poll().is_empty());
+
+    let _ = std::fs::remove_dir_all(&directory);
+}